@@ -0,0 +1,69 @@
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+/// One moved review, as written to the `--csv` audit file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRow {
+    pub note_id: i64,
+    pub card_id: i64,
+    pub deck_name: String, // Home deck, with `::` separators
+    pub old_review_millis: i64,
+    pub new_review_millis: i64,
+    pub offset_days: i64,
+}
+
+/// Writes `rows` to `path` as CSV with a header row, replacing any existing file. Fields are
+/// quoted where needed, so deck names may contain commas and quotes.
+pub fn write_csv(path: &Path, rows: &[AuditRow]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    if rows.is_empty() {
+        // Serializing writes the header with the first row, so write it explicitly here
+        writer.write_record([
+            "note_id",
+            "card_id",
+            "deck_name",
+            "old_review_millis",
+            "new_review_millis",
+            "offset_days",
+        ])?;
+    }
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_write_csv_quotes_deck_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.csv");
+        let row = |card_id, deck_name: &str| AuditRow {
+            note_id: 10,
+            card_id,
+            deck_name: deck_name.to_string(),
+            old_review_millis: 1_735_905_600_000,
+            new_review_millis: 1_735_819_200_000,
+            offset_days: 1,
+        };
+
+        write_csv(&path, &[row(100, "Spanish::Verbs"), row(101, "Words, \"common\"")]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "note_id,card_id,deck_name,old_review_millis,new_review_millis,offset_days\n\
+             10,100,Spanish::Verbs,1735905600000,1735819200000,1\n\
+             10,101,\"Words, \"\"common\"\"\",1735905600000,1735819200000,1\n"
+        );
+
+        write_csv(&path, &[]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "note_id,card_id,deck_name,old_review_millis,new_review_millis,offset_days\n"
+        );
+    }
+}
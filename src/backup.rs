@@ -0,0 +1,279 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns the file name prefix shared by every backup of `db_path`, e.g. `collection.anki2.bak-`.
+///
+/// Backups are named `<prefix><UTC timestamp>` with the timestamp formatted as `%Y%m%dT%H%M%S%3fZ`.
+pub fn backup_prefix(db_path: &Path) -> String {
+    let file_name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "collection.anki2".to_string());
+    format!("{}.bak-", file_name)
+}
+
+/// Builds the backup file name for `db_path` using the current UTC time, e.g.
+/// `collection.anki2.bak-20250103T101500123Z`.
+pub fn backup_file_name(db_path: &Path) -> String {
+    format!("{}{}", backup_prefix(db_path), Utc::now().format("%Y%m%dT%H%M%S%3fZ"))
+}
+
+/// Suffix of a backup that is still being copied. Only a crash leaves one behind.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Suffix given to partial or empty backups found from an earlier, interrupted run.
+const INCOMPLETE_SUFFIX: &str = ".incomplete";
+
+/// Suffix of the file next to each backup holding its SHA-256 checksum.
+const CHECKSUM_SUFFIX: &str = ".sha256";
+
+/// A backup that was written and checked against the collection.
+#[derive(Debug)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub sha256: String, // Lowercase hex, the same for the collection and the backup
+}
+
+/// Returns the SHA-256 checksum of the file at `path` as lowercase hex.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Path of the checksum file of the backup at `backup_path`.
+pub fn checksum_path(backup_path: &Path) -> PathBuf {
+    let mut name = backup_path.as_os_str().to_os_string();
+    name.push(CHECKSUM_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Copies `db_path` into `backup_dir` under a timestamped name and returns the backup.
+///
+/// `backup_dir` is created if it does not exist yet. The copy is written under a `.partial` name
+/// and only renamed into place once its size and SHA-256 checksum match the source, so an
+/// interrupted or corrupted copy never looks like a usable backup. The checksum is then written
+/// next to the backup in `sha256sum` format. An existing backup with the same name is never
+/// overwritten.
+pub fn create_backup(db_path: &Path, backup_dir: &Path) -> io::Result<Backup> {
+    fs::create_dir_all(backup_dir)?;
+    let backup_path = backup_dir.join(backup_file_name(db_path));
+    if backup_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Backup '{}' already exists", backup_path.display()),
+        ));
+    }
+
+    let mut partial_name = backup_path.as_os_str().to_os_string();
+    partial_name.push(PARTIAL_SUFFIX);
+    let partial_path = PathBuf::from(partial_name);
+
+    fs::copy(db_path, &partial_path)?;
+    let source_len = fs::metadata(db_path)?.len();
+    let backup_len = fs::metadata(&partial_path)?.len();
+    if source_len != backup_len {
+        fs::remove_file(&partial_path)?;
+        return Err(io::Error::other(format!(
+            "Backup size mismatch: expected {} bytes, copied {}",
+            source_len, backup_len
+        )));
+    }
+    let sha256 = sha256_file(db_path)?;
+    let backup_sha256 = sha256_file(&partial_path)?;
+    if sha256 != backup_sha256 {
+        fs::remove_file(&partial_path)?;
+        return Err(io::Error::other(format!(
+            "Backup checksum mismatch: the collection has SHA-256 {}, the copy {}",
+            sha256, backup_sha256
+        )));
+    }
+
+    fs::rename(&partial_path, &backup_path)?;
+    let file_name = backup_path.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(checksum_path(&backup_path), format!("{}  {}\n", sha256, file_name))?;
+    Ok(Backup { path: backup_path, sha256 })
+}
+
+/// Renames partial (`.partial`) and zero-byte backups of `db_path` left in `backup_dir` by an
+/// interrupted run, giving them an `.incomplete` suffix so they are neither mistaken for a good
+/// backup nor overwritten.
+///
+/// Returns the new paths of the files that were set aside.
+pub fn quarantine_incomplete_backups(backup_dir: &Path, db_path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = backup_prefix(db_path);
+    let mut quarantined = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(&prefix)
+            || name.ends_with(INCOMPLETE_SUFFIX)
+            || name.ends_with(CHECKSUM_SUFFIX)
+            || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        if name.ends_with(PARTIAL_SUFFIX) || entry.metadata()?.len() == 0 {
+            let target = backup_dir.join(format!("{}{}", name, INCOMPLETE_SUFFIX));
+            fs::rename(entry.path(), &target)?;
+            quarantined.push(target);
+        }
+    }
+    quarantined.sort();
+    Ok(quarantined)
+}
+
+/// Lists the backups of `db_path` found in `backup_dir`, oldest first, ignoring partial and
+/// incomplete copies and checksum files.
+///
+/// The timestamp suffix sorts lexicographically, so ordering by name is ordering by age.
+pub fn list_backups(backup_dir: &Path, db_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = backup_prefix(db_path);
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let in_progress = name.ends_with(PARTIAL_SUFFIX) || name.ends_with(INCOMPLETE_SUFFIX);
+        let checksum = name.ends_with(CHECKSUM_SUFFIX);
+        if name.starts_with(&prefix) && !in_progress && !checksum && entry.file_type()?.is_file() {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+/// Deletes the oldest backups of `db_path` in `backup_dir`, with their checksum files, so that at
+/// most `keep` remain.
+///
+/// Returns the paths of the backups that were removed.
+pub fn prune_backups(backup_dir: &Path, db_path: &Path, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let backups = list_backups(backup_dir, db_path)?;
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+    for path in &removed {
+        fs::remove_file(path)?;
+        let checksum = checksum_path(path);
+        if checksum.exists() {
+            fs::remove_file(checksum)?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_prefix_uses_collection_name() {
+        assert_eq!(backup_prefix(Path::new("/tmp/User 1/collection.anki2")), "collection.anki2.bak-");
+    }
+
+    #[test]
+    fn test_backup_file_name_uses_collection_name() {
+        let name = backup_file_name(Path::new("/tmp/User 1/collection.anki2"));
+        assert!(name.starts_with("collection.anki2.bak-"));
+        assert!(name.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_create_backup_copies_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("collection.anki2");
+        fs::write(&db_path, b"collection contents").unwrap();
+        let backup_dir = dir.path().join("backups");
+
+        let backup = create_backup(&db_path, &backup_dir).unwrap();
+
+        assert_eq!(backup.path.parent().unwrap(), backup_dir);
+        assert_eq!(fs::read(&backup.path).unwrap(), b"collection contents");
+        // As printed by `sha256sum`
+        assert_eq!(backup.sha256, "1e353e6a7b69655ce0dddbe08c94ff1283481f759a50cfe32fdc85eb1721ec6b");
+        let name = backup.path.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(
+            fs::read_to_string(checksum_path(&backup.path)).unwrap(),
+            format!("{}  {}\n", backup.sha256, name)
+        );
+        assert_eq!(list_backups(&backup_dir, &db_path).unwrap(), vec![backup.path]);
+    }
+
+    #[test]
+    fn test_partial_backup_from_prior_crash_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("collection.anki2");
+        fs::write(&db_path, b"collection contents").unwrap();
+        let empty = dir.path().join("collection.anki2.bak-20250101T000000Z");
+        let partial = dir.path().join("collection.anki2.bak-20250102T000000Z.partial");
+        fs::write(&empty, b"").unwrap();
+        fs::write(&partial, b"coll").unwrap();
+
+        let quarantined = quarantine_incomplete_backups(dir.path(), &db_path).unwrap();
+        let backup_path = create_backup(&db_path, dir.path()).unwrap().path;
+
+        assert_eq!(
+            quarantined,
+            vec![
+                dir.path().join("collection.anki2.bak-20250101T000000Z.incomplete"),
+                dir.path().join("collection.anki2.bak-20250102T000000Z.partial.incomplete"),
+            ]
+        );
+        assert!(!empty.exists());
+        assert!(!partial.exists());
+        assert_eq!(fs::read(&quarantined[1]).unwrap(), b"coll");
+        assert_eq!(
+            fs::metadata(&backup_path).unwrap().len(),
+            fs::metadata(&db_path).unwrap().len()
+        );
+        assert_eq!(list_backups(dir.path(), &db_path).unwrap(), vec![backup_path]);
+    }
+
+    #[test]
+    fn test_prune_backups_keeps_most_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("collection.anki2");
+        fs::write(&db_path, b"db").unwrap();
+        for stamp in ["20250101T000000Z", "20250102T000000Z", "20250103T000000Z", "20250104T000000Z"] {
+            fs::write(dir.path().join(format!("collection.anki2.bak-{}", stamp)), b"db").unwrap();
+            fs::write(dir.path().join(format!("collection.anki2.bak-{}.sha256", stamp)), b"x").unwrap();
+        }
+        fs::write(dir.path().join("unrelated.bak-20240101T000000Z"), b"x").unwrap();
+
+        let removed = prune_backups(dir.path(), &db_path, 2).unwrap();
+
+        assert_eq!(removed.len(), 2);
+        let remaining: Vec<String> = list_backups(dir.path(), &db_path)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![
+                "collection.anki2.bak-20250103T000000Z".to_string(),
+                "collection.anki2.bak-20250104T000000Z".to_string(),
+            ]
+        );
+        assert!(!dir.path().join("collection.anki2.bak-20250101T000000Z.sha256").exists());
+        assert!(dir.path().join("collection.anki2.bak-20250104T000000Z.sha256").exists());
+        assert!(db_path.exists());
+        assert!(dir.path().join("unrelated.bak-20240101T000000Z").exists());
+    }
+
+    #[test]
+    fn test_prune_backups_with_fewer_than_keep() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("collection.anki2");
+        fs::write(dir.path().join("collection.anki2.bak-20250101T000000Z"), b"db").unwrap();
+
+        assert!(prune_backups(dir.path(), &db_path, 5).unwrap().is_empty());
+        assert_eq!(list_backups(dir.path(), &db_path).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,585 @@
+//! Command-line definition: the `fix`, `report`, `undo` and `list` subcommands and their
+//! arguments.
+//!
+//! Each subcommand only accepts the arguments that mean something to it. Arguments shared by
+//! several subcommands are built by the functions below so that their help stays identical.
+
+use anki_streak_fixer::date;
+use anki_streak_fixer::{APP_NAME, APP_VERSION};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use std::ffi::OsString;
+
+/// Subcommand names, which the compatibility shim must not mistake for a deck name.
+const SUBCOMMANDS: [&str; 5] = ["fix", "report", "undo", "list", "help"];
+
+/// Parses the process arguments, treating the pre-subcommand forms as their subcommands.
+pub fn matches() -> ArgMatches {
+    command().get_matches_from(with_default_subcommand(std::env::args_os().collect()))
+}
+
+/// Rewrites the arguments of the flag-based command line into the subcommand form.
+///
+/// `--list-collections`, `--report` and `--undo <JOURNAL>` become `list`, `report` and
+/// `undo <JOURNAL>`; anything else without a subcommand, such as `anki_streak_fixer Spanish
+/// -c "User 1"`, becomes `fix`. `--help` and `--version` are left alone.
+pub fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    // Global flags may precede the subcommand
+    let first = args
+        .iter()
+        .skip(1)
+        .position(|arg| !is_global_flag(arg))
+        .map(|index| index + 1);
+    let Some(first) = first else {
+        args.push("fix".into());
+        return args;
+    };
+    if SUBCOMMANDS.iter().any(|name| args[first] == *name)
+        || ["-h", "--help", "-V", "--version"].iter().any(|flag| args[first] == *flag)
+    {
+        return args;
+    }
+
+    let subcommand = if let Some(index) = args.iter().position(|arg| arg == "--list-collections") {
+        args.remove(index);
+        "list"
+    } else if let Some(index) = args.iter().position(|arg| arg == "--report") {
+        args.remove(index);
+        "report"
+    } else if let Some(index) = args.iter().position(|arg| arg == "--undo") {
+        // The journal becomes the positional argument of `undo`
+        args.remove(index);
+        "undo"
+    } else if let Some(index) = args
+        .iter()
+        .position(|arg| arg.to_str().is_some_and(|arg| arg.starts_with("--undo=")))
+    {
+        let journal = args[index].to_str().unwrap()["--undo=".len()..].to_string();
+        args[index] = journal.into();
+        "undo"
+    } else {
+        "fix"
+    };
+    args.insert(1, subcommand.into());
+    args
+}
+
+/// Whether `arg` is one of the arguments accepted before any subcommand.
+fn is_global_flag(arg: &OsString) -> bool {
+    let Some(arg) = arg.to_str() else {
+        return false;
+    };
+    arg == "--verbose"
+        || arg == "--no-color"
+        || arg == "--quiet"
+        || arg == "-q"
+        || (arg.len() > 1 && arg.starts_with('-') && arg[1..].bytes().all(|b| b == b'v'))
+}
+
+/// Builds the full command line definition.
+pub fn command() -> Command {
+    Command::new(APP_NAME)
+        .version(APP_VERSION)
+        .about("Processes Anki notes based on deck and collection.")
+        .after_help("Without a subcommand, `fix` is assumed: `anki_streak_fixer Spanish -c \"User 1\"`.")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("no_color")
+                .help("Don't color the output. Setting the NO_COLOR environment variable has the same effect.")
+                .long("no-color")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .help("Emit verbose logging. Repeat (-vv) to also log SQL statements and timings.")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::Count),
+        )
+        .arg(
+            Arg::new("quiet")
+                .help("Print nothing but errors, warnings and the final one-line summary, e.g. for cron jobs.")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .conflicts_with("verbose")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(fix_command())
+        .subcommand(
+            Command::new("report")
+                .about("Print reviews per day, the longest streak and the missing days between --from and --to.")
+                .args(collection_args())
+                .args(deck_args())
+                .group(deck_selection())
+                .args([
+                    from_arg(),
+                    to_arg(),
+                    rollover_arg(),
+                    timezone_arg(),
+                    Arg::new("prune_empty_days")
+                        .help(
+                            "List only the days without a single review, one date per line, to pick the days \
+                             to fix.",
+                        )
+                        .long("prune-empty-days")
+                        .action(ArgAction::SetTrue),
+                    json_arg("Print the report as JSON instead of a table."),
+                ]),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Reverse a previous run using the undo journal it wrote.")
+                .arg(
+                    Arg::new("journal")
+                        .help("The undo journal written by the run to reverse.")
+                        .value_name("JOURNAL")
+                        .required(true),
+                )
+                .args(collection_args())
+                .args(backup_args())
+                .args([
+                    simulate_arg("Check that the journal matches the collection without changing anything."),
+                    apply_arg(),
+                    safe_mode_arg(),
+                    force_schema_arg(),
+                    Arg::new("force")
+                        .help("Run even if the collection appears to be open in Anki.")
+                        .long("force")
+                        .action(ArgAction::SetTrue),
+                    json_arg("Print a JSON summary of what was restored instead of the usual output."),
+                ]),
+        )
+        .subcommand(Command::new("list").about("List the Anki profiles that have a collection."))
+}
+
+/// The `fix` subcommand: moving reviews to an earlier day, and the operations built on it.
+fn fix_command() -> Command {
+    Command::new("fix")
+        .about("Move reviews back to an earlier day (the default).")
+        .args(collection_args())
+        .arg(
+            Arg::new("collection_glob")
+                .help(
+                    "Run on every profile whose name matches this glob (e.g. 'Lang*'), one after another, \
+                     each with its own backup and summary.",
+                )
+                .long("collection-glob")
+                .value_name("PATTERN")
+                .conflicts_with_all(["collection", "collection_path", "scan", "target_collection", "interactive"]),
+        )
+        .args(deck_args())
+        .args([
+            Arg::new("note_ids")
+                .help("Process exactly these notes (comma-separated ids) instead of selecting decks.")
+                .long("note-ids")
+                .value_name("IDS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(i64)),
+            Arg::new("note_ids_file")
+                .help("Like --note-ids, reading the ids from FILE, separated by commas or whitespace.")
+                .long("note-ids-file")
+                .value_name("FILE"),
+        ])
+        .group(deck_selection().args(["note_ids", "note_ids_file"]))
+        .args([
+            simulate_arg("Simulate the changes without applying them."),
+            apply_arg(),
+            safe_mode_arg(),
+            Arg::new("limit")
+                .help("Limit the number of notes whose reviews are moved. See --limit-order and --limit-scope.")
+                .short('l')
+                .long("limit")
+                .value_name("LIMIT"),
+            Arg::new("newest_only")
+                .help(
+                    "Move only each card's latest review of a day. Its earlier reviews that day are left \
+                     where they are (leave, the default) or deleted (delete, which needs --yes and is \
+                     recorded for undo).",
+                )
+                .long("newest-only")
+                .value_name("OLDER")
+                .value_parser(["leave", "delete"])
+                .num_args(0..=1)
+                .default_missing_value("leave"),
+            Arg::new("by_card")
+                .help(
+                    "Select and move reviews card by card instead of note by note, so --limit counts \
+                     cards and a note's other cards are left alone.",
+                )
+                .long("by-card")
+                .action(ArgAction::SetTrue),
+            Arg::new("limit_order")
+                .help(
+                    "Which notes --limit keeps: note-id keeps the lowest (oldest) note ids, \
+                     recent the notes with the latest reviews in the window, oldest those with the \
+                     earliest reviews, and random a random sample (reproducible with --seed).",
+                )
+                .long("limit-order")
+                .value_name("ORDER")
+                .value_parser(["note-id", "recent", "oldest", "random"])
+                .default_value("note-id"),
+            Arg::new("limit_scope")
+                .help(
+                    "Whether --limit caps the notes of the whole run (global) or of each matched deck \
+                     independently (per-deck). Cards count towards their home deck.",
+                )
+                .long("limit-scope")
+                .value_name("SCOPE")
+                .value_parser(["global", "per-deck"])
+                .default_value("global"),
+            Arg::new("seed")
+                .help("Seed for --limit-order random, making the sample reproducible.")
+                .long("seed")
+                .value_name("SEED")
+                .value_parser(clap::value_parser!(u64)),
+            rollover_arg(),
+            timezone_arg(),
+            from_arg(),
+            to_arg(),
+            Arg::new("days")
+                .help("Move the reviews from N days ago up to today (0 means only today).")
+                .long("days")
+                .value_name("N")
+                .value_parser(clap::value_parser!(i64).range(0..))
+                .conflicts_with_all(["from", "to"]),
+            Arg::new("shift")
+                .help("Move the reviews back by M days (default: 1). Also applies to a lone --from day.")
+                .long("shift")
+                .value_name("M")
+                .value_parser(clap::value_parser!(i64).range(1..))
+                .conflicts_with("to"),
+            Arg::new("max_offset_days")
+                .help("Refuse to move reviews back by more than N days (default: 30), to catch mistyped dates.")
+                .long("max-offset-days")
+                .value_name("N")
+                .value_parser(clap::value_parser!(i64).range(1..)),
+            Arg::new("since_last_run")
+                .help(
+                    "Move the reviews of every day since the last successful real run, up to today, \
+                     back by one day (or --shift days). Without a previous run, only today's reviews are moved.",
+                )
+                .long("since-last-run")
+                .conflicts_with_all(["from", "to", "days"])
+                .action(ArgAction::SetTrue),
+            force_schema_arg(),
+            Arg::new("resolve_deck")
+                .help("Print how a deck name is stored internally and which decks it matches, then exit.")
+                .long("resolve-deck")
+                .value_name("NAME"),
+            Arg::new("count_only")
+                .help("Print only the number of notes whose reviews would move, then exit without changing anything.")
+                .long("count-only")
+                .action(ArgAction::SetTrue),
+            Arg::new("backfill")
+                .help(
+                    "Add a synthetic review on DATE, a day without reviews in the selected decks, then exit. \
+                     This alters your review history and needs --yes.",
+                )
+                .long("backfill")
+                .value_name("DATE")
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["from", "to", "days", "shift", "since_last_run", "count_only"]),
+            Arg::new("cap_review_time")
+                .help("Clamp the answer time of shifted reviews to at most this many milliseconds.")
+                .long("cap-review-time")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(i64).range(0..)),
+            Arg::new("csv")
+                .help("Write every moved review (or, simulating, every review that would move) to FILE as CSV.")
+                .long("csv")
+                .value_name("FILE"),
+            Arg::new("reschedule")
+                .help(
+                    "Also move the due date of touched review cards back by the same number of days. \
+                     Without it only the review history changes.",
+                )
+                .long("reschedule")
+                .action(ArgAction::SetTrue),
+            Arg::new("mod_time")
+                .help(
+                    "What the modification time of touched cards is set to: now, the new time of the \
+                     card's latest moved review (review), or left as it was (keep).",
+                )
+                .long("mod-time")
+                .value_name("WHEN")
+                .value_parser(["now", "review", "keep"])
+                .default_value("now"),
+        ])
+        .args(backup_args())
+        .args([
+            Arg::new("compact")
+                .help("VACUUM the collection after a successful run. Rewrites the whole file; can be slow.")
+                .long("compact")
+                .action(ArgAction::SetTrue),
+            Arg::new("verify")
+                .help("After a real run, recount the reviews on the target day and report an error if the count is off.")
+                .long("verify")
+                .action(ArgAction::SetTrue),
+            Arg::new("no_landing_check")
+                .help("Skip checking that moved reviews landed on the target day.")
+                .long("no-landing-check")
+                .action(ArgAction::SetTrue),
+            Arg::new("strict")
+                .help("Fail instead of warning when moved reviews did not land on the target day.")
+                .long("strict")
+                .conflicts_with("no_landing_check")
+                .action(ArgAction::SetTrue),
+            Arg::new("tag")
+                .help("Only move reviews of notes with this tag.")
+                .long("tag")
+                .value_name("TAG"),
+            Arg::new("note_type")
+                .help("Only move reviews of notes of this note type.")
+                .long("note-type")
+                .value_name("NAME"),
+            Arg::new("review_types")
+                .help(
+                    "Only move reviews of these kinds, comma-separated: learn, review, relearn, cram \
+                     (default: all).",
+                )
+                .long("review-types")
+                .value_name("LIST")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(["learn", "review", "relearn", "cram"]),
+            Arg::new("yes")
+                .help("Don't ask for confirmation before modifying the collection.")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue),
+            Arg::new("force")
+                .help("Run even if the collection appears to be open in Anki.")
+                .long("force")
+                .action(ArgAction::SetTrue),
+            Arg::new("target_collection")
+                .help("Write the shifted result to a copy at this path, leaving the source untouched.")
+                .long("target-collection")
+                .visible_alias("output")
+                .value_name("PATH"),
+            Arg::new("overwrite")
+                .help("Allow --target-collection to replace an existing file.")
+                .long("overwrite")
+                .requires("target_collection")
+                .action(ArgAction::SetTrue),
+            json_arg("Print a single JSON report of the run instead of human-readable output."),
+        ])
+}
+
+/// Where the collection and the defaults come from.
+fn collection_args() -> [Arg; 4] {
+    [
+        Arg::new("collection")
+            .help("Name of the Anki collection.")
+            .short('c')
+            .long("collection")
+            .value_name("COLLECTION"),
+        Arg::new("config")
+            .help("Read defaults from this TOML file instead of the standard location.")
+            .long("config")
+            .value_name("FILE"),
+        Arg::new("collection_path")
+            .help("Path to a collection.anki2 file, used instead of looking up --collection.")
+            .long("collection-path")
+            .value_name("FILE")
+            .conflicts_with("collection"),
+        Arg::new("scan")
+            .help("Search DIR and its subfolders for collection.anki2 files and pick one, instead of naming a collection.")
+            .long("scan")
+            .value_name("DIR")
+            .conflicts_with_all(["collection", "collection_path", "json"]),
+    ]
+}
+
+/// Which decks are processed. Add `deck_selection()` as a group alongside.
+fn deck_args() -> [Arg; 8] {
+    [
+        Arg::new("deck_name")
+            .help("Name of the deck to process. Give several names to fix multiple decks in one run.")
+            .num_args(1..)
+            .index(1),
+        Arg::new("deck_pattern")
+            .help("Process every deck whose name matches this glob, e.g. 'Language::*::Listening'.")
+            .long("deck-pattern")
+            .value_name("GLOB"),
+        Arg::new("deck_id")
+            .help("Process the deck with this numeric id (and its subdecks) instead of naming it.")
+            .long("deck-id")
+            .value_name("ID")
+            .value_parser(clap::value_parser!(i64)),
+        Arg::new("deck_stdin")
+            .help(
+                "Read the decks to process from stdin, one name per line. Blank lines and lines starting \
+                 with # are ignored.",
+            )
+            .long("deck-stdin")
+            .action(ArgAction::SetTrue),
+        Arg::new("interactive")
+            .help("Without a deck name, list the decks and ask which one to process instead of processing all of them.")
+            .long("interactive")
+            .short('i')
+            .conflicts_with_all(["deck_selection", "json"])
+            .action(ArgAction::SetTrue),
+        Arg::new("all")
+            .help("Process every deck in the collection. Needed to fix reviews without naming a deck.")
+            .long("all")
+            .conflicts_with_all(["deck_selection", "interactive"])
+            .action(ArgAction::SetTrue),
+        Arg::new("exclude_subdecks")
+            .help("Only process the named deck itself, not its subdecks.")
+            .long("exclude-subdecks")
+            .requires("deck_selection")
+            .action(ArgAction::SetTrue),
+        Arg::new("case_sensitive")
+            .help("Match deck names and --deck-pattern with exact case, e.g. to tell 'DE' from 'de'.")
+            .long("case-sensitive")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn deck_selection() -> ArgGroup {
+    ArgGroup::new("deck_selection").args(["deck_name", "deck_pattern", "deck_id", "deck_stdin"])
+}
+
+/// How the collection is backed up before it is written.
+fn backup_args() -> [Arg; 3] {
+    [
+        Arg::new("keep_backups")
+            .help("Keep only the N most recent backups, deleting older ones. Keeps all by default.")
+            .long("keep-backups")
+            .value_name("N")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("no_backup")
+            .help("Do not back up the collection before modifying it.")
+            .long("no-backup")
+            .action(ArgAction::SetTrue),
+        Arg::new("backup_dir")
+            .help("Directory for collection backups (defaults to the collection's directory).")
+            .long("backup-dir")
+            .value_name("DIR")
+            .conflicts_with("no_backup"),
+    ]
+}
+
+fn from_arg() -> Arg {
+    Arg::new("from")
+        .help("Start date (format: YYYY-MM-DD, YYYYMMDD, today, yesterday, or relative: -3, 3d, 2w)")
+        .long("from")
+        .value_name("FROM_DATE")
+        .allow_hyphen_values(true)
+}
+
+fn to_arg() -> Arg {
+    Arg::new("to")
+        .help("End date (format: YYYY-MM-DD, YYYYMMDD, today, yesterday, or relative: -3, 3d, 2w)")
+        .long("to")
+        .value_name("TO_DATE")
+        .allow_hyphen_values(true)
+}
+
+fn rollover_arg() -> Arg {
+    Arg::new("rollover")
+        .help(
+            "Time at which a new Anki day starts, as an hour (0-23) or hour and minutes (e.g. 4:30), \
+             overriding the collection's setting.",
+        )
+        .long("rollover")
+        .value_name("HOUR[:MM]")
+        .value_parser(date::parse_rollover)
+}
+
+fn timezone_arg() -> Arg {
+    Arg::new("timezone")
+        .help("Count Anki days in this IANA time zone (e.g. Europe/Berlin) instead of the system's.")
+        .long("timezone")
+        .value_name("IANA")
+        .value_parser(|s: &str| date::Zone::parse(s))
+}
+
+fn simulate_arg(help: &'static str) -> Arg {
+    Arg::new("simulate").help(help).short('s').long("simulate").action(ArgAction::SetTrue)
+}
+
+fn apply_arg() -> Arg {
+    Arg::new("apply")
+        .help("Write the changes even in safe mode, or with `simulate = true` in the config file.")
+        .long("apply")
+        .visible_alias("no-simulate")
+        .conflicts_with("simulate")
+        .action(ArgAction::SetTrue)
+}
+
+fn safe_mode_arg() -> Arg {
+    Arg::new("safe_mode")
+        .help("Simulate unless --apply is given. Also set by `safe_mode = true` in the config file.")
+        .long("safe-mode")
+        .action(ArgAction::SetTrue)
+}
+
+fn force_schema_arg() -> Arg {
+    Arg::new("force_schema")
+        .help("Modify the collection even if its schema version is newer or older than this tool supports.")
+        .long("force-schema")
+        .action(ArgAction::SetTrue)
+}
+
+fn json_arg(help: &'static str) -> Arg {
+    Arg::new("json").help(help).long("json").conflicts_with("verbose").action(ArgAction::SetTrue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ArgMatches, clap::Error> {
+        let args = args.iter().map(OsString::from).collect();
+        command().try_get_matches_from(with_default_subcommand(args))
+    }
+
+    #[test]
+    fn test_command_is_well_formed() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn test_flag_form_maps_to_subcommands() {
+        let matches = parse(&["asf", "Spanish", "-c", "User 1", "-s"]).unwrap();
+        let (name, fix) = matches.subcommand().unwrap();
+        assert_eq!(name, "fix");
+        assert_eq!(fix.get_many::<String>("deck_name").unwrap().collect::<Vec<_>>(), ["Spanish"]);
+        assert!(fix.get_flag("simulate"));
+
+        assert_eq!(parse(&["asf"]).unwrap().subcommand_name(), Some("fix"));
+        assert_eq!(parse(&["asf", "-v", "-c", "User 1"]).unwrap().subcommand_name(), Some("fix"));
+        assert_eq!(parse(&["asf", "--list-collections"]).unwrap().subcommand_name(), Some("list"));
+
+        let matches = parse(&["asf", "-c", "User 1", "--report", "--from", "-7"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("report"));
+
+        for undo in [&["asf", "-c", "User 1", "--undo", "j.json"][..], &["asf", "--undo=j.json", "-c", "User 1"]] {
+            let matches = parse(undo).unwrap();
+            let (name, undo) = matches.subcommand().unwrap();
+            assert_eq!(name, "undo");
+            assert_eq!(undo.get_one::<String>("journal").unwrap(), "j.json");
+        }
+    }
+
+    #[test]
+    fn test_subcommands_reject_unrelated_flags() {
+        let matches = parse(&["asf", "report", "Spanish", "-c", "User 1", "-v"]).unwrap();
+        let (name, report) = matches.subcommand().unwrap();
+        assert_eq!(name, "report");
+        assert_eq!(report.get_count("verbose"), 1);
+
+        assert!(parse(&["asf", "report", "-c", "User 1", "--limit", "3"]).is_err());
+        assert!(parse(&["asf", "undo", "j.json", "--from", "today"]).is_err());
+        assert!(parse(&["asf", "undo", "-c", "User 1"]).is_err());
+        assert!(parse(&["asf", "list", "-c", "User 1"]).is_err());
+        assert!(parse(&["asf", "Spanish", "--deck-id", "1"]).is_err());
+        assert!(parse(&["asf", "report", "--collection-glob", "User*"]).is_err());
+        assert!(parse(&["asf", "fix", "Spanish", "-c", "User 1", "--collection-glob", "User*"]).is_err());
+        assert!(parse(&["asf", "fix", "Spanish", "--collection-glob", "User*"]).is_ok());
+    }
+}
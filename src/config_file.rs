@@ -0,0 +1,111 @@
+use crate::date;
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Defaults read from `config.toml`. Every field is optional, and anything given on the command
+/// line takes precedence.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub collection: Option<String>,
+    pub deck: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_rollover")]
+    pub rollover: Option<NaiveTime>,
+    pub backup_dir: Option<String>,
+    pub verbose: Option<bool>,
+    pub simulate: Option<bool>,
+    pub safe_mode: Option<bool>, // Simulate unless `--apply` is given
+}
+
+/// Returns where the config file is looked for when `--config` is not given:
+/// `$XDG_CONFIG_HOME/anki_streak_fixer/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("anki_streak_fixer").join("config.toml"))
+}
+
+/// Reads and parses the config file at `path`.
+pub fn load(path: &Path) -> Result<FileConfig, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config file '{}': {}", path.display(), e))?;
+    parse(&contents).map_err(|e| format!("Invalid config file '{}': {}", path.display(), e))
+}
+
+/// Accepts `rollover = 4` as well as `rollover = "4:30"`.
+fn deserialize_rollover<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveTime>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Rollover {
+        Hour(i64),
+        Time(String),
+    }
+
+    let rollover = match Rollover::deserialize(deserializer)? {
+        Rollover::Hour(hour) => date::rollover_from_hour(hour),
+        Rollover::Time(time) => date::parse_rollover(&time),
+    };
+    rollover
+        .map(Some)
+        .map_err(|e| serde::de::Error::custom(format!("rollover must be an hour from 0 to 23 or a time such as \"4:30\": {}", e)))
+}
+
+fn parse(contents: &str) -> Result<FileConfig, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse(
+            r#"
+            collection = "User 1"
+            deck = "Spanish::Verbs"
+            rollover = 5
+            verbose = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            FileConfig {
+                collection: Some("User 1".to_string()),
+                deck: Some("Spanish::Verbs".to_string()),
+                rollover: NaiveTime::from_hms_opt(5, 0, 0),
+                verbose: Some(true),
+                ..Default::default()
+            }
+        );
+        assert_eq!(parse("").unwrap(), FileConfig::default());
+        assert_eq!(parse("rollover = \"4:30\"").unwrap().rollover, NaiveTime::from_hms_opt(4, 30, 0));
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        fs::write(&path, "collection = ").unwrap();
+        assert!(load(&path).unwrap_err().starts_with("Invalid config file"));
+
+        fs::write(&path, "colection = \"User 1\"").unwrap();
+        assert!(load(&path).unwrap_err().contains("unknown field `colection`"));
+
+        fs::write(&path, "rollover = \"four\"").unwrap();
+        assert!(load(&path).is_err());
+
+        fs::write(&path, "safe_mode = \"yes\"").unwrap();
+        assert!(load(&path).is_err());
+
+        fs::write(&path, "rollover = 25").unwrap();
+        assert!(load(&path).unwrap_err().contains("rollover must be an hour from 0 to 23"));
+    }
+}
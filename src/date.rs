@@ -1,19 +1,31 @@
 use chrono::Local;
-use chrono::NaiveDate;
+use chrono::{Duration, FixedOffset, LocalResult, NaiveDate, NaiveTime, TimeZone, Timelike};
+use log::debug;
 
 pub fn parse_date(date_str: &str) -> Result<NaiveDate, String> {
+    parse_date_from(date_str, Local::now().date_naive())
+}
+
+/// Parses a date like `parse_date`, resolving `today`, `yesterday` and relative dates against
+/// `today`.
+pub fn parse_date_from(date_str: &str, today: NaiveDate) -> Result<NaiveDate, String> {
     // Handle special keywords
-    match date_str.to_lowercase().as_str() {
-        "today" => return Ok(Local::now().date_naive()),
+    let keyword = date_str.trim().to_lowercase();
+    match keyword.as_str() {
+        "today" => return Ok(today),
         "yesterday" => {
-            return Ok(Local::now()
-                .date_naive()
-                .pred_opt()
-                .ok_or("Failed to calculate yesterday's date")?);
+            return Ok(today.pred_opt().ok_or("Failed to calculate yesterday's date")?);
         }
         _ => {}
     }
 
+    // Or a number of days or weeks before today
+    if let Some(days) = days_ago(&keyword) {
+        return Duration::try_days(days)
+            .and_then(|days| today.checked_sub_signed(days))
+            .ok_or_else(|| format!("'{}' is too far in the past", date_str));
+    }
+
     // Try YYYY-MM-DD format
     if let Ok(date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
         return Ok(date);
@@ -24,7 +36,41 @@ pub fn parse_date(date_str: &str) -> Result<NaiveDate, String> {
         return Ok(date);
     }
 
-    Err("Invalid date format. Please use YYYY-MM-DD, YYYYMMDD, 'today', or 'yesterday'".to_string())
+    Err("Invalid date format. Please use YYYY-MM-DD, YYYYMMDD, 'today', 'yesterday', \
+         or a relative date such as -3, 3d, 2w or '3 days ago'"
+        .to_string())
+}
+
+/// Parses the value of the date option `option` (e.g. `--from`) like `parse_date`. The error names
+/// the option and echoes the value before listing the accepted formats.
+pub fn parse_date_arg(option: &str, value: &str) -> Result<NaiveDate, String> {
+    parse_date(value).map_err(|err| format!("{} '{}': {}", option, value, err))
+}
+
+/// Returns how many days before today a relative date means: `-3`, `3d` and `3 days ago` are
+/// three days, `2w` and `2 weeks ago` two weeks. `None` if `text` isn't a relative date.
+fn days_ago(text: &str) -> Option<i64> {
+    let (number, days_per_unit) = if let Some(number) = text.strip_prefix('-') {
+        (number, 1)
+    } else if let Some(amount) = text.strip_suffix(" ago") {
+        let (number, unit) = amount.split_once(char::is_whitespace)?;
+        match unit.trim() {
+            "day" | "days" => (number, 1),
+            "week" | "weeks" => (number, 7),
+            _ => return None,
+        }
+    } else if let Some(number) = text.strip_suffix('d') {
+        (number, 1)
+    } else if let Some(number) = text.strip_suffix('w') {
+        (number, 7)
+    } else {
+        return None;
+    };
+
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse::<i64>().ok()?.checked_mul(days_per_unit)
 }
 
 /// Calculates number of days between two dates, inclusive of both dates
@@ -37,15 +83,191 @@ pub fn calculate_id_offset(days: i64) -> i64 {
     days * 86_400_000 // milliseconds per day
 }
 
+/// Calculates the millisecond offset between the Anki days `from` and `to` from their rollover
+/// instants in `tz`, so that a move across a daylight saving change keeps each review's local
+/// time of day instead of landing an hour off.
+pub fn dst_aware_id_offset<Tz: TimeZone>(
+    tz: &Tz,
+    from: NaiveDate,
+    to: NaiveDate,
+    rollover: NaiveTime,
+) -> Result<i64, String> {
+    Ok(rollover_instant(tz, from, rollover)? - rollover_instant(tz, to, rollover)?)
+}
+
+/// Parses a rollover time given as an hour (`4`) or an hour and minutes (`4:30`). `24` and
+/// `24:00` are midnight, the same as `0`.
+pub fn parse_rollover(value: &str) -> Result<NaiveTime, String> {
+    let invalid = || {
+        format!(
+            "Invalid rollover '{}': expected an hour from 0 to 23, optionally with minutes (e.g. 4 or 4:30)",
+            value
+        )
+    };
+    let (hour, minute) = match value.trim().split_once(':') {
+        Some((hour, minute)) => (hour, minute),
+        None => (value.trim(), "0"),
+    };
+    let hour: u32 = hour.parse().map_err(|_| invalid())?;
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+    match (hour, minute) {
+        (24, 0) => Ok(NaiveTime::MIN),
+        (0..=23, _) => NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// Converts a whole rollover hour, as older collections store it, to a time of day. `24` is
+/// midnight, the same as `0`.
+pub fn rollover_from_hour(hour: i64) -> Result<NaiveTime, String> {
+    u32::try_from(hour % 24)
+        .ok()
+        .filter(|_| (0..=24).contains(&hour))
+        .and_then(|hour| NaiveTime::from_hms_opt(hour, 0, 0))
+        .ok_or_else(|| format!("Invalid rollover hour {}: expected an hour from 0 to 23", hour))
+}
+
+/// The time zone Anki days are counted in: the system's own, one named with `--timezone`, or the
+/// fixed offset a legacy collection counts its days in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Zone {
+    #[default]
+    Local,
+    Named(chrono_tz::Tz),
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    /// Parses an IANA time zone name such as `Europe/Berlin`.
+    pub fn parse(name: &str) -> Result<Zone, String> {
+        name.parse::<chrono_tz::Tz>()
+            .map(Zone::Named)
+            .map_err(|_| format!("Unknown time zone '{}': expected an IANA name such as Europe/Berlin", name))
+    }
+
+    /// `day_window` in this zone.
+    pub fn day_window(&self, date: NaiveDate, rollover: NaiveTime) -> Result<(i64, i64), String> {
+        match self {
+            Zone::Local => day_window(&Local, date, rollover),
+            Zone::Named(tz) => day_window(tz, date, rollover),
+            Zone::Fixed(offset) => day_window(offset, date, rollover),
+        }
+    }
+
+    /// `dst_aware_id_offset` in this zone.
+    pub fn id_offset(&self, from: NaiveDate, to: NaiveDate, rollover: NaiveTime) -> Result<i64, String> {
+        match self {
+            Zone::Local => dst_aware_id_offset(&Local, from, to, rollover),
+            Zone::Named(tz) => dst_aware_id_offset(tz, from, to, rollover),
+            Zone::Fixed(offset) => dst_aware_id_offset(offset, from, to, rollover),
+        }
+    }
+
+    /// `format_review_time` in this zone.
+    pub fn format_review_time(&self, revlog_id: i64) -> String {
+        match self {
+            Zone::Local => format_review_time(&Local, revlog_id),
+            Zone::Named(tz) => format_review_time(tz, revlog_id),
+            Zone::Fixed(offset) => format_review_time(offset, revlog_id),
+        }
+    }
+
+    /// The zone a collection on Anki's v1 scheduler counts its days in.
+    ///
+    /// The v1 scheduler starts a day every 24 hours after `col.crt`, the collection's first
+    /// rollover in seconds, whatever the local offset is today; that is a fixed offset once the
+    /// rollover time is known. Returns `None` when `crt` is not set.
+    pub fn from_crt(crt: i64, rollover: NaiveTime) -> Option<Zone> {
+        if crt <= 0 {
+            return None;
+        }
+        let seconds = (i64::from(rollover.num_seconds_from_midnight()) - crt).rem_euclid(86_400);
+        // UTC offsets run from -12:00 to +14:00
+        let seconds_east = if seconds > 14 * 3_600 { seconds - 86_400 } else { seconds };
+        i32::try_from(seconds_east).ok().and_then(FixedOffset::east_opt).map(Zone::Fixed)
+    }
+}
+
+/// Returns the `[start, end)` revlog id range (milliseconds) of the Anki day `date` in `tz`.
+///
+/// An Anki day starts at the rollover time rather than midnight and lasts until the rollover
+/// time of the next calendar day, so days with a daylight saving transition are 23 or 25 hours
+/// long.
+pub fn day_window<Tz: TimeZone>(tz: &Tz, date: NaiveDate, rollover: NaiveTime) -> Result<(i64, i64), String> {
+    let next_date = date.succ_opt().ok_or_else(|| format!("No day after {}", date))?;
+    let start_time = rollover_instant(tz, date, rollover)?;
+    let end_time = rollover_instant(tz, next_date, rollover)?;
+
+    Ok((start_time, end_time))
+}
+
+/// Returns the instant (epoch milliseconds) at which the Anki day `date` begins in `tz`.
+///
+/// Daylight saving transitions are resolved rather than rejected: when the rollover time is
+/// repeated (fall back), the earlier instant is used; when it is skipped (spring forward), the
+/// first valid local time after the gap is used.
+pub fn rollover_instant<Tz: TimeZone>(tz: &Tz, date: NaiveDate, rollover: NaiveTime) -> Result<i64, String> {
+    // Combine the date and rollover time
+    let naive_rollover_datetime = date.and_time(rollover);
+
+    // Step forward a minute at a time out of a DST gap; gaps never last a whole day
+    for minutes in 0..=24 * 60 {
+        let candidate = naive_rollover_datetime + Duration::minutes(minutes);
+        match tz.from_local_datetime(&candidate) {
+            LocalResult::Single(datetime) => return Ok(datetime.timestamp_millis()),
+            LocalResult::Ambiguous(earlier, _) => return Ok(earlier.timestamp_millis()),
+            LocalResult::None => continue,
+        }
+    }
+
+    Err(format!("No valid local time for the rollover on {}", date))
+}
+
+/// Returns the revlog ids that fall outside `window`, i.e. reviews that did not land in the
+/// intended day.
+pub fn misplaced_reviews(revlog_ids: &[i64], window: (i64, i64)) -> Vec<i64> {
+    let (start, end) = window;
+    revlog_ids
+        .iter()
+        .copied()
+        .filter(|id| *id < start || *id >= end)
+        .collect()
+}
+
+/// Formats a revlog id (epoch milliseconds) as a `YYYY-MM-DD HH:MM:SS` date and time in `tz`.
+pub fn format_review_time<Tz: TimeZone>(tz: &Tz, revlog_id: i64) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match tz.timestamp_millis_opt(revlog_id) {
+        LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => {
+            datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+        }
+        LocalResult::None => format!("invalid timestamp {}", revlog_id),
+    }
+}
+
+/// Fills in the default window when neither `--from` nor `--to` was given: today's reviews are
+/// moved back to yesterday, the tool's original purpose. A lone `--from` fixes that single day,
+/// moving its reviews back by `shift` days. Explicit dates are returned unchanged.
+pub fn resolve_date_range(
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+    today: NaiveDate,
+    shift: i64,
+) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    match (from_date, to_date) {
+        (None, None) => (Some(today), today.pred_opt()),
+        (Some(from), None) => (Some(from), Some(from - Duration::days(shift))),
+        dates => dates,
+    }
+}
+
 pub fn validate_dates(from_date: Option<NaiveDate>, to_date: Option<NaiveDate>, today: NaiveDate) -> Result<(), String> {
-    // println!("Validating dates...");
-    // println!("from_date: {:?}", from_date);
-    // println!("to_date: {:?}", to_date);
-    // println!("today: {:?}", today);
+    debug!("Validating dates: from {:?}, to {:?}, today {}", from_date, to_date, today);
 
     // Ensure 'to_date' is not in the future
     if let Some(to) = to_date {
-        // println!("Checking if 'to_date' ({}) is in the future...", to);
         if to > today {
             return Err(format!("Invalid 'to_date': {} is in the future.", to));
         }
@@ -53,7 +275,6 @@ pub fn validate_dates(from_date: Option<NaiveDate>, to_date: Option<NaiveDate>,
 
     // Ensure 'from_date' is not in the future
     if let Some(from) = from_date {
-        println!("Checking if 'from_date' ({}) is in the future...", from);
         if from > today {
             return Err(format!("Invalid 'from_date': {} is in the future.", from));
         }
@@ -61,16 +282,37 @@ pub fn validate_dates(from_date: Option<NaiveDate>, to_date: Option<NaiveDate>,
 
     // Check for invalid date range
     if let (Some(from), Some(to)) = (from_date, to_date) {
-        // println!("Checking date range: from_date ({}) > to_date ({})", from, to);
-        if from <= to {
+        if from < to {
             return Err(format!(
-                "Invalid date range: 'from_date' ({}) must be after 'to_date' ({}).",
+                "Invalid date range: 'from_date' ({}) must be on or after 'to_date' ({}); reviews can only be moved back in time.",
                 from, to
             ));
         }
+        if from == to {
+            return Err(format!(
+                "Invalid date range: 'from_date' and 'to_date' are the same day ({}), so there is nothing to move.",
+                from
+            ));
+        }
     }
 
-    println!("Dates are valid.");
+    Ok(())
+}
+
+/// Largest move, in days, allowed unless `--max-offset-days` raises it.
+pub const DEFAULT_MAX_OFFSET_DAYS: i64 = 30;
+
+/// Refuses a move of more than `max_days` days, which is far more likely a typo in `--from` or
+/// `--to` than an intended shift.
+pub fn check_max_offset(from: NaiveDate, to: NaiveDate, max_days: i64) -> Result<(), String> {
+    let days = days_between(to, from);
+    if days > max_days {
+        return Err(format!(
+            "Moving reviews from {} to {} shifts them back {} days, more than the limit of {}. \
+             If that is intended, pass --max-offset-days {} or more.",
+            from, to, days, max_days, days
+        ));
+    }
     Ok(())
 }
 
@@ -79,6 +321,23 @@ mod tests {
     use super::*;
     use chrono::NaiveDate;
 
+    fn hour(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_format_review_time() {
+        let tz = chrono_tz::America::New_York;
+        let noon_utc = NaiveDate::from_ymd_opt(2025, 1, 3)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis();
+        assert_eq!(format_review_time(&tz, noon_utc), "2025-01-03 07:00:00");
+        assert_eq!(format_review_time(&tz, noon_utc - 86_400_000), "2025-01-02 07:00:00");
+    }
+
     #[test]
     fn test_days_between() {
         let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
@@ -100,6 +359,36 @@ mod tests {
         assert_eq!(calculate_id_offset(7), 86_400_000 * 7);
     }
 
+    #[test]
+    fn test_zone_windows_differ_by_zone() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let new_york = Zone::parse("America/New_York").unwrap();
+        let tokyo = Zone::parse("Asia/Tokyo").unwrap();
+
+        // 4:00 EST is 09:00 UTC; 4:00 JST is 19:00 UTC the day before
+        assert_eq!(new_york.day_window(date, hour(4)).unwrap(), (1_735_894_800_000, 1_735_981_200_000));
+        assert_eq!(tokyo.day_window(date, hour(4)).unwrap(), (1_735_844_400_000, 1_735_930_800_000));
+        assert_eq!(new_york.format_review_time(1_735_894_800_000), "2025-01-03 04:00:00");
+        assert_eq!(tokyo.format_review_time(1_735_894_800_000), "2025-01-03 18:00:00");
+        assert!(Zone::parse("Mars/Olympus").unwrap_err().contains("Unknown time zone 'Mars/Olympus'"));
+    }
+
+    #[test]
+    fn test_dst_aware_id_offset() {
+        let tz = chrono_tz::America::New_York;
+        let date = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+
+        // No transition in between: whole days
+        assert_eq!(dst_aware_id_offset(&tz, date(5), date(4), hour(4)).unwrap(), calculate_id_offset(1));
+        // Clocks sprang forward at 2:00 on the 9th, so the 4:00 rollovers of the 8th and 9th are 23 hours apart
+        assert_eq!(dst_aware_id_offset(&tz, date(9), date(8), hour(4)).unwrap(), 23 * 3_600_000);
+        assert_eq!(dst_aware_id_offset(&tz, date(12), date(2), hour(4)).unwrap(), calculate_id_offset(10) - 3_600_000);
+        // Clocks fell back on 2025-11-02
+        let fall = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        assert_eq!(dst_aware_id_offset(&tz, fall(3), fall(1), hour(4)).unwrap(), calculate_id_offset(2) + 3_600_000);
+        assert_eq!(dst_aware_id_offset(&chrono::Utc, date(10), date(9), hour(4)).unwrap(), calculate_id_offset(1));
+    }
+
     #[test]
     fn test_parse_date_special_keywords() {
         let today = Local::now().date_naive();
@@ -111,6 +400,55 @@ mod tests {
         assert_eq!(parse_date("YESTERDAY").unwrap(), yesterday);
     }
 
+    #[test]
+    fn test_parse_date_relative() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(parse_date_from("-1", today), parse_date_from("yesterday", today));
+        assert_eq!(parse_date_from("-0", today).unwrap(), today);
+        assert_eq!(parse_date_from("-3", today).unwrap(), date(2025, 1, 7));
+        assert_eq!(parse_date_from("3d", today).unwrap(), date(2025, 1, 7));
+        assert_eq!(parse_date_from("3 days ago", today).unwrap(), date(2025, 1, 7));
+        assert_eq!(parse_date_from("1 Day ago", today).unwrap(), date(2025, 1, 9));
+
+        // Across the year and month boundaries
+        assert_eq!(parse_date_from("2w", today).unwrap(), date(2024, 12, 27));
+        assert_eq!(parse_date_from("2 weeks ago", today).unwrap(), date(2024, 12, 27));
+        assert_eq!(parse_date_from("-10", today).unwrap(), date(2024, 12, 31));
+        let march = date(2024, 3, 1);
+        assert_eq!(parse_date_from("1w", march).unwrap(), date(2024, 2, 23));
+        assert_eq!(parse_date_from("-1", march).unwrap(), date(2024, 2, 29));
+
+        for invalid in ["-", "d", "w", "-3d", "+3", "3x", "3 months ago", "-1.5", "days ago"] {
+            assert!(parse_date_from(invalid, today).is_err(), "{}", invalid);
+        }
+        assert!(parse_date_from("99999999999w", today).is_err());
+    }
+
+    #[test]
+    fn test_parse_date_arg_names_the_option() {
+        let err = parse_date_arg("--from", "2025-02-30").unwrap_err();
+        assert!(err.starts_with("--from '2025-02-30': "), "unexpected error: {}", err);
+        assert!(err.contains("YYYYMMDD"));
+        assert_eq!(parse_date_arg("--to", "2025-02-28").unwrap(), NaiveDate::from_ymd_opt(2025, 2, 28).unwrap());
+    }
+
+    #[test]
+    fn test_zone_from_crt() {
+        let four_am = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let tokyo = Some(Zone::Fixed(FixedOffset::east_opt(9 * 3_600).unwrap()));
+        // Created at 04:00 on 2020-01-01 in Tokyo, i.e. 19:00 UTC the day before
+        let crt = 1_577_818_800;
+
+        assert_eq!(Zone::from_crt(crt, four_am), tokyo);
+        assert_eq!(
+            Zone::from_crt(crt + 14 * 3_600, four_am),
+            Some(Zone::Fixed(FixedOffset::west_opt(5 * 3_600).unwrap()))
+        );
+        assert_eq!(Zone::from_crt(0, four_am), None);
+    }
+
     #[test]
     fn test_parse_date_formats() {
         let expected = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
@@ -125,6 +463,164 @@ mod tests {
         assert!(parse_date("20241345").is_err());
     }
 
+    #[test]
+    fn test_day_window_starts_at_rollover() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let (start, end) = day_window(&chrono::Utc, date, hour(4)).unwrap();
+        assert_eq!(start, 1_735_704_000_000); // 2025-01-01 04:00:00 UTC
+        assert_eq!(end - start, 86_400_000);
+
+        let (start, _) = day_window(&chrono::Utc, date, parse_rollover("4:30").unwrap()).unwrap();
+        assert_eq!(start, 1_735_705_800_000); // 2025-01-01 04:30:00 UTC
+    }
+
+    #[test]
+    fn test_day_window_follows_dst_day_length() {
+        let tz = chrono_tz::America::New_York;
+
+        // Clocks sprang forward at 2:00 on 2025-03-09, inside the Anki day that starts on the 8th
+        let spring = NaiveDate::from_ymd_opt(2025, 3, 8).unwrap();
+        let (start, end) = day_window(&tz, spring, parse_rollover("4:30").unwrap()).unwrap();
+        assert_eq!(end - start, 23 * 3_600_000);
+        assert_eq!(day_window(&tz, spring.succ_opt().unwrap(), parse_rollover("4:30").unwrap()).unwrap().0, end);
+
+        // Clocks fell back at 2:00 on 2025-11-02, inside the Anki day that starts on the 1st
+        let fall = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap();
+        let (start, end) = day_window(&tz, fall, hour(4)).unwrap();
+        assert_eq!(end - start, 25 * 3_600_000);
+    }
+
+    #[test]
+    fn test_parse_rollover() {
+        assert_eq!(parse_rollover("4").unwrap(), hour(4));
+        assert_eq!(parse_rollover("04:30").unwrap(), NaiveTime::from_hms_opt(4, 30, 0).unwrap());
+        for invalid in ["25", "24:30", "-1", "4:60", "four", "4:"] {
+            assert!(parse_rollover(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_rollover_hour_bounds() {
+        assert_eq!(rollover_from_hour(0).unwrap(), hour(0));
+        assert_eq!(rollover_from_hour(23).unwrap(), hour(23));
+        assert_eq!(rollover_from_hour(24).unwrap(), hour(0));
+        assert_eq!(rollover_from_hour(-1).unwrap_err(), "Invalid rollover hour -1: expected an hour from 0 to 23");
+        assert!(rollover_from_hour(25).is_err());
+
+        assert_eq!(parse_rollover("0").unwrap(), hour(0));
+        assert_eq!(parse_rollover("23").unwrap(), hour(23));
+        assert_eq!(parse_rollover("24").unwrap(), hour(0));
+        assert_eq!(parse_rollover("24:00").unwrap(), hour(0));
+        assert!(parse_rollover("-1").is_err());
+    }
+
+    #[test]
+    fn test_rollover_in_spring_forward_gap_advances_to_next_valid_time() {
+        // On 2025-03-09 New York clocks jumped from 2:00 EST to 3:00 EDT.
+        let tz = chrono_tz::America::New_York;
+        let date = NaiveDate::from_ymd_opt(2025, 3, 9).unwrap();
+        let start = rollover_instant(&tz, date, hour(2)).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2025, 3, 9).unwrap().and_hms_opt(7, 0, 0).unwrap().and_utc();
+        assert_eq!(start, expected.timestamp_millis()); // 3:00 EDT
+    }
+
+    #[test]
+    fn test_rollover_in_fall_back_overlap_uses_earlier_instant() {
+        // On 2025-11-02 New York clocks went from 2:00 EDT back to 1:00 EST, so 1:00 happened twice.
+        let tz = chrono_tz::America::New_York;
+        let date = NaiveDate::from_ymd_opt(2025, 11, 2).unwrap();
+        let start = rollover_instant(&tz, date, hour(1)).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2025, 11, 2).unwrap().and_hms_opt(5, 0, 0).unwrap().and_utc();
+        assert_eq!(start, expected.timestamp_millis()); // 1:00 EDT
+    }
+
+    #[test]
+    fn test_misplaced_reviews() {
+        let window = (1_000, 2_000);
+        assert_eq!(misplaced_reviews(&[999, 1_000, 1_999, 2_000], window), vec![999, 2_000]);
+        assert!(misplaced_reviews(&[], window).is_empty());
+    }
+
+    #[test]
+    fn test_landing_check_catches_naive_offset_across_dst() {
+        // US daylight saving time began on 2025-03-09, so that Anki day is 23 hours long.
+        let tz = chrono_tz::America::New_York;
+        let from = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 3, 8).unwrap();
+
+        // A review 30 minutes after the 4am rollover, shifted back by two flat 24-hour days,
+        // lands at 3:30am standard time, which still belongs to the 7th.
+        let (from_start, _) = day_window(&tz, from, hour(4)).unwrap();
+        let review = from_start + 30 * 60 * 1000;
+        let shifted = review - calculate_id_offset(days_between(to, from));
+        assert_eq!(misplaced_reviews(&[shifted], day_window(&tz, to, hour(4)).unwrap()), vec![shifted]);
+
+        // The same shift in January, away from any transition, lands correctly.
+        let from = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 8).unwrap();
+        let (from_start, _) = day_window(&tz, from, hour(4)).unwrap();
+        let shifted = from_start + 30 * 60 * 1000 - calculate_id_offset(2);
+        assert!(misplaced_reviews(&[shifted], day_window(&tz, to, hour(4)).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_date_range_defaults_to_today_and_yesterday() {
+        let today = mock_today();
+        let yesterday = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        assert_eq!(resolve_date_range(None, None, today, 1), (Some(today), Some(yesterday)));
+
+        let from = Some(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        let to = Some(NaiveDate::from_ymd_opt(2024, 12, 30).unwrap());
+        assert_eq!(resolve_date_range(from, to, today, 1), (from, to));
+    }
+
+    #[test]
+    fn test_resolve_date_range_lone_from_is_a_single_day() {
+        let today = mock_today();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let (resolved_from, to) = resolve_date_range(Some(from), None, today, 1);
+        assert_eq!(resolved_from, Some(from));
+        assert_eq!(to, NaiveDate::from_ymd_opt(2024, 12, 31));
+        assert!(validate_dates(resolved_from, to, today).is_ok());
+
+        let (_, to) = resolve_date_range(Some(from), None, today, 3);
+        assert_eq!(to, NaiveDate::from_ymd_opt(2024, 12, 29));
+    }
+
+    #[test]
+    fn test_multi_day_offset() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 12, 30).unwrap();
+        assert_eq!(calculate_id_offset(days_between(to, from)), 3 * 86_400_000);
+    }
+
+    #[test]
+    fn test_validate_dates_same_day() {
+        let date = Some(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        let result = validate_dates(date, date, mock_today());
+
+        assert_eq!(
+            result.unwrap_err(),
+            "Invalid date range: 'from_date' and 'to_date' are the same day (2025-01-02), so there is nothing to move."
+        );
+    }
+
+    #[test]
+    fn test_validate_dates_from_before_to() {
+        let from_date = Some(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        let to_date = Some(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        let err = validate_dates(from_date, to_date, mock_today()).unwrap_err();
+
+        assert!(err.contains("must be on or after"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_dates_adjacent_days() {
+        let from_date = Some(NaiveDate::from_ymd_opt(2025, 1, 3).unwrap());
+        let to_date = Some(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        assert!(validate_dates(from_date, to_date, mock_today()).is_ok());
+    }
+
     fn mock_today() -> NaiveDate {
         NaiveDate::from_ymd_opt(2025, 1, 4).unwrap() // Mocked "today" for testing
     }
@@ -140,6 +636,18 @@ mod tests {
         assert!(result.is_ok(), "Expected Ok(()), got: {:?}", result);
     }
 
+    #[test]
+    fn test_check_max_offset_boundary() {
+        let from = NaiveDate::from_ymd_opt(2025, 3, 31).unwrap();
+        assert!(check_max_offset(from, NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(), 30).is_ok());
+        let err = check_max_offset(from, NaiveDate::from_ymd_opt(2025, 2, 28).unwrap(), 30).unwrap_err();
+        assert_eq!(
+            err,
+            "Moving reviews from 2025-03-31 to 2025-02-28 shifts them back 31 days, more than the limit of 30. \
+             If that is intended, pass --max-offset-days 31 or more."
+        );
+    }
+
     #[test]
     fn test_validate_dates_valid_from_only() {
         let from_date = Some(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
@@ -155,8 +663,8 @@ mod tests {
 
         let result = validate_dates(from_date, to_date, today);
 
-        let expected_errors = vec![
-            "Invalid date range: 'from_date' (2025-01-01) must be after 'to_date' (2025-01-05).".to_string(),
+        let expected_errors = [
+            "Invalid date range: 'from_date' (2025-01-01) must be on or after 'to_date' (2025-01-05); reviews can only be moved back in time.".to_string(),
             "Invalid 'to_date': 2025-01-05 is in the future.".to_string(),
         ];
 
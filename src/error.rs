@@ -0,0 +1,163 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can stop a run, with a message meant for the user.
+#[derive(Debug)]
+pub enum AppError {
+    /// A deck name or `--deck-pattern` given on the command line matched no deck.
+    NoMatchingDeck(String),
+    /// No deck has the id given with `--deck-id`.
+    NoDeckWithId(i64),
+    /// A deck name matched no deck exactly, but several decks contain it. The candidates are
+    /// rendered with `::` separators.
+    AmbiguousDeck { name: String, candidates: Vec<String> },
+    /// Notes were fetched without a `--from` date to select reviews by.
+    MissingFromDate,
+    /// A deck lookup was attempted while processing all decks.
+    ModeMisuse,
+    /// The collection lacks something the requested operation needs.
+    UnsupportedCollection(String),
+    /// A real run was declined at the confirmation prompt.
+    Cancelled,
+    /// A real run needed confirmation, but there was no terminal to ask on (or `--json` was set).
+    ConfirmationRequired,
+    /// The collection file does not exist. `profile_exists` tells whether its folder does.
+    MissingCollection { path: PathBuf, profile_exists: bool },
+    /// The config file could not be read or parsed, or a required setting is missing.
+    Config(String),
+    /// A setting stored in the collection (`key`) could not be parsed.
+    InvalidSetting { key: String, message: String },
+    /// The file is not an Anki collection; `detail` says what is missing.
+    NotACollection { path: PathBuf, detail: String },
+    /// The collection is open in Anki and `--force` was not given.
+    CollectionInUse,
+    /// Moved reviews fell outside the target day and `--strict` was set.
+    MisplacedReviews(String),
+    /// The Anki day of a date could not be placed in the time zone.
+    InvalidDay(String),
+    /// The backup could not be made, so the collection was left alone.
+    Backup(io::Error),
+    /// The collection no longer matches the undo journal, e.g. because it was already undone.
+    UndoMismatch(String),
+    /// The `--csv` audit file could not be written.
+    Audit { path: PathBuf, source: io::Error },
+    /// `--verify` found a different number of reviews on the target days than the run moved there.
+    /// The changes were already saved.
+    VerificationFailed,
+    /// Reading or writing a file or the terminal failed.
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NoMatchingDeck(name) => write!(
+                f,
+                "No deck matches '{}'. Use --resolve-deck to see how the name is matched.",
+                name
+            ),
+            AppError::AmbiguousDeck { name, candidates } => {
+                writeln!(f, "'{}' does not name a single deck. Decks containing it:", name)?;
+                for candidate in candidates {
+                    writeln!(f, "  {}", candidate)?;
+                }
+                write!(
+                    f,
+                    "Give the exact deck name, or add --exclude-subdecks to process only that deck."
+                )
+            }
+            AppError::NoDeckWithId(id) => write!(f, "No deck has the id {}.", id),
+            AppError::MissingFromDate => write!(
+                f,
+                "A --from date is required (or omit dates to use the default); got none."
+            ),
+            AppError::ModeMisuse => write!(f, "Deck lookup is only possible when a deck is selected."),
+            AppError::UnsupportedCollection(message) => write!(f, "{}", message),
+            AppError::Cancelled => write!(f, "Cancelled. The collection was not changed."),
+            AppError::ConfirmationRequired => write!(
+                f,
+                "Refusing to modify the collection without confirmation. Pass --yes to run non-interactively."
+            ),
+            AppError::MissingCollection { path, profile_exists } => {
+                let dir = path.parent().map(|dir| dir.display().to_string()).unwrap_or_default();
+                if *profile_exists {
+                    write!(
+                        f,
+                        "No collection at '{}': the folder '{}' exists but holds no collection file. \
+                         Has the collection moved? Run `anki_streak_fixer list` to see the profiles that have one.",
+                        path.display(),
+                        dir
+                    )
+                } else {
+                    write!(
+                        f,
+                        "No collection at '{}': the profile folder '{}' does not exist. \
+                         Check the profile name, or run `anki_streak_fixer list` to see the available profiles.",
+                        path.display(),
+                        dir
+                    )
+                }
+            }
+            AppError::Config(message) => write!(f, "{}", message),
+            AppError::InvalidSetting { key, message } => write!(
+                f,
+                "The collection's '{}' setting is invalid: {}. Check it in Anki's preferences.",
+                key, message
+            ),
+            AppError::NotACollection { path, detail } => {
+                write!(f, "'{}' doesn't look like an Anki collection ({}).", path.display(), detail)
+            }
+            AppError::CollectionInUse => write!(
+                f,
+                "The collection appears to be open in Anki. Close Anki and try again, or pass --force."
+            ),
+            AppError::MisplacedReviews(message) => write!(f, "{}", message),
+            AppError::InvalidDay(message) => write!(f, "{}", message),
+            AppError::Backup(err) => write!(f, "Could not back up the collection, so it was not changed: {}", err),
+            AppError::UndoMismatch(message) => write!(f, "{} Nothing was changed.", message),
+            AppError::Audit { path, source } => {
+                write!(f, "Cannot write the audit CSV '{}': {}", path.display(), source)
+            }
+            AppError::VerificationFailed => write!(
+                f,
+                "Verification failed after the changes were saved. Check the collection, and use `undo` \
+                 with the journal or restore the backup if the reviews are not where they should be."
+            ),
+            AppError::Io(err) => write!(f, "{}", err),
+            AppError::Sqlite(rusqlite::Error::SqliteFailure(err, _))
+                if matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) =>
+            {
+                write!(
+                    f,
+                    "The collection is busy: another program kept it locked for too long. Is Anki open? \
+                     Close it, or wait for it to finish syncing, and try again."
+                )
+            }
+            AppError::Sqlite(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Sqlite(err) => Some(err),
+            AppError::Backup(err) | AppError::Io(err) | AppError::Audit { source: err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> Self {
+        AppError::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        AppError::Sqlite(err)
+    }
+}
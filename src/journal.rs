@@ -0,0 +1,117 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A review that was moved, identified by its id before and after the move.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevlogMove {
+    pub old_id: i64,
+    pub new_id: i64,
+}
+
+/// The state of a card before the run touched it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardState {
+    pub id: i64,
+    #[serde(rename = "mod")]
+    pub modified: i64,
+    pub usn: i64,
+    // `due` before `--reschedule` moved it; absent when the due date was left alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<i64>,
+}
+
+/// A review deleted by `--newest-only delete`, with every column needed to put it back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletedReview {
+    pub id: i64,
+    pub cid: i64,
+    pub usn: i64,
+    pub ease: i64,
+    pub ivl: i64,
+    #[serde(rename = "lastIvl")]
+    pub last_ivl: i64,
+    pub factor: i64,
+    pub time: i64,
+    #[serde(rename = "type")]
+    pub kind: i64,
+}
+
+/// Record of a real run, detailed enough to reverse it with `--undo`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    pub collection: String,
+    pub created: String,
+    pub revlog: Vec<RevlogMove>,
+    pub cards: Vec<CardState>,
+    // Reviews the run deleted; absent from journals of runs that deleted nothing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<DeletedReview>,
+}
+
+/// Builds the journal file name using the current UTC time, e.g.
+/// `anki_streak_fixer_undo_20250103T101500123Z.json`.
+pub fn journal_file_name() -> String {
+    format!("anki_streak_fixer_undo_{}.json", Utc::now().format("%Y%m%dT%H%M%S%3fZ"))
+}
+
+/// Writes `journal` into `dir` and returns the path of the new file.
+pub fn write_journal(journal: &Journal, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(journal_file_name());
+    let contents = serde_json::to_string_pretty(journal)?;
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+pub fn read_journal(path: &Path) -> io::Result<Journal> {
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal {
+            collection: "collection.anki2".to_string(),
+            created: "2025-01-03T10:15:00Z".to_string(),
+            revlog: vec![RevlogMove { old_id: 2_000, new_id: 1_000 }],
+            cards: vec![CardState { id: 7, modified: 1_700_000_000, usn: 42, due: Some(120) }],
+            deleted: vec![DeletedReview {
+                id: 1_500,
+                cid: 7,
+                usn: 3,
+                ease: 3,
+                ivl: 10,
+                last_ivl: 4,
+                factor: 2500,
+                time: 6000,
+                kind: 1,
+            }],
+        };
+
+        let path = write_journal(&journal, dir.path()).unwrap();
+
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("anki_streak_fixer_undo_"));
+        assert!(name.ends_with(".json"));
+        assert!(fs::read_to_string(&path).unwrap().contains("\"mod\": 1700000000"));
+        assert!(fs::read_to_string(&path).unwrap().contains("\"lastIvl\": 4"));
+        assert_eq!(read_journal(&path).unwrap(), journal);
+    }
+
+    #[test]
+    fn test_read_journal_rejects_malformed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(read_journal(&path).is_err());
+    }
+}
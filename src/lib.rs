@@ -0,0 +1,4338 @@
+//! Moves Anki reviews from one day to another to repair a broken review streak.
+//!
+//! [`AnkiProcessor`] does the work on a collection file, configured by an [`AppConfig`] that
+//! selects the decks ([`AppMode`]) and the options of a run. The [`date`] module has the day
+//! boundary arithmetic it is built on. The `anki_streak_fixer` binary is a thin command-line
+//! front end over this crate.
+
+pub mod config_file;
+pub mod date;
+pub mod error;
+pub mod journal;
+pub mod report;
+pub mod state;
+pub mod utils;
+mod audit;
+mod backup;
+mod queries;
+#[cfg(test)]
+mod test_fixtures;
+
+use log::{debug, trace};
+use rusqlite::{params, Connection, OpenFlags};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use chrono::{Local, NaiveDate, NaiveTime, Timelike};
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use unicase::UniCase;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use error::AppError;
+use utils::{
+    deck_glob_to_like, green_text, hex_bytes, replace_deck_delimiter, to_internal_deck_name, yellow_text,
+};
+
+/// Results of the processing steps; SQLite errors convert with `?`.
+type Result<T, E = AppError> = std::result::Result<T, E>;
+
+/// Name of the crate, used in version output and file names.
+pub const APP_NAME: &str = env!("CARGO_PKG_NAME");
+/// Version of the crate.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Tables every Anki collection has and this tool reads or writes.
+const ANKI_TABLES: [&str; 5] = ["col", "cards", "notes", "revlog", "decks"];
+
+/// Answer time, in milliseconds, recorded for a review added by `--backfill`.
+const BACKFILL_ANSWER_MS: i64 = 1000;
+
+/// How long to wait for a lock held by another process (normally Anki) before giving up.
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Collection schema versions (`col.ver`) this tool knows how to modify: 11 is the schema of Anki
+/// 2.1.x before 2.1.28, 18 the one current Anki writes.
+const SUPPORTED_SCHEMA_VERSIONS: std::ops::RangeInclusive<i64> = 11..=18;
+
+/// Exit code when the run worked but no notes matched, so nothing was (or would be) moved.
+/// Errors exit with 1.
+pub const EXIT_NO_NOTES: i32 = 2;
+
+/// Hour at which Anki starts a new day unless configured otherwise.
+const DEFAULT_ROLLOVER_HOURS: u32 = 4;
+
+/// Printed after every write that bumped `col.scm`, so the fix isn't lost by syncing the wrong
+/// way.
+const FULL_SYNC_NOTICE: &str = "The collection's schema was modified, so Anki's next sync needs a one-way upload. \
+When Anki asks, choose \"Upload to AnkiWeb\"; choosing download would throw this change away. \
+Your other devices then have to download the collection from AnkiWeb.";
+
+
+/// Describes a rollover override next to the collection's own setting, when it is known.
+fn rollover_override_warning(rollover: NaiveTime, collection: Option<NaiveTime>) -> String {
+    let setting = match collection {
+        Some(collection) if collection == rollover => " (the same as the collection's setting)".to_string(),
+        Some(collection) => format!(" instead of the collection's {}", collection.format("%H:%M")),
+        None => String::new(),
+    };
+    format!(
+        "Warning: Anki days start at {} for this run, overridden by --rollover or the config file{}.",
+        rollover.format("%H:%M"),
+        setting
+    )
+}
+
+/// Registers a custom collation named `unicase` to enable Unicode-aware case-insensitive comparisons
+/// in SQLite.
+///
+/// # Why is this needed?
+/// SQLite's default `NOCASE` collation only supports ASCII case-insensitivity. This means that
+/// comparisons like "Ä" vs. "ä" or "ß" vs. "ss" will not work as expected. For applications dealing
+/// with Unicode data, such as deck names in Anki that might include international characters,
+/// this limitation can result in inaccurate or incomplete query results.
+///
+/// To address this, we define a `unicase` collation that uses Rust's `unicase` crate to perform
+/// Unicode-aware case-insensitive comparisons.
+///
+/// # How does it work?
+/// - SQLite provides a mechanism for defining custom collations via the `create_collation` method
+///   in the `rusqlite` crate.
+/// - The `unicase` crate simplifies case-insensitive comparisons by normalizing strings before
+///   comparing them.
+/// - When this collation is applied in SQLite queries, it ensures that the string comparison respects
+///   Unicode case-folding rules.
+///
+/// # Arguments
+/// - `conn`: A reference to the SQLite `Connection` object where the collation should be registered.
+///
+/// # Returns
+/// - `Ok(())` if the collation is registered successfully.
+/// - An error if SQLite fails to register the collation.
+///
+/// # Usage
+/// This function is typically called during database initialization, after opening a connection.
+/// The `unicase` collation can then be used in queries:
+///
+/// ```sql
+/// SELECT name
+/// FROM decks
+/// WHERE name COLLATE unicase LIKE '%example%'
+/// ORDER BY name COLLATE unicase;
+/// ```
+///
+/// # Example
+/// ```rust,ignore
+/// let conn = Connection::open("example.db")?;
+/// register_unicase_collation(&conn)?;
+/// ```
+fn register_unicase_collation(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_collation("unicase", |s1: &str, s2: &str| {
+        let s1_key = UniCase::new(s1);
+        let s2_key = UniCase::new(s2);
+        s1_key.cmp(&s2_key)
+    })?;
+    Ok(())
+}
+
+/// Opens a SQLite database and registers the `unicase` collation for Unicode case-insensitivity.
+///
+/// # Why is this needed?
+/// When working with SQLite databases that contain non-ASCII text, such as Unicode deck names in Anki,
+/// the default case-insensitive collation (`NOCASE`) is insufficient because it does not handle Unicode
+/// characters correctly. Without registering a custom collation, queries involving case-insensitive
+/// matches may fail or produce incomplete results.
+///
+/// This function abstracts the process of opening a SQLite connection and ensuring that the custom
+/// `unicase` collation is available for all queries that require Unicode case-insensitivity.
+///
+/// # How does it work?
+/// - This function opens an existing SQLite database read-write using the given file path. It is never
+///   created, and the WAL journal Anki uses is picked up from the file itself.
+/// - It sets a busy timeout of `BUSY_TIMEOUT`, so a lock Anki holds only briefly is waited out instead
+///   of failing with `SQLITE_BUSY`.
+/// - After opening the connection, it registers the `unicase` collation by calling `register_unicase_collation`.
+/// - This ensures that any subsequent queries can use the `unicase` collation.
+///
+/// # Arguments
+/// - `db_path`: The file path to the SQLite database. This should be a valid path to an existing database file.
+///
+/// # Returns
+/// - `Ok(Connection)` if the database is opened and the collation is registered successfully.
+/// - An error if the database cannot be opened or the collation fails to register.
+///
+/// # Usage
+/// Use this function instead of directly calling `Connection::open` to ensure that the custom collation is
+/// registered automatically.
+///
+/// # Example
+/// ```rust,no_run
+/// # use anki_streak_fixer::open_database_with_collation;
+/// # fn main() -> rusqlite::Result<()> {
+/// let conn = open_database_with_collation("example.db")?;
+/// let query = "SELECT name FROM decks WHERE name COLLATE unicase LIKE '%example%' ORDER BY name COLLATE unicase;";
+/// let mut stmt = conn.prepare(query)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn open_database_with_collation(db_path: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    register_unicase_collation(&conn)?;
+    Ok(conn)
+}
+
+/// Returns `true` if another process (normally Anki itself) holds a lock on the collection.
+///
+/// Probes by starting and immediately rolling back a write transaction without waiting, so a
+/// collection that is merely present on disk is never modified.
+fn collection_in_use(db_path: &Path) -> rusqlite::Result<bool> {
+    let conn = Connection::open(db_path)?;
+    conn.busy_timeout(std::time::Duration::ZERO)?;
+    match conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;") {
+        Ok(()) => Ok(false),
+        Err(rusqlite::Error::SqliteFailure(err, _))
+            if matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) =>
+        {
+            Ok(true)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1;")?
+        .exists(params![table])
+}
+
+fn table_has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    if !table_exists(conn, table)? {
+        return Ok(false);
+    }
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({});", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(columns.iter().any(|name| name == column))
+}
+
+/// Lists the decks of the collection with a number each and asks which one to process, for
+/// `--interactive`. Answering `all` selects every deck; an unknown answer asks again.
+pub fn pick_deck(
+    conn: &Connection,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    interactive: bool,
+) -> Result<AppMode, AppError> {
+    if !interactive {
+        return Err(AppError::Config(
+            "--interactive needs a terminal to ask on. Give a deck name instead.".to_string(),
+        ));
+    }
+    let decks: Vec<String> = queries::deck_names(conn)?
+        .iter()
+        .map(|name| replace_deck_delimiter(name))
+        .collect();
+    for (index, deck) in decks.iter().enumerate() {
+        writeln!(output, "{:>3}  {}", index + 1, deck)?;
+    }
+
+    loop {
+        write!(output, "Deck number, or 'all' for every deck: ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(AppError::Cancelled);
+        }
+        let answer = answer.trim();
+        if answer.eq_ignore_ascii_case("all") {
+            return Ok(AppMode::All);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=decks.len()).contains(&number) => {
+                return Ok(AppMode::Decks(vec![decks[number - 1].clone()]))
+            }
+            _ => writeln!(output, "Please enter a number from 1 to {}, or 'all'.", decks.len())?,
+        }
+    }
+}
+
+/// Lists the collections `--scan` found under `root`, each with its folder (most likely the
+/// profile name), and returns the one to use. A single collection is used after confirming;
+/// between several, the user picks one by number.
+pub fn pick_collection(
+    root: &Path,
+    found: &[PathBuf],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    interactive: bool,
+) -> Result<PathBuf, AppError> {
+    if found.is_empty() {
+        return Err(AppError::Config(format!("No collection.anki2 found under '{}'.", root.display())));
+    }
+    for (index, path) in found.iter().enumerate() {
+        let profile = path
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        writeln!(output, "{:>3}  {}  ({})", index + 1, profile, path.display())?;
+    }
+
+    if !interactive {
+        if let [only] = found {
+            return Ok(only.clone());
+        }
+        return Err(AppError::Config(format!(
+            "Found {} collections under '{}'. Pass the one to use with --collection-path.",
+            found.len(),
+            root.display()
+        )));
+    }
+
+    let prompt = if found.len() == 1 {
+        "Use this collection? [Y/n] ".to_string()
+    } else {
+        format!("Collection number (1-{}): ", found.len())
+    };
+    loop {
+        write!(output, "{}", prompt)?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(AppError::Cancelled);
+        }
+        let answer = answer.trim().to_lowercase();
+        if found.len() == 1 {
+            return match answer.as_str() {
+                "" | "y" | "yes" => Ok(found[0].clone()),
+                _ => Err(AppError::Cancelled),
+            };
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=found.len()).contains(&number) => return Ok(found[number - 1].clone()),
+            _ => writeln!(output, "Please enter a number from 1 to {}.", found.len())?,
+        }
+    }
+}
+
+/// Which decks a run works on.
+#[derive(Debug, PartialEq)]
+pub enum AppMode {
+    Decks(Vec<String>), // Contains the deck names given on the command line
+    Pattern(String),    // Decks whose names match a `--deck-pattern` glob
+    DeckId(i64),        // The deck with a `--deck-id`, bypassing name matching
+    NoteIds(Vec<i64>),  // The notes given with `--note-ids`, bypassing deck and tag selection
+    All,                // All decks
+}
+
+/// Which notes `--limit` keeps when more notes match than the limit allows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitOrder {
+    NoteId, // Lowest note ids first
+    Random, // A random sample, reproducible with `--seed`
+    Recent, // Notes with the latest reviews in the window first
+    Oldest, // Notes with the earliest reviews in the window first
+}
+
+/// What `mod` is written to the cards whose reviews moved, with `--mod-time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModTime {
+    Now,    // The time of the run
+    Review, // The new time of the card's latest moved review
+    Keep,   // The card's current `mod`, unchanged
+}
+
+/// What `--newest-only` does with a card's earlier reviews of a day on which it was reviewed again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OlderReviews {
+    Leave,  // Leave them on the day they were made
+    Delete, // Delete them, recording them in the undo journal
+}
+
+/// Whether `--limit` caps the notes of the whole run or of each matched deck.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitScope {
+    Global,  // At most `limit` notes in total
+    PerDeck, // At most `limit` notes from each home deck
+}
+
+/// Options of a run that are not specific to one processor call. `Default` gives the behavior
+/// of the command line without any flags.
+pub struct AppConfig {
+    pub mode: AppMode,
+    pub cap_review_time: Option<i64>, // Maximum answer duration (ms) kept on shifted reviews
+    pub mod_time: ModTime,            // What `cards.mod` of touched cards is set to
+    pub csv: Option<PathBuf>,         // Where to write the audit CSV of moved reviews
+    pub timezone: date::Zone,         // Zone the Anki days are counted in
+    pub keep_backups: Option<usize>,  // Number of backups to retain; `None` keeps all
+    pub limit_order: LimitOrder,
+    pub limit_scope: LimitScope,
+    pub seed: Option<u64>,            // Seed for random sampling; `None` is nondeterministic
+    pub backup: bool,                 // Copy the collection aside before writing
+    pub backup_dir: Option<PathBuf>,  // Where backups go; defaults to the collection's directory
+    pub compact: bool,                // VACUUM the collection after a real run
+    pub check_landing: bool,          // Confirm moved reviews landed in the target day
+    pub verify: bool,                 // Recount the target day's reviews after the move
+    pub strict: bool,                 // Treat a failed landing check as an error
+    pub json: bool,                   // Print a JSON report instead of human-readable output
+    pub quiet: bool,                  // Print only errors, warnings and the final summary
+    pub rollover: Option<NaiveTime>,  // Overrides the rollover time read from the collection
+    pub force: bool,                  // Run even if the collection looks open in Anki
+    pub force_schema: bool,           // Run on a collection whose schema version isn't supported
+    pub exclude_subdecks: bool,       // Match only the named deck, not its children
+    pub case_sensitive: bool,         // Match deck names and patterns with exact case
+    pub tag: Option<String>,          // Only notes carrying this tag
+    pub note_type: Option<String>,    // Only notes of this note type
+    pub review_types: Option<Vec<i64>>, // Only reviews with these `revlog.type` values; `None` moves all
+    pub reschedule: bool,         // Move the due date of touched review cards along with their reviews
+    pub by_card: bool,            // Select and move reviews card by card instead of note by note
+    pub newest_only: Option<OlderReviews>, // Move only each card's latest review of a day; what happens to the rest
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            mode: AppMode::All,
+            cap_review_time: None,
+            mod_time: ModTime::Now,
+            csv: None,
+            timezone: date::Zone::Local,
+            keep_backups: None,
+            limit_order: LimitOrder::NoteId,
+            limit_scope: LimitScope::Global,
+            seed: None,
+            backup: true,
+            backup_dir: None,
+            compact: false,
+            check_landing: true,
+            verify: false,
+            strict: false,
+            json: false,
+            quiet: false,
+            rollover: None,
+            force: false,
+            force_schema: false,
+            exclude_subdecks: false,
+            case_sensitive: false,
+            tag: None,
+            note_type: None,
+            review_types: None,
+            reschedule: false,
+            by_card: false,
+            newest_only: None,
+        }
+    }
+}
+
+/// Current scheduling state of an affected card, shown by the simulate impact report.
+#[derive(Debug, PartialEq)]
+struct CardSchedule {
+    id: i64,
+    queue: i64,
+    due: i64,
+}
+
+/// Describes an Anki `cards.queue` value.
+fn queue_name(queue: i64) -> &'static str {
+    match queue {
+        0 => "new",
+        1 | 3 => "learning",
+        2 => "review",
+        4 => "preview",
+        -1 => "suspended",
+        -2 | -3 => "buried",
+        _ => "unknown",
+    }
+}
+
+/// A card whose reviews would move onto a day on which it already has a review of its own.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DestinationConflict {
+    pub note_id: i64,
+    pub card_id: i64,
+}
+
+/// Number of distinct notes and cards affected within a single deck.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DeckCounts {
+    pub notes: usize,
+    pub cards: usize,
+}
+
+/// Counts of what a run changed (or, in simulate mode, would change).
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub notes: usize,
+    pub cards: usize,
+    pub reviews: usize,
+}
+
+impl RunSummary {
+    /// One-line summary of the counts, worded for a real or a simulated run.
+    pub fn message(&self, simulate: bool) -> String {
+        format!(
+            "{} {} reviews across {} cards in {} notes.",
+            if simulate { "Would move" } else { "Moved" },
+            self.reviews,
+            self.cards,
+            self.notes
+        )
+    }
+}
+
+/// Everything a run did (or, in simulate mode, would do). Printed as JSON with `--json`.
+#[derive(Debug, Default, Serialize)]
+pub struct RunReport {
+    pub collection: String,
+    pub mode: String,         // "deck", "pattern" or "all"
+    pub decks: Vec<String>,   // Deck names or pattern as given on the command line
+    pub from_date: String,
+    pub to_date: String,
+    pub rollover_hours: i64,  // Hour of the rollover time; `rollover` also has the minutes
+    pub rollover: String,     // Rollover time as HH:MM
+    pub offset_days: i64,     // Days every affected review is moved back by
+    pub span_days: i64,       // Consecutive days moved, ending at from_date
+    pub simulate: bool,
+    pub note_ids: Vec<i64>,
+    pub card_ids: Vec<i64>,
+    pub revlog_ids: Vec<i64>, // Ids of the moved reviews after the move
+    pub skipped_reviews: usize, // Reviews left in place because moving them would predate their card
+    pub rescheduled_cards: usize, // Cards whose due date `--reschedule` moved
+    pub superseded_reviews: usize, // Earlier same-day reviews `--newest-only` left in place or deleted
+    pub destination_conflicts: Vec<DestinationConflict>, // Simulate only: cards already reviewed on the target days
+    pub by_deck: BTreeMap<String, DeckCounts>,
+    pub journal: Option<String>, // Undo journal written by a real run
+    pub verified: Option<bool>,  // With `--verify` on a real run: whether the target days' count matched
+    pub summary: RunSummary,
+}
+
+/// What an undo restored (or, in simulate mode, would restore). Printed as JSON with `--json`.
+#[derive(Debug, Default, Serialize)]
+pub struct UndoReport {
+    pub collection: String,
+    pub journal: String,
+    pub simulate: bool,
+    pub restored_reviews: usize, // Moved reviews returned to their original ids
+    pub reinserted_reviews: usize, // Reviews deleted by `--newest-only delete` put back
+    pub cards: usize,            // Cards whose mod, usn and due date were restored
+}
+
+/// A collection that `--collection-glob` could not process, with the reason.
+#[derive(Debug, Serialize)]
+pub struct CollectionFailure {
+    pub collection: String,
+    pub error: String,
+}
+
+/// Everything a `--collection-glob` run did, one collection after another. Printed as JSON with
+/// `--json`.
+#[derive(Debug, Default, Serialize)]
+pub struct MultiRunReport {
+    pub collections: Vec<RunReport>,
+    pub failures: Vec<CollectionFailure>,
+    pub summary: RunSummary, // Totals over `collections`
+}
+
+impl MultiRunReport {
+    /// Combines the reports of the collections that were processed with the failures of those
+    /// that weren't.
+    pub fn new(collections: Vec<RunReport>, failures: Vec<CollectionFailure>) -> Self {
+        let mut summary = RunSummary::default();
+        for report in &collections {
+            summary.notes += report.summary.notes;
+            summary.cards += report.summary.cards;
+            summary.reviews += report.summary.reviews;
+        }
+        Self { collections, failures, summary }
+    }
+}
+
+/// A collection in the Anki profiles directory, named after its profile.
+#[derive(Debug)]
+pub struct AnkiCollection {
+    collection_name: String,
+}
+
+impl AnkiCollection {
+    pub fn new(collection_name: &str) -> Self {
+        Self {
+            collection_name: collection_name.to_string(),
+        }
+    }
+
+    /// Path of the profile's `collection.anki2` under [`AnkiCollection::profiles_dir`].
+    pub fn collection_path(&self) -> PathBuf {
+        Self::profiles_dir()
+            .join(&self.collection_name)
+            .join("collection.anki2")
+    }
+
+    /// Returns the directory holding the profiles: `$ANKI_BASE` when set, as with Anki's own
+    /// `-b` option, otherwise the default `Anki2` directory of this OS.
+    pub fn profiles_dir() -> PathBuf {
+        match env::var_os("ANKI_BASE").filter(|base| !base.is_empty()) {
+            Some(base) => {
+                let base = PathBuf::from(shellexpand::tilde(&base.to_string_lossy()).to_string());
+                debug!("Using the Anki base folder '{}' from ANKI_BASE.", base.display());
+                base
+            }
+            None => {
+                let base = Self::base_dir(env::consts::OS);
+                debug!("Using the default Anki base folder '{}'.", base.display());
+                base
+            }
+        }
+    }
+
+    /// Lists the profile names under `base_dir` that contain a `collection.anki2`, sorted by name.
+    pub fn list_collections(base_dir: &Path) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(base_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && entry.path().join("collection.anki2").is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Lists the profile names under `base_dir` that match the glob `pattern` (see
+    /// [`utils::glob_matches`]) and contain a `collection.anki2`, sorted by name.
+    pub fn matching(base_dir: &Path, pattern: &str) -> io::Result<Vec<String>> {
+        let mut names = Self::list_collections(base_dir)?;
+        names.retain(|name| utils::glob_matches(pattern, name));
+        Ok(names)
+    }
+
+    /// Finds every `collection.anki2` below `root`, at any depth, sorted by path. Symbolic links
+    /// are not followed, and subdirectories that can't be read are skipped.
+    pub fn scan(root: &Path) -> io::Result<Vec<PathBuf>> {
+        fn walk(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    let _ = walk(&entry.path(), found);
+                } else if file_type.is_file() && entry.file_name() == "collection.anki2" {
+                    found.push(entry.path());
+                }
+            }
+            Ok(())
+        }
+
+        let mut found = Vec::new();
+        walk(root, &mut found)?;
+        found.sort();
+        Ok(found)
+    }
+
+    /// Returns the `Anki2` directory holding the profiles for the given OS.
+    ///
+    /// On Windows this is resolved from `%APPDATA%`, falling back to
+    /// `%USERPROFILE%\AppData\Roaming` when `APPDATA` is not set.
+    fn base_dir(os: &str) -> PathBuf {
+        let home = || PathBuf::from(shellexpand::tilde("~").to_string());
+        match os {
+            "macos" => home().join("Library").join("Application Support").join("Anki2"),
+            "windows" => {
+                let roaming = env::var_os("APPDATA")
+                    .map(PathBuf::from)
+                    .or_else(|| {
+                        env::var_os("USERPROFILE")
+                            .map(|profile| PathBuf::from(profile).join("AppData").join("Roaming"))
+                    })
+                    .expect("Neither APPDATA nor USERPROFILE is set");
+                roaming.join("Anki2")
+            }
+            "linux" => home().join(".local").join("share").join("Anki2"),
+            _ => panic!("Unsupported OS"),
+        }
+    }
+}
+
+/// Moves, reports on and restores the reviews of one collection file.
+pub struct AnkiProcessor<'a> {
+    simulate: bool,
+    db_path: PathBuf,
+    limit: i64,
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+    span_days: i64, // Consecutive Anki days, ending at `from_date`, whose reviews are moved
+    confirm: bool,  // Ask before writing to the collection
+    zone: OnceCell<date::Zone>, // Zone the Anki days are counted in, once read from the collection
+    config: &'a AppConfig,
+}
+
+impl<'a> AnkiProcessor<'a> {
+    /// Creates a processor for the collection at `db_path` that moves the reviews of `from_date`
+    /// to `to_date`. Without dates, today's reviews are moved to yesterday.
+    pub fn new(
+        db_path: PathBuf,
+        simulate: bool,
+        limit: i64,
+        from_date: Option<NaiveDate>,
+        to_date: Option<NaiveDate>,
+        config: &'a AppConfig,
+    ) -> Self {
+        Self {
+            //deck_name: deck_name.to_string(),
+            simulate,
+            db_path,
+            limit,
+            from_date,
+            to_date,
+            span_days: 1,
+            confirm: false,
+            zone: OnceCell::new(),
+            config,
+        }
+    }
+
+    /// Makes real runs stop for a y/N confirmation once the affected notes are known.
+    pub fn require_confirmation(&mut self) {
+        self.confirm = true;
+    }
+
+    /// Moves the reviews of `days` consecutive Anki days ending at `from_date` instead of just
+    /// that one day.
+    pub fn set_span_days(&mut self, days: i64) {
+        self.span_days = days.max(1);
+    }
+
+    /// Points the processor at a copy of the collection so the source file is never written.
+    ///
+    /// The source is copied to `target` first and all subsequent reads and writes go to the copy.
+    /// In simulate mode nothing is written anywhere, so no copy is made and the source is read
+    /// directly. An existing `target` is refused unless `overwrite` is set.
+    pub fn redirect_to_target(&mut self, target: &Path, overwrite: bool) -> io::Result<()> {
+        if target.exists() && !overwrite {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Target collection '{}' already exists. Use --overwrite to replace it.",
+                    target.display()
+                ),
+            ));
+        }
+
+        if self.simulate {
+            debug!("Simulation mode: target collection will not be written.");
+            return Ok(());
+        }
+
+        debug!("Copying '{}' to target collection '{}'", self.db_path.display(), target.display());
+        fs::copy(&self.db_path, target)?;
+        self.db_path = target.to_path_buf();
+        Ok(())
+    }
+
+    /// Fails with a message naming the path tried when the collection file does not exist, which
+    /// SQLite would otherwise report without saying where it looked.
+    fn ensure_collection_exists(&self) -> Result<(), AppError> {
+        if self.db_path.exists() {
+            return Ok(());
+        }
+        let profile_exists = self.db_path.parent().is_some_and(|dir| dir.is_dir());
+        Err(AppError::MissingCollection { path: self.db_path.clone(), profile_exists })
+    }
+
+    /// Opens the collection with the `unicase` collation registered. Each operation opens it once
+    /// and hands the connection to every step.
+    fn open_collection(&self) -> Result<Connection> {
+        Ok(open_database_with_collation(self.db_path.to_str().unwrap())?)
+    }
+
+    /// Prints human-readable progress output, which is suppressed with `--json` and `--quiet`.
+    fn print(&self, message: &str) {
+        if !self.config.json && !self.config.quiet {
+            println!("{}", message);
+        }
+    }
+
+    /// Explains the full sync that a committed `scm` bump forces.
+    fn print_sync_notice(&self) {
+        self.print(&yellow_text(FULL_SYNC_NOTICE));
+    }
+
+    /// Moves the reviews of the selected decks, or with `simulate` only reports what would move.
+    pub fn process(&self) -> Result<RunReport, AppError> {
+        debug!("Starting processing...");
+        self.ensure_collection_exists()?;
+        let started = std::time::Instant::now();
+        if self.simulate {
+            self.print(&format!(
+                "Running {} v{} - {}",
+                APP_NAME,
+                APP_VERSION,
+                green_text("Simulation mode")
+            ));
+        } else {
+            self.print(&format!("Running {} v{}", APP_NAME, APP_VERSION));
+        }
+
+        // Every read and the final write go through this one connection, so they see the same
+        // collection
+        let mut conn = self.open_collection()?;
+        self.ensure_supported_schema(self.check_schema(&conn)?)?;
+        if !self.simulate {
+            self.ensure_not_in_use()?;
+        }
+
+        let rollover = self.get_rollover(&conn)?;
+        self.load_zone(&conn)?;
+        let today = Local::now().date_naive();
+
+        // Use from_date if provided, otherwise use today
+        let base_date = self.from_date.unwrap_or(today);
+        let target_date = self.to_date.unwrap_or_else(|| base_date.pred_opt().unwrap());
+        let first_date = base_date - chrono::Duration::days(self.span_days - 1);
+        let rid_string = self.generate_rid_string(first_date, base_date, rollover)?;
+
+        // Only reviews still inside the source days are moved, so repeating a run leaves the
+        // reviews it already moved alone. That only holds while the destination days don't
+        // overlap the source days, as with `--days 2 --shift 1`.
+        let source_start = self.anki_day_window(first_date, rollover)?.0;
+        let (_, destination_end) = self.target_window(target_date, rollover)?;
+        if destination_end > source_start && !self.simulate {
+            eprintln!(
+                "Warning: the days reviews are moved to overlap the days they are moved from; running this again \
+                 would move some of them a second time."
+            );
+        }
+
+        let mut report = RunReport {
+            collection: self.db_path.display().to_string(),
+            mode: match &self.config.mode {
+                AppMode::Decks(_) => "deck".to_string(),
+                AppMode::Pattern(_) => "pattern".to_string(),
+                AppMode::DeckId(_) => "deck-id".to_string(),
+                AppMode::NoteIds(_) => "note-ids".to_string(),
+                AppMode::All => "all".to_string(),
+            },
+            decks: match &self.config.mode {
+                AppMode::Decks(deck_names) => deck_names.clone(),
+                AppMode::Pattern(pattern) => vec![pattern.clone()],
+                AppMode::DeckId(id) => vec![id.to_string()],
+                AppMode::NoteIds(_) | AppMode::All => Vec::new(),
+            },
+            from_date: base_date.to_string(),
+            to_date: target_date.to_string(),
+            rollover_hours: i64::from(rollover.hour()),
+            rollover: rollover.format("%H:%M").to_string(),
+            offset_days: self.id_offset() / 86_400_000,
+            span_days: self.span_days,
+            simulate: self.simulate,
+            ..Default::default()
+        };
+
+        // Resolve the deck hierarchies once; they select the notes and scope the moves
+        let matching_decks = self.matching_decks(&conn)?;
+        let note_ids = self.fetch_reviewed_notes(&conn, matching_decks.as_deref(), rollover)?;
+        trace!("Selected {} notes after {:?}", note_ids.len(), started.elapsed());
+
+        if note_ids.is_empty() {
+            let msg = match &self.config.mode {
+                AppMode::All => format!("No notes found in any deck for {}", base_date),
+                AppMode::Decks(deck_names) => format!(
+                    "No notes found in the deck '{}' for {}",
+                    deck_names.join("', '"), base_date
+                ),
+                AppMode::Pattern(pattern) => format!(
+                    "No notes found in decks matching '{}' for {}",
+                    pattern, base_date
+                ),
+                AppMode::DeckId(id) => format!("No notes found in the deck with id {} for {}", id, base_date),
+                AppMode::NoteIds(_) => format!("None of the given notes have reviews on {}", base_date),
+            };
+
+            self.print(&msg);
+        } else {
+            if self.confirm && !self.simulate {
+                let summary = self.confirmation_summary(first_date, base_date, target_date, note_ids.len());
+                let stdin = io::stdin();
+                if !self.confirm_run(&summary, &mut stdin.lock(), stdin.is_terminal())? {
+                    return Err(AppError::Cancelled);
+                }
+            }
+            // With --verify, count the target days' reviews before the move to compare against
+            let verify = if self.config.verify && !self.simulate {
+                let window = self.target_window(target_date, rollover)?;
+                let before = queries::review_ids(&conn, window, matching_decks.as_deref())?.len();
+                Some((window, before))
+            } else {
+                None
+            };
+            let id_offset = self.dst_aware_id_offset(base_date, target_date, rollover)?;
+            // With --newest-only a card's reviews are compared within each source day
+            let day_ends = match self.config.newest_only {
+                Some(_) => Some(
+                    first_date
+                        .iter_days()
+                        .take_while(|date| *date <= base_date)
+                        .map(|date| Ok(self.anki_day_window(date, rollover)?.1))
+                        .collect::<Result<Vec<i64>>>()?,
+                ),
+                None => None,
+            };
+            let scope = queries::ReviewScope {
+                decks: matching_decks.as_deref(),
+                types: self.config.review_types.as_deref(),
+                by_card: self.config.by_card,
+                day_ends: day_ends.as_deref(),
+            };
+            self.process_notes(&mut conn, note_ids, &scope, &rid_string, id_offset, &mut report)?;
+            trace!("Processed the notes after {:?}", started.elapsed());
+
+            if self.config.check_landing && !self.simulate {
+                self.check_landing(&conn, &report.revlog_ids, target_date, rollover)?;
+            }
+            if let Some((window, before)) = verify {
+                report.verified = Some(self.verify_target_count(
+                    &conn,
+                    window,
+                    matching_decks.as_deref(),
+                    before,
+                    report.revlog_ids.len(),
+                )?);
+            }
+        }
+        report.summary = RunSummary {
+            notes: report.note_ids.len(),
+            cards: report.card_ids.len(),
+            reviews: report.revlog_ids.len(),
+        };
+
+        if self.config.compact && !self.simulate {
+            self.compact(&conn)?;
+        }
+
+        debug!("Processing completed.");
+        trace!("Run took {:?}", started.elapsed());
+        Ok(report)
+    }
+
+    /// Describes what a real run is about to change, for the confirmation prompt.
+    fn confirmation_summary(&self, first: NaiveDate, last: NaiveDate, target: NaiveDate, notes: usize) -> String {
+        let decks = match &self.config.mode {
+            AppMode::All => "all decks".to_string(),
+            AppMode::Decks(deck_names) => deck_names.join(", "),
+            AppMode::Pattern(pattern) => format!("decks matching '{}'", pattern),
+            AppMode::DeckId(id) => format!("the deck with id {}", id),
+            AppMode::NoteIds(ids) => format!("{} notes given by id", ids.len()),
+        };
+        let days = if first == last {
+            last.to_string()
+        } else {
+            format!("{} to {}", first, last)
+        };
+        format!(
+            "About to modify '{}'\n  Decks: {}\n  Moving reviews from {} back {} days (to {})\n  Notes affected: {}",
+            self.db_path.display(),
+            decks,
+            days,
+            self.id_offset() / 86_400_000,
+            target,
+            notes
+        )
+    }
+
+    /// Shows `summary` and asks for a y/N answer on `input`. Anything but `y` or `yes` declines.
+    ///
+    /// Without a terminal to ask on, or with `--json`, the run is refused outright so that a
+    /// script can never write to the collection without passing `--yes`.
+    fn confirm_run(&self, summary: &str, input: &mut impl BufRead, interactive: bool) -> Result<bool, AppError> {
+        if self.config.json || !interactive {
+            return Err(AppError::ConfirmationRequired);
+        }
+        println!("{}", summary);
+        print!("Proceed? [y/N] ");
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    /// Confirms the file is an Anki collection before anything else touches it, and returns its
+    /// schema version (`col.ver`).
+    fn check_schema(&self, conn: &Connection) -> Result<i64, AppError> {
+        let not_a_collection = |detail: &str| AppError::NotACollection {
+            path: self.db_path.clone(),
+            detail: detail.to_string(),
+        };
+
+        for table in ANKI_TABLES {
+            match table_exists(conn, table) {
+                Ok(true) => {}
+                Ok(false) => return Err(not_a_collection(&format!("missing table '{}'", table))),
+                Err(rusqlite::Error::SqliteFailure(err, _)) if err.code == rusqlite::ErrorCode::NotADatabase => {
+                    return Err(not_a_collection("not an SQLite database"));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let version: i64 = conn
+            .query_row("SELECT ver FROM col;", [], |row| row.get(0))
+            .map_err(|_| not_a_collection("no schema version in 'col'"))?;
+        debug!("Collection schema version: {}", version);
+        Ok(version)
+    }
+
+    /// Refuses to modify a collection whose schema version is outside `SUPPORTED_SCHEMA_VERSIONS`,
+    /// such as one upgraded by a newer Anki, unless `--force-schema` is set.
+    fn ensure_supported_schema(&self, version: i64) -> Result<(), AppError> {
+        if SUPPORTED_SCHEMA_VERSIONS.contains(&version) {
+            return Ok(());
+        }
+        let message = format!(
+            "The collection has schema version {}, but only versions {} to {} are supported.",
+            version,
+            SUPPORTED_SCHEMA_VERSIONS.start(),
+            SUPPORTED_SCHEMA_VERSIONS.end()
+        );
+        if self.config.force_schema {
+            eprintln!("Warning: {} Continuing because of --force-schema.", message);
+            return Ok(());
+        }
+        Err(AppError::UnsupportedCollection(format!(
+            "{} Update this tool, or pass --force-schema to run anyway.",
+            message
+        )))
+    }
+
+    /// Refuses to run while the collection is locked by another process, unless `--force` is set.
+    fn ensure_not_in_use(&self) -> Result<()> {
+        if !collection_in_use(&self.db_path)? {
+            return Ok(());
+        }
+        if self.config.force {
+            eprintln!("Warning: the collection appears to be open in Anki; continuing because of --force.");
+            return Ok(());
+        }
+        Err(AppError::CollectionInUse)
+    }
+
+    /// Re-reads the moved revlog rows and confirms each now falls inside the Anki day of
+    /// `target_date`.
+    ///
+    /// Reviews outside that window point at a DST or rollover miscalculation. They are reported
+    /// as a warning, or as an error with `--strict`.
+    fn check_landing(&self, conn: &Connection, moved_ids: &[i64], target_date: NaiveDate, rollover: NaiveTime) -> Result<()> {
+        debug!("Checking that {} moved reviews landed on {}", moved_ids.len(), target_date);
+
+        let mut stmt = conn.prepare("SELECT id FROM revlog WHERE id = ?1;")?;
+        let mut present = Vec::with_capacity(moved_ids.len());
+        for id in moved_ids {
+            if stmt.exists(params![id])? {
+                present.push(*id);
+            }
+        }
+
+        let window = self.target_window(target_date, rollover)?;
+        let misplaced = date::misplaced_reviews(&present, window);
+        let missing = moved_ids.len() - present.len();
+        if misplaced.is_empty() && missing == 0 {
+            debug!("All moved reviews landed on the target day.");
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} moved reviews did not land on {} ({} misplaced, {} missing): {:?}",
+            misplaced.len() + missing,
+            target_date,
+            misplaced.len(),
+            missing,
+            misplaced
+        );
+        if self.config.strict {
+            return Err(AppError::MisplacedReviews(message));
+        }
+        eprintln!("Warning: {}", message);
+        Ok(())
+    }
+
+    /// Returns the revlog id range of the Anki days the reviews are moved to: `span_days` days
+    /// ending with `target_date`.
+    fn target_window(&self, target_date: NaiveDate, rollover: NaiveTime) -> Result<(i64, i64)> {
+        let first_date = target_date - chrono::Duration::days(self.span_days - 1);
+        Ok((
+            self.anki_day_window(first_date, rollover)?.0,
+            self.anki_day_window(target_date, rollover)?.1,
+        ))
+    }
+
+    /// Recounts the reviews of the selected decks in the target `window` after a real run and
+    /// prints the counts unless it grew by exactly the `moved` reviews. Returns whether it did.
+    ///
+    /// Unlike the landing check, which follows the moved ids, this re-runs the day query the way
+    /// `--report` buckets reviews, so it also notices rows that went missing from the target day.
+    fn verify_target_count(
+        &self,
+        conn: &Connection,
+        window: (i64, i64),
+        decks: Option<&[String]>,
+        before: usize,
+        moved: usize,
+    ) -> Result<bool> {
+        let after = queries::review_ids(conn, window, decks)?.len();
+        if after == before + moved {
+            debug!("Verified: the target days now have {} reviews ({} + {} moved).", after, before, moved);
+            return Ok(true);
+        }
+        eprintln!(
+            "Verification failed: expected {} reviews on the target days ({} + {} moved), found {}.",
+            before + moved,
+            before,
+            moved,
+            after
+        );
+        Ok(false)
+    }
+
+    /// Runs `VACUUM` on the collection to reclaim the space left behind by rewriting the revlog.
+    ///
+    /// Only called once all changes have been committed.
+    fn compact(&self, conn: &Connection) -> Result<()> {
+        self.print("Compacting collection. VACUUM rewrites the whole file and can be slow on large collections...");
+        conn.execute_batch("VACUUM;")?;
+        debug!("Compaction finished.");
+        Ok(())
+    }
+
+    /// Directory backups are written to: `--backup-dir` if given, otherwise next to the collection.
+    fn backup_dir(&self) -> PathBuf {
+        match &self.config.backup_dir {
+            Some(dir) => dir.clone(),
+            None => self
+                .db_path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .to_path_buf(),
+        }
+    }
+
+    /// Copies the collection to a timestamped backup before it is modified.
+    ///
+    /// Any failure is returned so the caller can abort before touching the database.
+    fn backup_collection(&self) -> Result<()> {
+        if !self.config.backup {
+            debug!("Skipping backup (--no-backup).");
+            return Ok(());
+        }
+
+        let backup_dir = self.backup_dir();
+        let quarantined =
+            backup::quarantine_incomplete_backups(&backup_dir, &self.db_path).map_err(AppError::Backup)?;
+        for path in quarantined {
+            eprintln!(
+                "Warning: found an incomplete backup from an interrupted run; moved it to '{}'",
+                path.display()
+            );
+        }
+
+        let backup = backup::create_backup(&self.db_path, &backup_dir).map_err(AppError::Backup)?;
+        self.print(&format!("Backed up collection to '{}'", backup.path.display()));
+        debug!("Backup checksum (SHA-256): {}", backup.sha256);
+
+        self.prune_backups();
+        Ok(())
+    }
+
+    /// Removes backups beyond the `--keep-backups` limit. Failing to prune is not fatal.
+    fn prune_backups(&self) {
+        let Some(keep) = self.config.keep_backups else {
+            return;
+        };
+        match backup::prune_backups(&self.backup_dir(), &self.db_path, keep) {
+            Ok(removed) => {
+                for path in removed {
+                    debug!("Removed old backup '{}'", path.display());
+                }
+            }
+            Err(err) => eprintln!("Warning: failed to prune old backups: {}", err),
+        }
+    }
+
+    /// Determines the time of day at which Anki starts a new day.
+    ///
+    /// A `--rollover` override wins. Otherwise the value is looked up in the `config` table
+    /// (current Anki), then in the `rollover` field of the `col.conf` JSON (older Anki), and
+    /// finally defaults to Anki's own default of 4:00.
+    fn get_rollover(&self, conn: &Connection) -> Result<NaiveTime> {
+        if let Some(rollover) = self.config.rollover {
+            // A forgotten override silently shifts every day boundary, so always say so. The
+            // collection's own setting is only for the message; failing to read it is no error.
+            let collection = self.collection_rollover(conn).ok();
+            eprintln!("{}", yellow_text(&rollover_override_warning(rollover, collection)));
+            return Ok(rollover);
+        }
+        self.collection_rollover(conn)
+    }
+
+    /// Returns the rollover time the collection itself is configured with, ignoring `--rollover`.
+    fn collection_rollover(&self, conn: &Connection) -> Result<NaiveTime> {
+        debug!("Querying rollover hours.");
+        if let Some(rollover) = queries::rollover_from_config_table(conn)? {
+            debug!("Rollover from the config table: {}", rollover);
+            return Ok(rollover);
+        }
+        if let Some(rollover) = queries::rollover_from_col_conf(conn)? {
+            debug!("Rollover from col.conf: {}", rollover);
+            return Ok(rollover);
+        }
+
+        debug!(
+            "No rollover setting found in the collection; using Anki's default of {}:00.",
+            DEFAULT_ROLLOVER_HOURS
+        );
+        Ok(NaiveTime::from_hms_opt(DEFAULT_ROLLOVER_HOURS, 0, 0).unwrap())
+    }
+
+    /// Settles the zone Anki days are counted in: the one named with `--timezone`, else the
+    /// system's, as current Anki does. Only a collection on the v1 scheduler counts its days
+    /// from `col.crt` instead.
+    fn load_zone(&self, conn: &Connection) -> Result<date::Zone> {
+        if let Some(zone) = self.zone.get() {
+            return Ok(*zone);
+        }
+        let zone = match self.config.timezone {
+            date::Zone::Local if queries::scheduler_version(conn)? == 1 => {
+                // A legacy collection's crt falls on its own rollover, not an overridden one
+                date::Zone::from_crt(queries::creation_time(conn)?, self.collection_rollover(conn)?)
+                    .unwrap_or(date::Zone::Local)
+            }
+            zone => zone,
+        };
+        // Anki uses the creation offset only to number the days, so it is merely reported
+        if let Some(minutes_west) = queries::creation_offset(conn)? {
+            let local_minutes_west = -Local::now().offset().local_minus_utc() as i64 / 60;
+            if minutes_west != local_minutes_west {
+                debug!(
+                    "The collection was created at UTC{:+} minutes; the system is at UTC{:+} minutes now.",
+                    -minutes_west, -local_minutes_west
+                );
+            }
+        }
+        debug!("Counting Anki days in {:?}", zone);
+        Ok(*self.zone.get_or_init(|| zone))
+    }
+
+    /// The zone set by `load_zone`, or the configured one before the collection was read.
+    fn zone(&self) -> date::Zone {
+        self.zone.get().copied().unwrap_or(self.config.timezone)
+    }
+
+    /// Returns the `rid:<start>:<end>` revlog id range covering every Anki day from `first` to
+    /// `last` inclusive.
+    fn generate_rid_string(&self, first: NaiveDate, last: NaiveDate, rollover: NaiveTime) -> Result<String> {
+        let (start_time, _) = self.anki_day_window(first, rollover)?;
+        let (_, end_time) = self.anki_day_window(last, rollover)?;
+        Ok(format!("rid:{}:{}", start_time, end_time))
+    }
+
+    /// Returns the `[start, end)` revlog id range of the Anki day `date`.
+    fn anki_day_window(&self, date: NaiveDate, rollover: NaiveTime) -> Result<(i64, i64)> {
+        // Day boundaries are in the system's timezone unless --timezone or the collection names another
+        self.zone().day_window(date, rollover).map_err(AppError::InvalidDay)
+    }
+
+    /// Counts the reviews on every Anki day from `start` to `end` (inclusive) in the selected
+    /// decks and summarizes the streak. Nothing is written to the collection.
+    pub fn streak_report(&self, start: NaiveDate, end: NaiveDate) -> Result<report::StreakReport, AppError> {
+        self.ensure_collection_exists()?;
+        let conn = self.open_collection()?;
+        self.check_schema(&conn)?;
+        let rollover = self.get_rollover(&conn)?;
+        self.load_zone(&conn)?;
+        let dates: Vec<NaiveDate> = start.iter_days().take_while(|date| *date <= end).collect();
+        let windows = dates
+            .iter()
+            .map(|date| self.anki_day_window(*date, rollover))
+            .collect::<Result<Vec<(i64, i64)>>>()?;
+        let (Some(first), Some(last)) = (windows.first(), windows.last()) else {
+            return Ok(report::streak_report(Vec::new()));
+        };
+
+        let matching_decks = match &self.config.mode {
+            // A streak belongs to the whole collection, so single notes don't narrow it
+            AppMode::All | AppMode::NoteIds(_) => Vec::new(),
+            AppMode::Decks(_) | AppMode::Pattern(_) | AppMode::DeckId(_) => self.fetch_matching_decks(&conn)?,
+        };
+        let decks = (!matching_decks.is_empty()).then_some(matching_decks.as_slice());
+        let review_ids = queries::review_ids(&conn, (first.0, last.1), decks)?;
+
+        // `review_ids` is sorted, so each day's count is the distance between its window bounds
+        let days = dates
+            .into_iter()
+            .zip(windows)
+            .map(|(date, (day_start, day_end))| report::DayCount {
+                date,
+                reviews: review_ids.partition_point(|id| *id < day_end)
+                    - review_ids.partition_point(|id| *id < day_start),
+            })
+            .collect();
+        Ok(report::streak_report(days))
+    }
+
+    /// Counts the notes a run would move, honoring the dates, `--limit` and the deck selection,
+    /// without touching the collection.
+    pub fn count_notes(&self) -> Result<usize, AppError> {
+        self.ensure_collection_exists()?;
+        let conn = self.open_collection()?;
+        self.check_schema(&conn)?;
+        if let Some(from_date) = self.from_date {
+            let first_date = from_date - chrono::Duration::days(self.span_days - 1);
+            debug!("Counting notes reviewed from {} to {}", first_date, from_date);
+        }
+        let rollover = self.get_rollover(&conn)?;
+        self.load_zone(&conn)?;
+        let matching_decks = self.matching_decks(&conn)?;
+        Ok(self.fetch_reviewed_notes(&conn, matching_decks.as_deref(), rollover)?.len())
+    }
+
+    /// Prints the per-day review counts, the longest streak and the missing days.
+    pub fn print_streak_report(&self, streak_report: &report::StreakReport) {
+        for day in &streak_report.days {
+            if day.reviews == 0 {
+                self.print(&format!("{}  {}", day.date, yellow_text("no reviews")));
+            } else {
+                self.print(&format!("{}  {} reviews", day.date, day.reviews));
+            }
+        }
+        match &streak_report.longest_streak {
+            Some(streak) => self.print(&format!(
+                "Longest streak: {} days ({} to {})",
+                streak.days, streak.start, streak.end
+            )),
+            None => self.print("No reviews in this range."),
+        }
+        if streak_report.missing.is_empty() {
+            self.print("Missing days: none");
+        } else {
+            self.print(&format!(
+                "Missing days: {}",
+                streak_report
+                    .missing
+                    .iter()
+                    .map(|date| date.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    /// Fetches the decks matching every name given on the command line, together with their
+    /// children, and returns their union without duplicates.
+    fn fetch_matching_decks(&self, conn: &Connection) -> Result<Vec<String>, AppError> {
+        // Ensure this is only called in AppMode::Decks or AppMode::Pattern
+        let deck_names = match &self.config.mode {
+            AppMode::Decks(names) => names,
+            AppMode::Pattern(pattern) => return self.fetch_decks_matching_pattern(conn, pattern),
+            AppMode::DeckId(id) => return self.fetch_decks_with_id(conn, *id),
+            AppMode::All | AppMode::NoteIds(_) => {
+                return Err(AppError::ModeMisuse); // Protect against misuse
+            }
+        };
+
+        let mut seen = HashSet::new();
+        let mut matching_decks = Vec::new();
+        for deck_name in deck_names {
+            for name in self.fetch_decks_matching(conn, deck_name)? {
+                if seen.insert(name.clone()) {
+                    matching_decks.push(name);
+                }
+            }
+        }
+
+        Ok(matching_decks)
+    }
+
+    /// Fetches matching deck names where the name contains the provided deck name.
+    /// Ensures that the parent deck is processed if it matches or has children.
+    fn fetch_decks_matching(&self, conn: &Connection, deck_name: &str) -> Result<Vec<String>, AppError> {
+        // Pasted names often carry a stray (non-breaking) space; `trim` removes both
+        let trimmed = deck_name.trim();
+        if trimmed != deck_name {
+            debug!("Trimmed whitespace from the deck name {:?}, using {:?}", deck_name, trimmed);
+        }
+        let deck_name = trimmed;
+        debug!("Fetching matching deck names for '{}'", deck_name);
+
+        let matching_decks =
+            queries::decks_matching(conn, deck_name, self.config.exclude_subdecks, self.config.case_sensitive)?;
+
+        if matching_decks.is_empty() {
+            debug!("No decks found matching or under '{}'", deck_name);
+            // Show what the name could have meant rather than failing without a hint
+            let candidates = queries::decks_containing(conn, deck_name)?;
+            if candidates.is_empty() {
+                return Err(AppError::NoMatchingDeck(deck_name.to_string()));
+            }
+            return Err(AppError::AmbiguousDeck {
+                name: deck_name.to_string(),
+                candidates: candidates.iter().map(|d| replace_deck_delimiter(d)).collect(),
+            });
+        }
+
+        match matching_decks.len() {
+            1 => debug!("Single matching deck found: '{}'", matching_decks[0]),
+            _ => debug!(
+                "Parent deck '{}' contains the following child decks:\n{}",
+                deck_name,
+                matching_decks
+                    .iter()
+                    .map(|d| replace_deck_delimiter(d))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+
+        Ok(matching_decks)
+    }
+
+    /// Fetches the decks whose names match the `--deck-pattern` glob, plus their children unless
+    /// `--exclude-subdecks` is set, and prints them so the selection can be checked.
+    fn fetch_decks_matching_pattern(&self, conn: &Connection, pattern: &str) -> Result<Vec<String>, AppError> {
+        let matching_decks = if self.config.case_sensitive {
+            let glob = utils::deck_glob_to_glob(pattern);
+            debug!("Fetching deck names matching pattern '{}' (GLOB '{}')", pattern, glob.escape_debug());
+            queries::decks_matching_glob(conn, &glob, self.config.exclude_subdecks)?
+        } else {
+            let like = deck_glob_to_like(pattern);
+            debug!("Fetching deck names matching pattern '{}' (LIKE '{}')", pattern, like.escape_debug());
+            queries::decks_matching_like(conn, &like, self.config.exclude_subdecks)?
+        };
+
+        if matching_decks.is_empty() {
+            debug!("No decks found matching pattern '{}'", pattern);
+            return Err(AppError::NoMatchingDeck(pattern.to_string()));
+        }
+
+        self.print(&format!("Decks matching '{}':", pattern));
+        for name in &matching_decks {
+            self.print(&format!("  {}", green_text(&replace_deck_delimiter(name))));
+        }
+
+        Ok(matching_decks)
+    }
+
+    /// Fetches the deck with `id` and, unless `--exclude-subdecks` is set, its children.
+    fn fetch_decks_with_id(&self, conn: &Connection, id: i64) -> Result<Vec<String>, AppError> {
+        debug!("Fetching the deck with id {}", id);
+        let matching_decks = queries::decks_with_id(conn, id, self.config.exclude_subdecks)?;
+        if matching_decks.is_empty() {
+            return Err(AppError::NoDeckWithId(id));
+        }
+
+        self.print(&format!("Deck with id {}:", id));
+        for name in &matching_decks {
+            self.print(&format!("  {}", green_text(&replace_deck_delimiter(name))));
+        }
+
+        Ok(matching_decks)
+    }
+
+    /// Shows how a user-supplied deck name is compared against `decks.name`.
+    ///
+    /// Prints the internal (`0x1F`-delimited) form of `input` followed by every deck whose stored
+    /// name matches it exactly or as a parent. With `--verbose`, the bytes of each name are dumped
+    /// in hex so that `::` versus `0x1F` separator mismatches are visible.
+    pub fn resolve_deck(&self, input: &str) -> Result<Vec<String>> {
+        let conn = self.open_collection()?;
+        self.check_schema(&conn)?;
+        let internal = to_internal_deck_name(input);
+        println!("Internal form: {}", internal.escape_debug());
+        debug!("Input bytes:    {}", hex_bytes(input));
+        debug!("Internal bytes: {}", hex_bytes(&internal));
+
+        // Subdecks are always listed here, whatever --exclude-subdecks says
+        let matches = queries::decks_matching(&conn, input, false, self.config.case_sensitive)?;
+
+        if matches.is_empty() {
+            println!("No decks match '{}'", input);
+        }
+        for name in &matches {
+            let exact = |other: &str| {
+                if self.config.case_sensitive {
+                    name == other
+                } else {
+                    name.eq_ignore_ascii_case(other)
+                }
+            };
+            let kind = if exact(&internal) || exact(input) {
+                "exact"
+            } else {
+                "prefix"
+            };
+            println!("{} ({})", replace_deck_delimiter(name), kind);
+            debug!("  bytes: {}", hex_bytes(name));
+        }
+
+        Ok(matches)
+    }
+
+    /// The decks selected by name or pattern, or `None` when processing all decks or notes given
+    /// by id.
+    fn matching_decks(&self, conn: &Connection) -> Result<Option<Vec<String>>, AppError> {
+        match &self.config.mode {
+            AppMode::All | AppMode::NoteIds(_) => Ok(None),
+            AppMode::Decks(_) | AppMode::Pattern(_) | AppMode::DeckId(_) => Ok(Some(self.fetch_matching_decks(conn)?)),
+        }
+    }
+
+    /// Fetches the notes with reviews on the processed Anki days in `matching_decks` (all decks
+    /// when `None`), after applying `--limit`. The days start at `rollover` in the run's zone,
+    /// exactly like the window the reviews are moved from.
+    fn fetch_reviewed_notes(
+        &self,
+        conn: &Connection,
+        matching_decks: Option<&[String]>,
+        rollover: NaiveTime,
+    ) -> Result<Vec<i64>, AppError> {
+        debug!("Fetching reviewed notes...");
+
+        // Ensure we have a valid `from_date` to work with
+        let from_date = match self.from_date {
+            Some(date) => date,
+            None => {
+                return Err(AppError::MissingFromDate); // `--from` date is required
+            }
+        };
+
+        debug!("Fetching notes reviewed on: {}", from_date);
+
+        // The revlog id range of the processed days, up to the end of `from_date`
+        let first_date = from_date - chrono::Duration::days(self.span_days - 1);
+        let window_start = self.anki_day_window(first_date, rollover)?.0;
+        let window_end = self.anki_day_window(from_date, rollover)?.1;
+
+        match matching_decks {
+            None => debug!("Mode: All decks"),
+            Some(matching_decks) => debug!(
+                "Processing decks:\n{}",
+                matching_decks
+                    .iter()
+                    .map(|d| replace_deck_delimiter(d))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+
+        // Notes given by id skip the deck and tag filters; ids that aren't notes are only warned about
+        if let AppMode::NoteIds(ids) = &self.config.mode {
+            let known = queries::existing_notes(conn, ids)?;
+            for id in ids.iter().filter(|id| !known.contains(id)) {
+                eprintln!("Warning: there is no note with id {}; skipping it.", id);
+            }
+            let filter = queries::NoteFilter {
+                start: window_start,
+                end: window_end,
+                note_ids: Some(&known),
+                review_types: self.config.review_types.as_deref(),
+                by_card: self.config.by_card,
+                ..Default::default()
+            };
+            let rows = queries::reviewed_notes(conn, &filter)?;
+            return Ok(limit_notes(rows, self.limit, self.config.limit_scope, self.config.limit_order, self.config.seed));
+        }
+
+        // `notes.tags` is space-separated; padding it with spaces matches whole tags only
+        let tag_pattern = self.config.tag.as_deref().map(tag_to_like);
+        if let Some(tag) = &self.config.tag {
+            debug!("Only notes tagged '{}'", tag);
+        }
+
+        if let Some(note_type) = &self.config.note_type {
+            if !table_exists(conn, "notetypes")? {
+                return Err(AppError::UnsupportedCollection(
+                    "--note-type needs a collection with a 'notetypes' table (Anki 2.1.28 or later).".to_string(),
+                ));
+            }
+            debug!("Only notes of type '{}'", note_type);
+        }
+
+        let filter = queries::NoteFilter {
+            start: window_start,
+            end: window_end,
+            decks: matching_decks,
+            tag: tag_pattern.as_deref(),
+            note_type: self.config.note_type.as_deref(),
+            review_types: self.config.review_types.as_deref(),
+            note_ids: None,
+            by_card: self.config.by_card,
+        };
+        let rows = queries::reviewed_notes(conn, &filter)?;
+
+        // Apply limit if specified
+        Ok(limit_notes(rows, self.limit, self.config.limit_scope, self.config.limit_order, self.config.seed))
+    }
+
+    /// Milliseconds every affected review is moved back by.
+    fn id_offset(&self) -> i64 {
+        // Calculate the actual ID offset using your utility functions
+        if let (Some(from), Some(to)) = (self.from_date, self.to_date) {
+            let days_difference = date::days_between(to, from);
+            date::calculate_id_offset(days_difference)
+        } else {
+            date::calculate_id_offset(1) // Default 1-day offset if dates are not provided
+        }
+    }
+
+    /// Returns the offset between the Anki days `from` and `to` in the collection's timezone, which
+    /// is an hour more or less than `id_offset` when a daylight saving change lies in between.
+    fn dst_aware_id_offset(&self, from: NaiveDate, to: NaiveDate, rollover: NaiveTime) -> Result<i64> {
+        self.zone().id_offset(from, to, rollover).map_err(AppError::InvalidDay)
+    }
+
+    /// Moves the reviews of `notes` inside the `rid_string` window back by `id_offset`, recording
+    /// the affected notes, cards and new revlog ids in `report`.
+    ///
+    /// Only the reviews `scope` covers are moved; a note's reviews in decks outside `scope.decks`
+    /// are left alone with a warning. With `--newest-only delete` the earlier same-day reviews of
+    /// each card are deleted.
+    ///
+    /// In simulate mode nothing is written; `report` describes what would change.
+    fn process_notes(
+        &self,
+        conn: &mut Connection,
+        notes: Vec<i64>,
+        scope: &queries::ReviewScope,
+        rid_string: &str,
+        id_offset: i64,
+        report: &mut RunReport,
+    ) -> Result<()> {
+        debug!("Processing {} notes...", notes.len());
+
+        let start_time: i64 = rid_string.split(':').nth(1).unwrap().parse().unwrap();
+        let end_time: i64 = rid_string.split(':').nth(2).unwrap().parse().unwrap();
+        let window = (start_time, end_time);
+
+        if let Some(cap) = self.config.cap_review_time {
+            debug!("Capping review time of shifted reviews at {} ms.", cap);
+        }
+
+        let mut affected_cards = Vec::new();
+        let mut affected_notes = Vec::new();
+        let mut moved_ids = Vec::new();
+        // (note id, card id, old revlog id, new revlog id) of every moved review, for `--csv`
+        let mut audit_moves = Vec::new();
+        let current_time = chrono::Utc::now().timestamp();
+        let delete_superseded = self.config.newest_only == Some(OlderReviews::Delete);
+        // With `--by-card` the ids being processed are card ids; reports still name their notes
+        let (unit_name, unit_title) = if self.config.by_card { ("card", "Card") } else { ("note", "Note") };
+        let note_of = |conn: &Connection, unit: i64| -> Result<i64> {
+            if self.config.by_card {
+                Ok(queries::card_note(conn, unit)?)
+            } else {
+                Ok(unit)
+            }
+        };
+        // Whole days the reviews move by, which `--reschedule` moves due dates by as well
+        let reschedule_days = (id_offset as f64 / 86_400_000.0).round() as i64;
+
+        for unit in &notes {
+            for (id, cid) in queries::reviews_before_creation(conn, *unit, window, id_offset, scope)? {
+                eprintln!(
+                    "Warning: skipping review {} of card {}; moving it would place it before the card was created.",
+                    id, cid
+                );
+                report.skipped_reviews += 1;
+            }
+            let elsewhere = queries::reviews_outside_decks(conn, *unit, window, scope)?;
+            if !elsewhere.is_empty() {
+                eprintln!(
+                    "Warning: {} {} also has {} reviews of cards in decks that were not selected; leaving them alone.",
+                    unit_name,
+                    unit,
+                    elsewhere.len()
+                );
+            }
+        }
+
+        // Counter on stderr, cleared around each note's own output so the two never share a line
+        let mut progress = utils::Progress::stderr("Notes", notes.len(), !self.config.json && !self.config.quiet);
+
+        if self.simulate {
+            for unit in &notes {
+                let note_id = note_of(conn, *unit)?;
+                // Collect the revlog IDs and card IDs that would be affected for the current note
+                let selected = queries::movable_reviews(conn, *unit, window, id_offset, scope)?;
+                if !selected.is_empty() {
+                    affected_notes.push(note_id);
+                }
+
+                progress.clear();
+                self.print(&format!(
+                    "Simulating update for {} {}, moving back {} days:",
+                    unit_name,
+                    unit,
+                    self.id_offset() / 86_400_000 // Whole days for display
+                ));
+                // Cards that already have a review where theirs would land
+                let mut cards: Vec<i64> = selected.iter().map(|(_, cid)| *cid).collect();
+                cards.sort_unstable();
+                cards.dedup();
+                for cid in cards {
+                    let destination = (start_time - id_offset, end_time - id_offset);
+                    if queries::card_reviews_between(conn, cid, destination, window)? > 0 {
+                        report.destination_conflicts.push(DestinationConflict { note_id, card_id: cid });
+                    }
+                }
+                // Show each review that would move, in local time
+                for (id, cid) in selected {
+                    self.print(&format!(
+                        "  card {}: {} -> {}",
+                        cid,
+                        self.zone().format_review_time(id),
+                        self.zone().format_review_time(id - id_offset)
+                    ));
+                    moved_ids.push(id - id_offset);
+                    audit_moves.push((note_id, cid, id, id - id_offset));
+                    affected_cards.push(cid);
+                }
+                for (id, cid) in queries::superseded_reviews(conn, *unit, window, id_offset, scope)? {
+                    self.print(&format!(
+                        "  card {}: {} is not the card's latest review that day and would be {}",
+                        cid,
+                        self.zone().format_review_time(id),
+                        if delete_superseded { "deleted" } else { "left in place" }
+                    ));
+                    report.superseded_reviews += 1;
+                    if delete_superseded {
+                        affected_cards.push(cid);
+                    }
+                }
+                progress.advance();
+            }
+            progress.finish();
+            self.print(&format!(
+                "{} reviews across {} notes would be moved.",
+                moved_ids.len(),
+                affected_notes.len()
+            ));
+            if report.superseded_reviews > 0 {
+                self.print(&format!(
+                    "{} earlier reviews of cards reviewed again later the same day would be {}.",
+                    report.superseded_reviews,
+                    if delete_superseded { "deleted" } else { "left in place" }
+                ));
+            }
+            if !report.destination_conflicts.is_empty() {
+                self.print(&yellow_text(&format!(
+                    "{} cards already have reviews on the days their reviews would move to; those days would \
+                     count the card twice:",
+                    report.destination_conflicts.len()
+                )));
+                for conflict in &report.destination_conflicts {
+                    self.print(&format!("  note {}, card {}", conflict.note_id, conflict.card_id));
+                }
+            }
+            if self.config.reschedule {
+                let mut cards = affected_cards.clone();
+                cards.sort_unstable();
+                cards.dedup();
+                for cid in cards {
+                    if queries::day_due(conn, cid)?.is_some() {
+                        report.rescheduled_cards += 1;
+                    }
+                }
+                self.print(&format!(
+                    "The due date of {} cards would move back by {} days.",
+                    report.rescheduled_cards, reschedule_days
+                ));
+            }
+            self.write_audit(conn, &audit_moves)?;
+        } else {
+            // Abort before any write if the collection cannot be backed up
+            self.backup_collection()?;
+
+            let mut journal = journal::Journal {
+                collection: self.db_path.display().to_string(),
+                created: chrono::Utc::now().to_rfc3339(),
+                ..Default::default()
+            };
+            let mut journaled_cards = HashSet::new();
+            // The latest new review time (ms) of each card, for `--mod-time review`
+            let mut latest_review: HashMap<i64, i64> = HashMap::new();
+
+            // Every write happens inside this transaction. Returning early with an error drops it,
+            // which rolls back, so the collection is never left with only some notes moved.
+            let tx = conn.transaction()?;
+            {
+                for unit in &notes {
+                    let note_id = note_of(&tx, *unit)?;
+                    // The latest review of a day is only known while the earlier ones are still there
+                    let superseded = queries::superseded_reviews(&tx, *unit, window, id_offset, scope)?;
+                    report.superseded_reviews += superseded.len();
+                    let mut deleted_cards = Vec::new();
+                    if delete_superseded {
+                        let ids: Vec<i64> = superseded.iter().map(|(id, _)| *id).collect();
+                        let deleted = queries::delete_reviews(&tx, &ids)?;
+                        deleted_cards.extend(deleted.iter().map(|review| review.cid));
+                        journal.deleted.extend(deleted);
+                    }
+                    // Collect the new revlog IDs and affected card IDs for the current note
+                    let moved =
+                        queries::move_reviews(&tx, *unit, window, id_offset, self.config.cap_review_time, scope)?;
+                    if !moved.is_empty() {
+                        affected_notes.push(note_id);
+                    }
+                    for (new_id, cid) in &moved {
+                        audit_moves.push((note_id, *cid, new_id + id_offset, *new_id));
+                        let latest = latest_review.entry(*cid).or_insert(*new_id);
+                        *latest = (*latest).max(*new_id);
+                    }
+                    let (note_moved, mut note_cards): (Vec<i64>, Vec<i64>) = moved.into_iter().unzip();
+                    note_cards.extend(deleted_cards);
+                    journal.revlog.extend(note_moved.iter().map(|new_id| journal::RevlogMove {
+                        old_id: new_id + id_offset,
+                        new_id: *new_id,
+                    }));
+                    moved_ids.extend(note_moved);
+
+                    // Record the prior state of each card for the undo journal
+                    for cid in &note_cards {
+                        if journaled_cards.insert(*cid) {
+                            let (modified, usn) = queries::card_mod_usn(&tx, *cid)?;
+                            journal.cards.push(journal::CardState { id: *cid, modified, usn, due: None });
+                        }
+                    }
+                    affected_cards.extend(note_cards);
+                    progress.clear();
+                    self.print(&format!("{} date updated successfully for {}.", unit_title, unit));
+                    progress.advance();
+                }
+                progress.finish();
+                if report.superseded_reviews > 0 {
+                    self.print(&format!(
+                        "{} {} earlier reviews of cards reviewed again later the same day{}.",
+                        if delete_superseded { "Deleted" } else { "Left" },
+                        report.superseded_reviews,
+                        if delete_superseded { "; undo restores them" } else { " in place" }
+                    ));
+                }
+
+                // Update every affected card once
+                let cards: Vec<(i64, i64)> = journal
+                    .cards
+                    .iter()
+                    .map(|card| {
+                        let modified = match self.config.mod_time {
+                            ModTime::Now => current_time,
+                            // A card whose reviews were only deleted keeps its modification time
+                            ModTime::Review => latest_review.get(&card.id).map_or(card.modified, |id| id / 1000),
+                            ModTime::Keep => card.modified,
+                        };
+                        (card.id, modified)
+                    })
+                    .collect();
+                queries::mark_cards_modified(&tx, &cards)?;
+
+                if self.config.reschedule {
+                    for card in journal.cards.iter_mut() {
+                        if let Some(due) = queries::day_due(&tx, card.id)? {
+                            queries::set_due(&tx, card.id, due - reschedule_days)?;
+                            card.due = Some(due);
+                            report.rescheduled_cards += 1;
+                        }
+                    }
+                    self.print(&format!(
+                        "Moved the due date of {} cards back by {} days.",
+                        report.rescheduled_cards, reschedule_days
+                    ));
+                }
+            }
+
+            // A schema bump forces a full upload on the next sync, so only do it if the revlog changed
+            let revlog_changed = !moved_ids.is_empty() || !journal.deleted.is_empty();
+            if !revlog_changed {
+                debug!("No reviews moved; leaving the schema modification time alone.");
+            } else {
+                debug!("Will trigger full database sync criterion.");
+                queries::bump_schema(&tx)?;
+            }
+
+            // The journal and audit are written before committing, so a run that cannot be undone
+            // is never applied
+            self.write_audit(&tx, &audit_moves)?;
+            let journal_dir = self.backup_dir();
+            let journal_path = journal::write_journal(&journal, &journal_dir).map_err(|e| {
+                AppError::Io(io::Error::new(
+                    e.kind(),
+                    format!("Cannot write the undo journal in '{}': {}", journal_dir.display(), e),
+                ))
+            })?;
+            tx.commit()?;
+            debug!("Committed changes to the collection.");
+            self.print(&format!("Undo journal written to '{}'", journal_path.display()));
+            report.journal = Some(journal_path.display().to_string());
+            if revlog_changed {
+                self.print_sync_notice();
+            }
+        }
+
+        debug!("Marked {} cards as needing sync.", affected_cards.len());
+
+        report.by_deck = self.group_by_deck(conn, &affected_cards)?;
+
+        affected_cards.sort_unstable();
+        affected_cards.dedup();
+        if self.simulate && !affected_cards.is_empty() {
+            self.print_scheduling_impact(&self.scheduling_impact(conn, &affected_cards)?);
+        }
+        // Several cards of one note are processed separately with `--by-card`
+        affected_notes.sort_unstable();
+        affected_notes.dedup();
+        report.note_ids = affected_notes;
+        report.card_ids = affected_cards;
+        report.revlog_ids = moved_ids;
+
+        Ok(())
+    }
+
+    /// Adds a synthetic review on `date` for `--backfill`, returning its revlog id, or `None` if
+    /// the selected decks already have a review that day.
+    ///
+    /// Unlike a normal run this creates history instead of moving it. The review copies the card
+    /// and scheduling state of the latest earlier review in the selected decks, is answered "Good",
+    /// and sits in the middle of the Anki day. It is not recorded in an undo journal.
+    pub fn backfill(&self, date: NaiveDate) -> Result<Option<i64>, AppError> {
+        self.ensure_collection_exists()?;
+        let mut conn = self.open_collection()?;
+        self.ensure_supported_schema(self.check_schema(&conn)?)?;
+        if !self.simulate {
+            self.ensure_not_in_use()?;
+        }
+        let rollover = self.get_rollover(&conn)?;
+        self.load_zone(&conn)?;
+        let (start, end) = self.anki_day_window(date, rollover)?;
+        let matching_decks = self.matching_decks(&conn)?;
+
+        if !queries::review_ids(&conn, (start, end), matching_decks.as_deref())?.is_empty() {
+            self.print(&format!("{} already has reviews; there is nothing to backfill.", date));
+            return Ok(None);
+        }
+        let template = queries::backfill_template(&conn, start, matching_decks.as_deref())?.ok_or_else(|| {
+            AppError::Config(format!(
+                "No card in the selected decks was reviewed before {}, so there is no review to base a backfill on.",
+                date
+            ))
+        })?;
+        let id = queries::free_revlog_id(&conn, start + (end - start) / 2)?;
+        debug!("Backfilling from the review state of card {}: {:?}", template.card_id, template);
+
+        if self.simulate {
+            self.print(&format!(
+                "Would add a review of card {} at {}.",
+                template.card_id,
+                self.zone().format_review_time(id)
+            ));
+            return Ok(Some(id));
+        }
+
+        self.backup_collection()?;
+        let tx = conn.transaction()?;
+        queries::insert_backfilled_review(&tx, id, &template, BACKFILL_ANSWER_MS)?;
+        queries::bump_schema(&tx)?;
+        tx.commit()?;
+        self.print(&format!(
+            "Added a review of card {} at {}.",
+            template.card_id,
+            self.zone().format_review_time(id)
+        ));
+        self.print_sync_notice();
+        Ok(Some(id))
+    }
+
+    /// Reverses a previous run recorded in `journal`.
+    ///
+    /// Every moved review is returned to its original id and every touched card gets its prior
+    /// `mod`/`usn` back, followed by a single `scm` bump. Nothing is changed unless every review
+    /// is still exactly where the journal left it, so a journal can't be applied twice.
+    pub fn undo(&self, journal: &journal::Journal) -> Result<UndoReport> {
+        self.ensure_collection_exists()?;
+        if !self.simulate {
+            self.ensure_not_in_use()?;
+        }
+        self.print(&format!(
+            "Undoing {} review moves, {} deleted reviews and {} card updates",
+            journal.revlog.len(),
+            journal.deleted.len(),
+            journal.cards.len()
+        ));
+
+        let mut conn = self.open_collection()?;
+        self.ensure_supported_schema(self.check_schema(&conn)?)?;
+        {
+            let mut exists = conn.prepare("SELECT 1 FROM revlog WHERE id = ?1;")?;
+            for entry in &journal.revlog {
+                if !exists.exists(params![entry.new_id])? || exists.exists(params![entry.old_id])? {
+                    return Err(AppError::UndoMismatch(format!(
+                        "Review {} is not where the journal expects it (already undone?).",
+                        entry.new_id
+                    )));
+                }
+            }
+            for review in &journal.deleted {
+                if exists.exists(params![review.id])? {
+                    return Err(AppError::UndoMismatch(format!(
+                        "Deleted review {} is already back in the collection (already undone?).",
+                        review.id
+                    )));
+                }
+            }
+        }
+
+        let report = UndoReport {
+            collection: self.db_path.display().to_string(),
+            simulate: self.simulate,
+            restored_reviews: journal.revlog.len(),
+            reinserted_reviews: journal.deleted.len(),
+            cards: journal.cards.len(),
+            ..Default::default()
+        };
+        if self.simulate {
+            self.print("Simulation mode: the journal matches the collection and could be undone.");
+            return Ok(report);
+        }
+
+        self.backup_collection()?;
+
+        let tx = conn.transaction()?;
+        for entry in &journal.revlog {
+            tx.execute(
+                "UPDATE revlog SET id = ?1 WHERE id = ?2;",
+                params![entry.old_id, entry.new_id],
+            )?;
+        }
+        for review in &journal.deleted {
+            queries::restore_review(&tx, review)?;
+        }
+        for card in &journal.cards {
+            tx.execute(
+                "UPDATE cards SET mod = ?1, usn = ?2 WHERE id = ?3;",
+                params![card.modified, card.usn, card.id],
+            )?;
+            if let Some(due) = card.due {
+                queries::set_due(&tx, card.id, due)?;
+            }
+        }
+        debug!("Will trigger full database sync criterion.");
+        queries::bump_schema(&tx)?;
+        tx.commit()?;
+
+        self.print("Undo completed.");
+        self.print_sync_notice();
+        Ok(report)
+    }
+
+    /// Writes the `--csv` audit file, one row per moved review, if one was asked for.
+    fn write_audit(&self, conn: &Connection, moves: &[(i64, i64, i64, i64)]) -> Result<()> {
+        let Some(path) = &self.config.csv else {
+            return Ok(());
+        };
+        let mut rows = Vec::with_capacity(moves.len());
+        for (note_id, card_id, old_id, new_id) in moves {
+            rows.push(audit::AuditRow {
+                note_id: *note_id,
+                card_id: *card_id,
+                deck_name: replace_deck_delimiter(&queries::home_deck(conn, *card_id)?),
+                old_review_millis: *old_id,
+                new_review_millis: *new_id,
+                offset_days: self.id_offset() / 86_400_000,
+            });
+        }
+        audit::write_csv(path, &rows).map_err(|source| AppError::Audit { path: path.clone(), source })?;
+        self.print(&format!("Audit CSV written to '{}'", path.display()));
+        Ok(())
+    }
+
+    /// Looks up the current queue and due value of `card_ids`, grouped by home deck.
+    fn scheduling_impact(&self, conn: &Connection, card_ids: &[i64]) -> Result<BTreeMap<String, Vec<CardSchedule>>> {
+        let mut stmt = conn.prepare(
+            "
+            SELECT cards.queue, cards.due, decks.name
+            FROM cards
+            JOIN decks ON COALESCE(NULLIF(cards.odid, 0), cards.did) = decks.id
+            WHERE cards.id = ?1;
+            ",
+        )?;
+
+        let mut by_deck: BTreeMap<String, Vec<CardSchedule>> = BTreeMap::new();
+        for id in card_ids {
+            let (queue, due, deck): (i64, i64, String) =
+                stmt.query_row(params![id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            by_deck
+                .entry(replace_deck_delimiter(&deck))
+                .or_default()
+                .push(CardSchedule { id: *id, queue, due });
+        }
+        Ok(by_deck)
+    }
+
+    /// Prints how many affected cards of each deck sit in each queue, with every card's due value
+    /// in verbose mode.
+    ///
+    /// Moving reviews only rewrites the revlog: the due date, interval and ease Anki schedules
+    /// from live on the card and stay as they are.
+    fn print_scheduling_impact(&self, impact: &BTreeMap<String, Vec<CardSchedule>>) {
+        self.print("Scheduling impact of the affected cards:");
+        for (deck, cards) in impact {
+            let mut queues: BTreeMap<&str, usize> = BTreeMap::new();
+            for card in cards {
+                *queues.entry(queue_name(card.queue)).or_default() += 1;
+            }
+            self.print(&format!(
+                "  {}: {} cards ({})",
+                deck,
+                cards.len(),
+                queues
+                    .iter()
+                    .map(|(queue, count)| format!("{} {}", count, queue))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            for card in cards {
+                debug!("    card {}: queue {}, due {}", card.id, queue_name(card.queue), card.due);
+            }
+        }
+        self.print(
+            "Due dates and intervals are stored on the cards and are not changed, so Anki's next \
+             scheduling of these cards stays the same. Only the review history moves (which FSRS \
+             uses if you later optimize or reschedule).",
+        );
+    }
+
+    /// Groups the affected cards by their home deck, keyed by the rendered deck name. Cards that
+    /// are currently in a filtered deck count towards the deck they came from.
+    ///
+    /// Each card is counted once, and each note once per deck it has affected cards in.
+    fn group_by_deck(&self, conn: &Connection, card_ids: &[i64]) -> Result<BTreeMap<String, DeckCounts>> {
+        let mut stmt = conn.prepare(
+            "
+            SELECT cards.nid, decks.name
+            FROM cards
+            JOIN decks ON COALESCE(NULLIF(cards.odid, 0), cards.did) = decks.id
+            WHERE cards.id = ?1;
+            ",
+        )?;
+
+        let mut by_deck: BTreeMap<String, DeckCounts> = BTreeMap::new();
+        let mut seen_cards = HashSet::new();
+        let mut seen_notes = HashSet::new();
+        for cid in card_ids {
+            if !seen_cards.insert(*cid) {
+                continue;
+            }
+            let (nid, deck): (i64, String) =
+                stmt.query_row(params![cid], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            let deck = replace_deck_delimiter(&deck);
+            let counts = by_deck.entry(deck.clone()).or_default();
+            counts.cards += 1;
+            if seen_notes.insert((deck, nid)) {
+                counts.notes += 1;
+            }
+        }
+
+        Ok(by_deck)
+    }
+
+}
+
+/// Checks that a path given with `--collection-path` points at an existing `.anki2` file.
+pub fn validate_collection_path(path: &Path) -> Result<()> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("anki2") {
+        return Err(AppError::Config(format!(
+            "Collection path '{}' must point to an .anki2 file.",
+            path.display()
+        )));
+    }
+    if !path.is_file() {
+        return Err(AppError::Config(format!("Collection file '{}' does not exist.", path.display())));
+    }
+    Ok(())
+}
+
+/// Builds the `LIKE` pattern that finds `tag` as a whole tag in a space-padded `notes.tags`.
+fn tag_to_like(tag: &str) -> String {
+    let escaped = tag.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("% {} %", escaped)
+}
+
+/// Applies `--limit` to the reviewed notes (ordered by note id), returning the kept note ids in
+/// note id order.
+///
+/// With `LimitScope::PerDeck` every deck is limited on its own. A note with cards in several decks
+/// is kept if any of its decks keeps it.
+fn limit_notes(
+    mut rows: Vec<queries::ReviewedNote>,
+    limit: i64,
+    scope: LimitScope,
+    order: LimitOrder,
+    seed: Option<u64>,
+) -> Vec<i64> {
+    // The stable sorts keep note id order between notes reviewed at the same time
+    match order {
+        LimitOrder::Recent => rows.sort_by_key(|row| std::cmp::Reverse(row.last_review)),
+        LimitOrder::Oldest => rows.sort_by_key(|row| row.first_review),
+        LimitOrder::NoteId | LimitOrder::Random => {}
+    }
+    match scope {
+        LimitScope::Global => {
+            // A note reviewed in several decks counts once, at its first position
+            let mut seen = HashSet::new();
+            let notes: Vec<i64> = rows.into_iter().map(|row| row.note_id).filter(|note| seen.insert(*note)).collect();
+            select_limited(notes, limit, order, seed)
+        }
+        LimitScope::PerDeck => {
+            let mut by_deck: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+            for row in rows {
+                by_deck.entry(row.deck_id).or_default().push(row.note_id);
+            }
+            let mut notes: Vec<i64> = by_deck
+                .into_values()
+                .flat_map(|notes| select_limited(notes, limit, order, seed))
+                .collect();
+            notes.sort_unstable();
+            notes.dedup();
+            notes
+        }
+    }
+}
+
+/// Applies `--limit` to the matched notes, keeping the first ones in the given order (or a sample
+/// for `LimitOrder::Random`), and returns them in note id order.
+///
+/// With `LimitOrder::Random` the kept notes are a random sample. The same `seed` always selects the
+/// same notes from the same input; without a seed the sample differs from run to run.
+fn select_limited(mut notes: Vec<i64>, limit: i64, order: LimitOrder, seed: Option<u64>) -> Vec<i64> {
+    if limit <= 0 || notes.len() <= limit as usize {
+        return notes;
+    }
+
+    if order == LimitOrder::Random {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        notes.shuffle(&mut rng);
+    }
+
+    let mut selected: Vec<i64> = notes.into_iter().take(limit as usize).collect();
+    selected.sort_unstable();
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_generate_rid_string() {
+        let config = AppConfig {
+            timezone: date::Zone::Named(chrono_tz::America::New_York),
+            ..Default::default()
+        };
+        let processor = AnkiProcessor::new(PathBuf::from("collection.anki2"), true, 1, None, None, &config);
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let one_am = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
+        let rid_string = processor.generate_rid_string(date, date, one_am).unwrap();
+
+        assert!(rid_string.starts_with("rid:"));
+        let parts: Vec<&str> = rid_string.split(':').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[1], "1735711200000"); // 2025-01-01 01:00:00 in New York
+        assert_eq!(parts[2], "1735797600000"); // 2025-01-02 01:00:00 in New York
+
+        let date2 = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let rid_string2 = processor.generate_rid_string(date2, date2, one_am).unwrap();
+        assert_eq!(rid_string2, "rid:1735711200000:1735797600000");
+    }
+
+    fn processor_for<'a>(
+        db_path: &Path,
+        simulate: bool,
+        from_date: NaiveDate,
+        to_date: NaiveDate,
+        config: &'a AppConfig,
+    ) -> AnkiProcessor<'a> {
+        AnkiProcessor::new(db_path.to_path_buf(), simulate, 0, Some(from_date), Some(to_date), config)
+    }
+
+    /// Runs note selection the way `process` does.
+    fn reviewed_notes(processor: &AnkiProcessor, path: &Path) -> std::result::Result<Vec<i64>, AppError> {
+        let conn = conn_for(path);
+        let rollover = processor.get_rollover(&conn)?;
+        processor.load_zone(&conn)?;
+        let matching_decks = processor.matching_decks(&conn)?;
+        processor.fetch_reviewed_notes(&conn, matching_decks.as_deref(), rollover)
+    }
+
+    fn four_am() -> NaiveTime {
+        NaiveTime::from_hms_opt(4, 0, 0).unwrap()
+    }
+
+    /// Opens `path` the way `AnkiProcessor` does, for calling its steps directly.
+    fn conn_for(path: &Path) -> Connection {
+        open_database_with_collation(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_target_collection_leaves_source_unchanged() {
+        let (dir, source) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&source, 1, "Default");
+        test_fixtures::add_note(&source, 10);
+        test_fixtures::add_card(&source, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&source, review_id, 100);
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let mut processor = processor_for(&source, false, from, to, &config);
+
+        let target = dir.path().join("shifted.anki2");
+        processor.redirect_to_target(&target, false).unwrap();
+        processor.process().unwrap();
+
+        assert_eq!(test_fixtures::revlog_ids(&source), vec![review_id]);
+        assert_eq!(
+            test_fixtures::revlog_ids(&target),
+            vec![review_id - date::calculate_id_offset(1)]
+        );
+    }
+
+    #[test]
+    fn test_target_collection_requires_overwrite() {
+        let (dir, source) = test_fixtures::create_collection(4);
+        let target = dir.path().join("existing.anki2");
+        fs::write(&target, b"").unwrap();
+
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let mut processor = processor_for(&source, false, date, date, &config);
+
+        assert!(processor.redirect_to_target(&target, false).is_err());
+        assert!(processor.redirect_to_target(&target, true).is_ok());
+        assert_eq!(processor.db_path, target);
+    }
+
+    #[test]
+    fn test_group_by_deck_two_decks() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French\u{1f}Verbs");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 10, 1);
+        test_fixtures::add_card(&path, 102, 11, 2);
+
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+        let conn = Connection::open(&path).unwrap();
+        let by_deck = processor.group_by_deck(&conn, &[100, 101, 102, 100]).unwrap();
+
+        assert_eq!(by_deck.len(), 2);
+        assert_eq!(by_deck["Spanish"], DeckCounts { notes: 1, cards: 2 });
+        assert_eq!(by_deck["French::Verbs"], DeckCounts { notes: 1, cards: 1 });
+    }
+
+    #[test]
+    fn test_cap_review_time_only_clamps_shifted_rows() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let shifted = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let untouched = test_fixtures::noon_utc_millis(2024, 12, 30);
+        test_fixtures::add_review_with_time(&path, shifted, 100, 600_000);
+        test_fixtures::add_review_with_time(&path, untouched, 100, 600_000);
+
+        let config = AppConfig {
+            cap_review_time: Some(60_000),
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        processor_for(&path, false, from, to, &config).process().unwrap();
+
+        assert_eq!(
+            test_fixtures::revlog_times(&path),
+            vec![
+                (untouched, 600_000),
+                (shifted - date::calculate_id_offset(1), 60_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_lists_every_moved_review() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Words, common\x1fVerbs");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, 101);
+
+        let csv = dir.path().join("audit.csv");
+        let config = AppConfig { csv: Some(csv.clone()), ..Default::default() };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let offset = date::calculate_id_offset(1);
+        let expected = format!(
+            "note_id,card_id,deck_name,old_review_millis,new_review_millis,offset_days\n\
+             10,100,\"Words, common::Verbs\",{},{},1\n\
+             10,101,\"Words, common::Verbs\",{},{},1\n",
+            review_id,
+            review_id - offset,
+            review_id + 1000,
+            review_id + 1000 - offset
+        );
+
+        // A simulation writes the planned moves, and the real run the same moves
+        processor_for(&path, true, from, to, &config).process().unwrap();
+        assert_eq!(fs::read_to_string(&csv).unwrap(), expected);
+        fs::remove_file(&csv).unwrap();
+
+        // A directory in the way of the CSV stops the run and names the path
+        let blocked = AppConfig { csv: Some(dir.path().to_path_buf()), ..Default::default() };
+        let err = processor_for(&path, true, from, to, &blocked).process().unwrap_err();
+        assert!(matches!(&err, AppError::Audit { path, .. } if path == dir.path()), "{:?}", err);
+        processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!(fs::read_to_string(&csv).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_mod_time_modes() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let first = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let card_mod = |mod_time: ModTime| -> i64 {
+            let (_dir, path) = test_fixtures::create_collection(4);
+            test_fixtures::add_deck(&path, 1, "Default");
+            test_fixtures::add_note(&path, 10);
+            test_fixtures::add_card(&path, 100, 10, 1);
+            conn_for(&path).execute("UPDATE cards SET mod = 12345", []).unwrap();
+            test_fixtures::add_review(&path, first, 100);
+            test_fixtures::add_review(&path, first + 60_000, 100);
+
+            let config = AppConfig { mod_time, ..Default::default() };
+            processor_for(&path, false, from, to, &config).process().unwrap();
+            let (modified, usn) = queries::card_mod_usn(&conn_for(&path), 100).unwrap();
+            assert_eq!(usn, -1);
+            modified
+        };
+
+        let before = chrono::Utc::now().timestamp();
+        assert!(card_mod(ModTime::Now) >= before);
+        assert_eq!(card_mod(ModTime::Review), (first + 60_000 - date::calculate_id_offset(1)) / 1000);
+        assert_eq!(card_mod(ModTime::Keep), 12345);
+    }
+
+    #[test]
+    fn test_by_card_leaves_sibling_cards_alone() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        // Note 10 has two cards reviewed on the day, note 11 one
+        let collection = || {
+            let (dir, path) = test_fixtures::create_collection(4);
+            test_fixtures::add_deck(&path, 1, "Default");
+            test_fixtures::add_note(&path, 10);
+            test_fixtures::add_note(&path, 11);
+            for (cid, nid) in [(100, 10), (101, 10), (102, 11)] {
+                test_fixtures::add_card(&path, cid, nid, 1);
+                test_fixtures::add_review(&path, review_id + (cid - 100) * 1000, cid);
+            }
+            (dir, path)
+        };
+        let day = 86_400_000;
+
+        // By note, a limit of 1 keeps note 10 and moves both of its cards
+        let (_dir, path) = collection();
+        let by_note = AppConfig::default();
+        let report = AnkiProcessor::new(path.clone(), false, 1, Some(from), Some(to), &by_note).process().unwrap();
+        assert_eq!((report.note_ids, report.card_ids), (vec![10], vec![100, 101]));
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id - day, review_id + 1000 - day, review_id + 2000]
+        );
+
+        // By card, a limit of 1 keeps card 100 and its sibling's review stays
+        let (_dir, path) = collection();
+        let by_card = AppConfig { by_card: true, ..Default::default() };
+        let processor = AnkiProcessor::new(path.clone(), false, 1, Some(from), Some(to), &by_card);
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![100]);
+        let report = processor.process().unwrap();
+        assert_eq!((report.note_ids, report.card_ids), (vec![10], vec![100]));
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id - day, review_id + 1000, review_id + 2000]
+        );
+
+        // Without a limit both ways move the same reviews, reported by note
+        let (_dir, path) = collection();
+        let report = processor_for(&path, false, from, to, &by_card).process().unwrap();
+        assert_eq!((report.note_ids, report.summary.reviews), (vec![10, 11], 3));
+    }
+
+    #[test]
+    fn test_simulate_lists_cards_already_reviewed_on_the_target_day() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for i in 0..2 {
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+        // Card 100 was also reviewed on the day its review would move to
+        test_fixtures::add_review(&path, review_id - 86_400_000 + 5000, 100);
+
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let config = AppConfig::default();
+        let preview = processor_for(&path, true, from, to, &config).process().unwrap();
+        assert_eq!(preview.destination_conflicts, vec![DestinationConflict { note_id: 10, card_id: 100 }]);
+
+        // Moving to a day without reviews of either card conflicts with nothing
+        let earlier = NaiveDate::from_ymd_opt(2024, 12, 30).unwrap();
+        let preview = processor_for(&path, true, from, earlier, &config).process().unwrap();
+        assert!(preview.destination_conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_reschedule_moves_due_of_review_cards() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        for i in 0..2 {
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+        // Card 101 is in a learning step, so its `due` is a timestamp and must not move
+        conn_for(&path).execute("UPDATE cards SET queue = 1, due = 1735900000 WHERE id = 101", []).unwrap();
+        let dues = || -> Vec<i64> {
+            let conn = conn_for(&path);
+            let mut stmt = conn.prepare("SELECT due FROM cards ORDER BY id").unwrap();
+            let dues = stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<Vec<i64>>>().unwrap();
+            dues
+        };
+
+        let plain = AppConfig::default();
+        let rescheduling = AppConfig { reschedule: true, ..Default::default() };
+        let preview = processor_for(&path, true, from, to, &rescheduling).process().unwrap();
+        assert_eq!(preview.rescheduled_cards, 1);
+        assert_eq!(dues(), vec![100, 1735900000]);
+
+        let report = processor_for(&path, false, from, to, &rescheduling).process().unwrap();
+        assert_eq!(report.rescheduled_cards, 1);
+        assert_eq!(dues(), vec![97, 1735900000]);
+
+        // Undo puts the due date back along with the reviews
+        let journal = journal::read_journal(Path::new(&report.journal.unwrap())).unwrap();
+        processor_for(&path, false, from, to, &plain).undo(&journal).unwrap();
+        assert_eq!(dues(), vec![100, 1735900000]);
+
+        // Without --reschedule only the review history changes
+        let report = processor_for(&path, false, from, to, &plain).process().unwrap();
+        assert_eq!(report.summary.reviews, 2);
+        assert_eq!(report.rescheduled_cards, 0);
+        assert_eq!(dues(), vec![100, 1735900000]);
+    }
+
+    #[test]
+    fn test_newest_only_moves_just_the_latest_review_of_the_day() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let hour = 3_600_000;
+        let day = 86_400_000;
+        // Card 100 was reviewed three times on the day, card 101 once
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 10, 1);
+        for i in 0..3 {
+            test_fixtures::add_review(&path, review_id + i * hour, 100);
+        }
+        test_fixtures::add_review(&path, review_id + 30_000, 101);
+
+        // Left in place, the two earlier reviews of card 100 stay on the day
+        let leave = AppConfig { newest_only: Some(OlderReviews::Leave), ..Default::default() };
+        let preview = processor_for(&path, true, from, to, &leave).process().unwrap();
+        assert_eq!((preview.summary.reviews, preview.superseded_reviews), (2, 2));
+        let report = processor_for(&path, false, from, to, &leave).process().unwrap();
+        assert_eq!(report.superseded_reviews, 2);
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id + 30_000 - day, review_id + 2 * hour - day, review_id, review_id + hour]
+        );
+        assert!(journal::read_journal(Path::new(&report.journal.unwrap())).unwrap().deleted.is_empty());
+
+        // Deleted, they are recorded in the journal and undo puts them back
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        for i in 0..3 {
+            test_fixtures::add_review(&path, review_id + i * hour, 100);
+        }
+        let original = test_fixtures::revlog_ids(&path);
+        let delete = AppConfig { newest_only: Some(OlderReviews::Delete), ..Default::default() };
+        let preview = processor_for(&path, true, from, to, &delete).process().unwrap();
+        assert_eq!(preview.superseded_reviews, 2);
+        assert_eq!(test_fixtures::revlog_ids(&path), original);
+
+        let report = processor_for(&path, false, from, to, &delete).process().unwrap();
+        assert_eq!((report.revlog_ids, report.superseded_reviews), (vec![review_id + 2 * hour - day], 2));
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id + 2 * hour - day]);
+        let journal = journal::read_journal(Path::new(&report.journal.unwrap())).unwrap();
+        assert_eq!(
+            journal.deleted.iter().map(|review| (review.id, review.cid)).collect::<Vec<_>>(),
+            vec![(review_id, 100), (review_id + hour, 100)]
+        );
+
+        let processor = processor_for(&path, false, from, to, &delete);
+        processor.undo(&journal).unwrap();
+        assert_eq!(test_fixtures::revlog_ids(&path), original);
+        assert!(matches!(processor.undo(&journal), Err(AppError::UndoMismatch(_))));
+    }
+
+    #[test]
+    fn test_newest_only_keeps_the_reviews_of_a_latest_review_that_stays() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let hour = 3_600_000;
+        let day = 86_400_000;
+        // Card 101 was created on the day, so its latest review cannot move back before it
+        let new_card = review_id - hour;
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, new_card, 10, 1);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + hour, 100);
+        test_fixtures::add_review(&path, review_id + 30_000, new_card);
+        test_fixtures::add_review(&path, review_id + 2 * hour, new_card);
+        let config = AppConfig {
+            newest_only: Some(OlderReviews::Delete),
+            mod_time: ModTime::Review,
+            ..Default::default()
+        };
+
+        let preview = processor_for(&path, true, from, to, &config).process().unwrap();
+        assert_eq!((preview.summary.reviews, preview.superseded_reviews), (1, 1));
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!((report.skipped_reviews, report.superseded_reviews), (1, 1));
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id + hour - day, review_id + 30_000, review_id + 2 * hour]
+        );
+    }
+
+    #[test]
+    fn test_resolve_deck_uses_internal_delimiter() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish\u{1f}Verbs");
+        test_fixtures::add_deck(&path, 2, "Spanish\u{1f}Verbs\u{1f}Irregular");
+        test_fixtures::add_deck(&path, 3, "Spanish\u{1f}Nouns");
+
+        assert_eq!(to_internal_deck_name("Spanish::Verbs"), "Spanish\u{1f}Verbs");
+
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+        let matches = processor.resolve_deck("Spanish::Verbs").unwrap();
+
+        assert_eq!(
+            matches,
+            vec!["Spanish\u{1f}Verbs".to_string(), "Spanish\u{1f}Verbs\u{1f}Irregular".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_selection_errors_say_what_went_wrong() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spansh".to_string()]),
+            ..Default::default()
+        };
+        let err = reviewed_notes(&processor_for(&path, true, date, date, &config), &path).unwrap_err();
+        assert!(matches!(&err, AppError::NoMatchingDeck(name) if name == "Spansh"));
+        assert!(err.to_string().starts_with("No deck matches 'Spansh'."));
+
+        test_fixtures::add_deck(&path, 2, "Spanish\u{1f}Verbs");
+        test_fixtures::add_deck(&path, 3, "French\u{1f}Verbs");
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["verbs".to_string()]),
+            ..Default::default()
+        };
+        let err = processor_for(&path, true, date, date, &config).fetch_matching_decks(&conn_for(&path)).unwrap_err();
+        assert!(matches!(
+            &err,
+            AppError::AmbiguousDeck { candidates, .. }
+                if *candidates == vec!["French::Verbs".to_string(), "Spanish::Verbs".to_string()]
+        ));
+        assert!(err.to_string().contains("\n  French::Verbs\n  Spanish::Verbs\n"));
+
+        let config = AppConfig::default();
+        let processor = AnkiProcessor::new(path.clone(), true, 0, None, None, &config);
+        let err = reviewed_notes(&processor, &path).unwrap_err();
+        assert!(matches!(err, AppError::MissingFromDate));
+        assert_eq!(
+            err.to_string(),
+            "A --from date is required (or omit dates to use the default); got none."
+        );
+        assert!(matches!(processor.fetch_matching_decks(&conn_for(&path)), Err(AppError::ModeMisuse)));
+    }
+
+    #[test]
+    fn test_deck_mode_includes_child_decks() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "Spanish\u{1f}Verbs");
+        test_fixtures::add_deck(&path, 3, "Spanish\u{1f}Nouns");
+        test_fixtures::add_deck(&path, 4, "French");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, did) in [1, 2, 3, 4].into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, did);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["spanish".to_string()]),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![10, 11, 12]);
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["spanish".to_string()]),
+            exclude_subdecks: true,
+            ..Default::default()
+        };
+        let processor = processor_for(&path, true, date, date, &config);
+
+        assert_eq!(processor.fetch_matching_decks(&conn_for(&path)).unwrap(), vec!["Spanish".to_string()]);
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![10]);
+    }
+
+    #[test]
+    fn test_multiple_decks_in_one_run() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "Spanish\u{1f}Verbs");
+        test_fixtures::add_deck(&path, 3, "French");
+        test_fixtures::add_deck(&path, 4, "German");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, did) in [1, 2, 3, 4].into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, did);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+
+        // "Spanish::Verbs" is already covered by "Spanish" and must not be listed twice
+        let config = AppConfig {
+            mode: AppMode::Decks(vec![
+                "Spanish".to_string(),
+                "French".to_string(),
+                "Spanish\u{1f}Verbs".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let processor = processor_for(&path, false, from, to, &config);
+        assert_eq!(
+            processor.fetch_matching_decks(&conn_for(&path)).unwrap(),
+            vec!["Spanish".to_string(), "Spanish\u{1f}Verbs".to_string(), "French".to_string()]
+        );
+
+        let report = processor.process().unwrap();
+
+        assert_eq!(report.note_ids, vec![10, 11, 12]);
+        assert_eq!(backup::list_backups(dir.path(), &path).unwrap().len(), 1);
+        let scm: i64 = Connection::open(&path)
+            .unwrap()
+            .query_row("SELECT scm FROM col", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(scm, 1);
+    }
+
+    #[test]
+    fn test_span_days_moves_several_days_at_once() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let reviews: Vec<i64> = (1..=4).map(|day| test_fixtures::noon_utc_millis(2025, 1, day)).collect();
+        for id in &reviews {
+            test_fixtures::add_review(&path, *id, 100);
+        }
+
+        // The last three days up to Jan 3, shifted back by one day
+        let config = AppConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let mut processor = processor_for(&path, false, from, to, &config);
+        processor.set_span_days(3);
+        let report = processor.process().unwrap();
+
+        let day = 86_400_000;
+        assert_eq!(report.span_days, 3);
+        assert_eq!(report.revlog_ids, vec![reviews[0] - day, reviews[1] - day, reviews[2] - day]);
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![reviews[0] - day, reviews[1] - day, reviews[2] - day, reviews[3]]
+        );
+    }
+
+    #[test]
+    fn test_timezone_sets_the_day_window() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let window = |zone: &str| {
+            let config = AppConfig { timezone: date::Zone::parse(zone).unwrap(), ..Default::default() };
+            processor_for(Path::new("collection.anki2"), true, date, date, &config)
+                .anki_day_window(date, four_am())
+                .unwrap()
+        };
+
+        let (berlin, _) = window("Europe/Berlin");
+        let (los_angeles, _) = window("America/Los_Angeles");
+        assert_eq!(berlin, 1_735_873_200_000); // 03:00 UTC
+        assert_eq!(los_angeles - berlin, 9 * 3_600_000);
+    }
+
+    #[test]
+    fn test_missing_collection_names_the_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        let path = dir.path().join("User 1").join("collection.anki2");
+        let err = processor_for(&path, true, date, date, &config).process().unwrap_err();
+        assert!(matches!(&err, AppError::MissingCollection { profile_exists: false, .. }));
+        let message = err.to_string();
+        assert!(message.contains(&path.display().to_string()), "{}", message);
+        assert!(message.contains("profile folder"), "{}", message);
+
+        fs::create_dir(dir.path().join("User 1")).unwrap();
+        let err = processor_for(&path, true, date, date, &config).process().unwrap_err();
+        assert!(matches!(&err, AppError::MissingCollection { profile_exists: true, .. }));
+        assert!(err.to_string().contains("holds no collection file"));
+        assert!(!path.exists());
+
+        // Undo must not create an empty collection either
+        let err = processor_for(&path, false, date, date, &config).undo(&journal::Journal::default()).unwrap_err();
+        assert!(matches!(&err, AppError::MissingCollection { profile_exists: true, .. }));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_second_run_is_a_no_op() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 60_000, 100);
+        let scm = || -> i64 { conn_for(&path).query_row("SELECT scm FROM col", [], |row| row.get(0)).unwrap() };
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let first = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!(first.summary.reviews, 2);
+        let moved = test_fixtures::revlog_ids(&path);
+        let scm_after_first = scm();
+
+        let second = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!(second.summary.reviews, 0);
+        assert_eq!(test_fixtures::revlog_ids(&path), moved);
+        assert_eq!(scm(), scm_after_first);
+    }
+
+    #[test]
+    fn test_note_ids_bypass_deck_selection() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for i in 0..3 {
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1 + i % 2);
+        }
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, 101);
+        // Note 12 was reviewed, but not on the day being moved
+        test_fixtures::add_review(&path, review_id - 2 * 86_400_000, 102);
+
+        let config = AppConfig { mode: AppMode::NoteIds(vec![11, 12, 99]), ..Default::default() };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let processor = processor_for(&path, false, from, to, &config);
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![11]);
+        let report = processor.process().unwrap();
+
+        assert_eq!(report.mode, "note-ids");
+        assert_eq!(report.note_ids, vec![11]);
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id - 2 * 86_400_000, review_id + 1000 - 86_400_000, review_id]
+        );
+
+        let config = AppConfig { mode: AppMode::NoteIds(vec![99]), ..Default::default() };
+        let processor = processor_for(&path, true, from, to, &config);
+        assert!(reviewed_notes(&processor, &path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_deck_names_match_without_surrounding_whitespace() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Русский");
+        test_fixtures::add_deck(&path, 2, "Spanish\u{a0}");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for i in 0..2 {
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1 + i);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        for (input, expected) in [("Русский ", 10), ("\u{a0}русский\u{a0}", 10), ("\tSpanish", 11), ("spanish", 11)] {
+            let config = AppConfig { mode: AppMode::Decks(vec![input.to_string()]), ..Default::default() };
+            let processor = processor_for(&path, true, date, date, &config);
+            assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![expected], "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_review_types_limit_which_reviews_move() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 11, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, kind) in [0, 1, 2, 3].into_iter().enumerate() {
+            test_fixtures::add_review_of_type(&path, review_id + i as i64 * 1000, 100, kind);
+        }
+        // A note whose only review is a learning step isn't selected at all
+        test_fixtures::add_review_of_type(&path, review_id + 10_000, 101, 0);
+
+        let config = AppConfig { review_types: Some(vec![1, 2]), ..Default::default() };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let processor = processor_for(&path, false, from, to, &config);
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![10]);
+        let report = processor.process().unwrap();
+
+        assert_eq!(report.summary.reviews, 2);
+        let day = 86_400_000;
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![
+                review_id + 1000 - day,
+                review_id + 2000 - day,
+                review_id,
+                review_id + 3000,
+                review_id + 10_000
+            ]
+        );
+    }
+
+    #[test]
+    fn test_streak_report_counts_reviews_per_day() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 11, 2);
+        for day in [1, 3, 4] {
+            test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, day), 100);
+        }
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 4) + 1000, 100);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 2), 101);
+        let before = test_fixtures::revlog_ids(&path);
+
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 1, 5).unwrap();
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            ..Default::default()
+        };
+        let processor = AnkiProcessor::new(path.clone(), true, 0, None, None, &config);
+        let streak_report = processor.streak_report(start, end).unwrap();
+
+        let counts: Vec<usize> = streak_report.days.iter().map(|day| day.reviews).collect();
+        assert_eq!(counts, vec![1, 0, 1, 2, 0]);
+        assert_eq!(
+            streak_report.missing,
+            vec![NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(), end]
+        );
+        assert_eq!(streak_report.longest_streak.unwrap().days, 2);
+
+        // Across all decks the French review fills the gap
+        let config = AppConfig::default();
+        let processor = AnkiProcessor::new(path.clone(), true, 0, None, None, &config);
+        assert_eq!(processor.streak_report(start, end).unwrap().longest_streak.unwrap().days, 4);
+        assert_eq!(test_fixtures::revlog_ids(&path), before);
+    }
+
+    #[test]
+    fn test_scheduling_impact_groups_cards_by_deck() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French\u{1f}Verbs");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 10, 2);
+        Connection::open(&path)
+            .unwrap()
+            .execute("UPDATE cards SET queue = 1, due = 1735700000 WHERE id = 101", [])
+            .unwrap();
+
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+        let conn = Connection::open(&path).unwrap();
+        let impact = processor.scheduling_impact(&conn, &[100, 101]).unwrap();
+
+        assert_eq!(
+            impact,
+            BTreeMap::from([
+                ("French::Verbs".to_string(), vec![CardSchedule { id: 101, queue: 1, due: 1735700000 }]),
+                ("Spanish".to_string(), vec![CardSchedule { id: 100, queue: 2, due: 100 }]),
+            ])
+        );
+        assert_eq!(queue_name(1), "learning");
+        assert_eq!(queue_name(-1), "suspended");
+    }
+
+    #[test]
+    fn test_reviews_are_not_moved_before_card_creation() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        // Card 100 is old; the other card was created an hour before its review
+        let new_card = review_id - 3_600_000;
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, new_card, 11, 1);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, new_card);
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+
+        let preview = processor_for(&path, true, from, to, &config).process().unwrap();
+        assert_eq!(preview.skipped_reviews, 1);
+        assert_eq!(preview.card_ids, vec![100]);
+
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!(report.skipped_reviews, 1);
+        assert_eq!(report.revlog_ids, vec![review_id - 86_400_000]);
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id - 86_400_000, review_id + 1000]
+        );
+    }
+
+    #[test]
+    fn test_tag_filter_matches_whole_tags_only() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        let tags = [" leech ", " leechy ", " verbs LEECH ", " leech::hard ", "", "leech"];
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, tags) in tags.into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note_with(&path, 10 + i, 1, tags);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+
+        let config = AppConfig {
+            tag: Some("leech".to_string()),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![10, 12, 15]);
+        assert_eq!(tag_to_like("100%_done"), "% 100\\%\\_done %");
+    }
+
+    #[test]
+    fn test_note_type_and_deck_filters_combine() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French");
+        test_fixtures::add_notetype(&path, 1, "Basic");
+        test_fixtures::add_notetype(&path, 2, "Cloze");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, (mid, did)) in [(1, 1), (2, 1), (2, 2)].into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note_with(&path, 10 + i, mid, "");
+            test_fixtures::add_card(&path, 100 + i, 10 + i, did);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            note_type: Some("cloze".to_string()),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![11]);
+    }
+
+    #[test]
+    fn test_filtered_deck_cards_count_towards_home_deck() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French");
+        test_fixtures::add_deck(&path, 3, "Filtered Deck 1");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, did) in [1, 2].into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, did);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+            test_fixtures::move_to_filtered_deck(&path, 100 + i, 3);
+        }
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let report = processor_for(&path, true, date, date.pred_opt().unwrap(), &config)
+            .process()
+            .unwrap();
+
+        assert_eq!(report.note_ids, vec![10]);
+        assert_eq!(
+            report.by_deck,
+            BTreeMap::from([("Spanish".to_string(), DeckCounts { notes: 1, cards: 1 })])
+        );
+    }
+
+    #[test]
+    fn test_note_spanning_decks_only_moves_selected_deck_reviews() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 10, 2);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, 101);
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            backup: false,
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let report = processor_for(&path, false, date, date.pred_opt().unwrap(), &config)
+            .process()
+            .unwrap();
+
+        let moved = review_id - date::calculate_id_offset(1);
+        assert_eq!(report.card_ids, vec![100]);
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![moved, review_id + 1000]);
+    }
+
+    #[test]
+    fn test_deck_pattern_selects_matching_decks() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Language\u{1f}Spanish\u{1f}Listening");
+        test_fixtures::add_deck(&path, 2, "Language\u{1f}Spanish\u{1f}Listening\u{1f}Podcasts");
+        test_fixtures::add_deck(&path, 3, "Language\u{1f}French\u{1f}Listening");
+        test_fixtures::add_deck(&path, 4, "Language\u{1f}French\u{1f}Reading");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (i, did) in [1, 2, 3, 4].into_iter().enumerate() {
+            let i = i as i64;
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, did);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+
+        let config = AppConfig {
+            mode: AppMode::Pattern("language::*::listening".to_string()),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let processor = processor_for(&path, true, date, date, &config);
+
+        assert_eq!(
+            processor.fetch_matching_decks(&conn_for(&path)).unwrap(),
+            vec![
+                "Language\u{1f}French\u{1f}Listening".to_string(),
+                "Language\u{1f}Spanish\u{1f}Listening".to_string(),
+                "Language\u{1f}Spanish\u{1f}Listening\u{1f}Podcasts".to_string(),
+            ]
+        );
+        assert_eq!(reviewed_notes(&processor, &path).unwrap(), vec![10, 11, 12]);
+
+        let config = AppConfig {
+            mode: AppMode::Pattern("Nothing::*".to_string()),
+            ..Default::default()
+        };
+        assert!(processor_for(&path, true, date, date, &config).fetch_matching_decks(&conn_for(&path)).is_err());
+    }
+
+    #[test]
+    fn test_rejects_files_that_are_not_anki_collections() {
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let unrelated = dir.path().join("unrelated.anki2");
+        Connection::open(&unrelated)
+            .unwrap()
+            .execute_batch("CREATE TABLE col (id integer PRIMARY KEY, ver integer); CREATE TABLE notes (id integer);")
+            .unwrap();
+        let err = processor_for(&unrelated, true, date, date, &config).process().unwrap_err();
+        assert!(matches!(err, AppError::NotACollection { .. }));
+        assert!(err.to_string().contains("doesn't look like an Anki collection (missing table 'cards')"));
+
+        let garbage = dir.path().join("garbage.anki2");
+        fs::write(&garbage, vec![b'x'; 4096]).unwrap();
+        let err = processor_for(&garbage, true, date, date, &config).process().unwrap_err();
+        assert!(err.to_string().contains("not an SQLite database"));
+
+        let (_dir, path) = test_fixtures::create_collection(4);
+        assert_eq!(processor_for(&path, true, date, date, &config).check_schema(&conn_for(&path)).unwrap(), 18);
+    }
+
+    #[test]
+    fn test_pick_deck() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        test_fixtures::add_deck(&path, 11, "Spanish\u{1F}Verbs");
+        test_fixtures::add_deck(&path, 20, "French");
+        let conn = conn_for(&path);
+
+        let mut output = Vec::new();
+        let mode = pick_deck(&conn, &mut "7\nthree\n3\n".as_bytes(), &mut output, true).unwrap();
+        assert_eq!(mode, AppMode::Decks(vec!["Spanish::Verbs".to_string()]));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("  1  French\n  2  Spanish\n  3  Spanish::Verbs\n"));
+        assert_eq!(output.matches("Please enter a number from 1 to 3").count(), 2);
+
+        let mode = pick_deck(&conn, &mut "ALL\n".as_bytes(), &mut Vec::new(), true).unwrap();
+        assert_eq!(mode, AppMode::All);
+        assert!(matches!(
+            pick_deck(&conn, &mut "".as_bytes(), &mut Vec::new(), true),
+            Err(AppError::Cancelled)
+        ));
+        assert!(matches!(
+            pick_deck(&conn, &mut "1\n".as_bytes(), &mut Vec::new(), false),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_case_sensitive_isolates_decks_differing_by_case() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "DE");
+        test_fixtures::add_deck(&path, 20, "de");
+        test_fixtures::add_deck(&path, 21, "de\u{1F}Verbs");
+        for (note, deck) in [(1, 10), (2, 20), (3, 21)] {
+            test_fixtures::add_note(&path, note);
+            test_fixtures::add_card(&path, 100 + note, note, deck);
+            test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3) + note, 100 + note);
+        }
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = from.pred_opt().unwrap();
+        let notes = |mode: AppMode, case_sensitive: bool| {
+            let config = AppConfig { mode, case_sensitive, ..Default::default() };
+            reviewed_notes(&processor_for(&path, true, from, to, &config), &path).unwrap()
+        };
+        let decks = |name: &str| AppMode::Decks(vec![name.to_string()]);
+
+        assert_eq!(notes(decks("DE"), false), vec![1, 2, 3]);
+        assert_eq!(notes(decks("DE"), true), vec![1]);
+        assert_eq!(notes(decks("de"), true), vec![2, 3]);
+        assert_eq!(notes(AppMode::Pattern("D*".to_string()), true), vec![1]);
+        assert_eq!(notes(AppMode::Pattern("D*".to_string()), false), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_deck_id_selects_the_deck_and_its_children() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Espa\u{f1}ol");
+        test_fixtures::add_deck(&path, 11, "Espa\u{f1}ol\x1fVerbos");
+        test_fixtures::add_deck(&path, 20, "Espa\u{f1}ol 2");
+        for (note, deck) in [(1, 10), (2, 11), (3, 20)] {
+            test_fixtures::add_note(&path, note);
+            test_fixtures::add_card(&path, 100 + note, note, deck);
+            test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3) + note, 100 + note);
+        }
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = from.pred_opt().unwrap();
+        let notes = |id: i64, exclude_subdecks: bool| {
+            let config = AppConfig { mode: AppMode::DeckId(id), exclude_subdecks, ..Default::default() };
+            reviewed_notes(&processor_for(&path, true, from, to, &config), &path)
+        };
+
+        assert_eq!(notes(10, false).unwrap(), vec![1, 2]);
+        assert_eq!(notes(10, true).unwrap(), vec![1]);
+        assert_eq!(notes(11, false).unwrap(), vec![2]);
+        assert!(matches!(notes(99, false), Err(AppError::NoDeckWithId(99))));
+    }
+
+    #[test]
+    fn test_count_notes() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        test_fixtures::add_deck(&path, 20, "French");
+        for (note, deck) in [(1, 10), (2, 10), (3, 20)] {
+            test_fixtures::add_note(&path, note);
+            test_fixtures::add_card(&path, 100 + note, note, deck);
+            test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3) + note, 100 + note);
+        }
+        let before = test_fixtures::revlog_ids(&path);
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = from.pred_opt().unwrap();
+
+        let config = AppConfig::default();
+        assert_eq!(processor_for(&path, false, from, to, &config).count_notes().unwrap(), 3);
+        let mut processor = processor_for(&path, false, from, to, &config);
+        processor.limit = 1;
+        assert_eq!(processor.count_notes().unwrap(), 1);
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(processor_for(&path, false, from, to, &config).count_notes().unwrap(), 2);
+        // Counting never modifies the collection
+        assert_eq!(test_fixtures::revlog_ids(&path), before);
+    }
+
+    #[test]
+    fn test_refuses_unsupported_schema_version() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        Connection::open(&path).unwrap().execute("UPDATE col SET ver = 19", []).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        let config = AppConfig::default();
+        let err = processor_for(&path, true, date, date, &config).process().unwrap_err();
+        assert!(matches!(err, AppError::UnsupportedCollection(_)));
+        assert!(err.to_string().contains("schema version 19"));
+
+        let config = AppConfig {
+            force_schema: true,
+            ..Default::default()
+        };
+        assert!(processor_for(&path, true, date, date, &config).process().is_ok());
+    }
+
+    #[test]
+    fn test_confirm_run() {
+        let config = AppConfig::default();
+        let processor = AnkiProcessor::new(PathBuf::from("collection.anki2"), false, 0, None, None, &config);
+
+        assert!(processor.confirm_run("summary", &mut io::Cursor::new("y\n"), true).unwrap());
+        assert!(processor.confirm_run("summary", &mut io::Cursor::new(" YES \n"), true).unwrap());
+        assert!(!processor.confirm_run("summary", &mut io::Cursor::new("\n"), true).unwrap());
+        assert!(!processor.confirm_run("summary", &mut io::Cursor::new("nope\n"), true).unwrap());
+        assert!(matches!(
+            processor.confirm_run("summary", &mut io::Cursor::new("y\n"), false),
+            Err(AppError::ConfirmationRequired)
+        ));
+        // An unreadable answer is an I/O error, not a database one
+        assert!(matches!(
+            processor.confirm_run("summary", &mut io::Cursor::new(vec![0xff, b'\n']), true),
+            Err(AppError::Io(_))
+        ));
+
+        let config = AppConfig {
+            json: true,
+            ..Default::default()
+        };
+        let processor = AnkiProcessor::new(PathBuf::from("collection.anki2"), false, 0, None, None, &config);
+        assert!(matches!(
+            processor.confirm_run("summary", &mut io::Cursor::new("y\n"), true),
+            Err(AppError::ConfirmationRequired)
+        ));
+    }
+
+    #[test]
+    fn test_refuses_to_run_while_collection_is_locked() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        assert!(!collection_in_use(&path).unwrap());
+
+        // Anki keeps a write lock on the collection while it is open
+        let anki = Connection::open(&path).unwrap();
+        anki.execute_batch("BEGIN IMMEDIATE;").unwrap();
+        assert!(collection_in_use(&path).unwrap());
+
+        let config = AppConfig::default();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let err = processor_for(&path, false, date, date.pred_opt().unwrap(), &config)
+            .process()
+            .unwrap_err();
+        assert!(matches!(err, AppError::CollectionInUse));
+        assert!(err.to_string().contains("open in Anki"));
+
+        // Simulate mode writes nothing, so it is allowed
+        assert!(processor_for(&path, true, date, date.pred_opt().unwrap(), &config).process().is_ok());
+
+        // Undo writes too
+        let err = processor_for(&path, false, date, date, &config).undo(&journal::Journal::default()).unwrap_err();
+        assert!(matches!(err, AppError::CollectionInUse));
+
+        anki.execute_batch("ROLLBACK;").unwrap();
+        assert!(!collection_in_use(&path).unwrap());
+    }
+
+    #[test]
+    fn test_parent_child_input_matches_internal_separator() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "Spanish\u{1f}Verbs");
+        test_fixtures::add_deck(&path, 3, "Spanish\u{1f}Verbs\u{1f}Irregular");
+        test_fixtures::add_deck(&path, 4, "Spanish\u{1f}Nouns");
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["spanish::verbs".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            processor_for(&path, true, date, date, &config).fetch_matching_decks(&conn_for(&path)).unwrap(),
+            vec!["Spanish\u{1f}Verbs".to_string(), "Spanish\u{1f}Verbs\u{1f}Irregular".to_string()]
+        );
+
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish::Verbs".to_string()]),
+            exclude_subdecks: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            processor_for(&path, true, date, date, &config).fetch_matching_decks(&conn_for(&path)).unwrap(),
+            vec!["Spanish\u{1f}Verbs".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_keep_backups_prunes_after_run() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3), 100);
+        for stamp in ["20250101T000000Z", "20250102T000000Z", "20250103T000000Z"] {
+            fs::write(dir.path().join(format!("collection.anki2.bak-{}", stamp)), b"db").unwrap();
+        }
+
+        let config = AppConfig {
+            keep_backups: Some(1),
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        processor_for(&path, false, from, to, &config).process().unwrap();
+
+        // Only the backup made by this run survives.
+        let remaining = backup::list_backups(dir.path(), &path).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert!(!remaining[0].ends_with("collection.anki2.bak-20250103T000000Z"));
+    }
+
+    #[test]
+    fn test_backup_written_before_changes() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+
+        let backup_dir = dir.path().join("backups");
+        let config = AppConfig {
+            backup_dir: Some(backup_dir.clone()),
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        processor_for(&path, false, from, to, &config).process().unwrap();
+
+        let backups = backup::list_backups(&backup_dir, &path).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(test_fixtures::revlog_ids(&backups[0]), vec![review_id]);
+        assert_ne!(test_fixtures::revlog_ids(&path), vec![review_id]);
+    }
+
+    #[test]
+    fn test_no_backup_and_simulate_skip_backup() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3), 100);
+
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let config = AppConfig::default();
+        processor_for(&path, true, from, to, &config).process().unwrap();
+        let config = AppConfig {
+            backup: false,
+            ..Default::default()
+        };
+        processor_for(&path, false, from, to, &config).process().unwrap();
+
+        assert!(backup::list_backups(dir.path(), &path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_simulate_does_not_modify_revlog() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        processor_for(&path, true, from, to, &config).process().unwrap();
+
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id]);
+    }
+
+    #[test]
+    fn test_unwritable_journal_leaves_the_collection_alone() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        // A file where the journal directory should be
+        let blocked = dir.path().join("journals");
+        fs::write(&blocked, "").unwrap();
+
+        let config = AppConfig { backup: false, backup_dir: Some(blocked), ..Default::default() };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let err = processor_for(&path, false, from, from.pred_opt().unwrap(), &config).process().unwrap_err();
+        assert!(matches!(&err, AppError::Io(_)), "{:?}", err);
+        assert!(err.to_string().contains("Cannot write the undo journal in"));
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id]);
+    }
+
+    #[test]
+    fn test_failed_run_rolls_back_all_notes() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 11, 1);
+        let first = test_fixtures::noon_utc_millis(2025, 1, 3);
+        let second = first + 1000;
+        test_fixtures::add_review(&path, first, 100);
+        test_fixtures::add_review(&path, second, 101);
+
+        // Fail partway through: the second note's card cannot be updated.
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER fail_card BEFORE UPDATE ON cards WHEN old.id = 101
+                 BEGIN SELECT RAISE(ABORT, 'simulated failure'); END;",
+            )
+            .unwrap();
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        assert!(processor_for(&path, false, from, to, &config).process().is_err());
+
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![first, second]);
+        let scm: i64 = Connection::open(&path)
+            .unwrap()
+            .query_row("SELECT scm FROM col", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(scm, 0);
+    }
+
+    #[test]
+    fn test_select_limited_by_note_id() {
+        let notes: Vec<i64> = (1..=10).collect();
+        assert_eq!(select_limited(notes.clone(), 3, LimitOrder::NoteId, None), vec![1, 2, 3]);
+        assert_eq!(select_limited(notes.clone(), 0, LimitOrder::NoteId, None), notes);
+    }
+
+    #[test]
+    fn test_limit_notes_per_deck() {
+        // Notes 1-4 in deck 10, notes 5-6 in deck 20, note 7 in both
+        let rows = [(1, 10), (2, 10), (3, 10), (4, 10), (5, 20), (6, 20), (7, 10), (7, 20)]
+            .map(|(note_id, deck_id)| queries::ReviewedNote { note_id, deck_id, first_review: 0, last_review: 0 })
+            .to_vec();
+
+        assert_eq!(limit_notes(rows.clone(), 2, LimitScope::Global, LimitOrder::NoteId, None), vec![1, 2]);
+        assert_eq!(limit_notes(rows.clone(), 2, LimitScope::PerDeck, LimitOrder::NoteId, None), vec![1, 2, 5, 6]);
+        assert_eq!(limit_notes(rows, 0, LimitScope::PerDeck, LimitOrder::NoteId, None), (1..=7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_limit_order_by_review_time() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        let noon = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for (note, hours) in [(1, -1), (2, 3), (3, -3), (4, 1)] {
+            test_fixtures::add_note(&path, note);
+            test_fixtures::add_card(&path, 100 + note, note, 10);
+            test_fixtures::add_review(&path, noon + hours * 3_600_000, 100 + note);
+        }
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = from.pred_opt().unwrap();
+
+        let selected = |limit_order| {
+            // Counted in UTC, so every review from 09:00 to 15:00 falls on the same Anki day
+            let config = AppConfig { limit_order, timezone: date::Zone::parse("UTC").unwrap(), ..Default::default() };
+            let mut processor = processor_for(&path, true, from, to, &config);
+            processor.limit = 2;
+            reviewed_notes(&processor, &path).unwrap()
+        };
+        assert_eq!(selected(LimitOrder::NoteId), vec![1, 2]);
+        assert_eq!(selected(LimitOrder::Recent), vec![2, 4]);
+        assert_eq!(selected(LimitOrder::Oldest), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_select_limited_random_is_reproducible_with_seed() {
+        let notes: Vec<i64> = (1..=100).collect();
+        let first = select_limited(notes.clone(), 10, LimitOrder::Random, Some(42));
+        let second = select_limited(notes.clone(), 10, LimitOrder::Random, Some(42));
+
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 10);
+        assert!(first.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(first.iter().all(|id| notes.contains(id)));
+    }
+
+    #[test]
+    fn test_compact_leaves_collection_queryable() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+
+        let config = AppConfig {
+            compact: true,
+            backup: false,
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        processor_for(&path, false, from, to, &config).process().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0)).unwrap();
+        assert_eq!(integrity, "ok");
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![review_id - date::calculate_id_offset(1)]
+        );
+    }
+
+    #[test]
+    fn test_open_database_waits_for_a_brief_lock() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        let anki = Connection::open(&path).unwrap();
+        anki.execute_batch("BEGIN EXCLUSIVE;").unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            anki.execute_batch("COMMIT;").unwrap();
+        });
+
+        let conn = open_database_with_collation(path.to_str().unwrap()).unwrap();
+        let version: i64 = conn.query_row("SELECT ver FROM col", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, 18);
+        release.join().unwrap();
+
+        let missing = path.with_file_name("missing.anki2");
+        assert!(open_database_with_collation(missing.to_str().unwrap()).is_err());
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_busy_error_message() {
+        let busy = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None);
+        let message = AppError::from(busy).to_string();
+        assert!(message.contains("Is Anki open?"), "{}", message);
+    }
+
+    #[test]
+    fn test_backfill_adds_a_review_inside_the_day() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        test_fixtures::add_deck(&path, 20, "French");
+        test_fixtures::add_note(&path, 1);
+        test_fixtures::add_card(&path, 100, 1, 10);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 1), 100);
+        test_fixtures::add_note(&path, 2);
+        test_fixtures::add_card(&path, 200, 2, 20);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 2), 200);
+        let scm = |path: &Path| -> i64 { conn_for(path).query_row("SELECT scm FROM col", [], |row| row.get(0)).unwrap() };
+        let scm_before = scm(&path);
+        let day = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            backup: false,
+            ..Default::default()
+        };
+
+        // Simulating changes nothing
+        let preview = AnkiProcessor::new(path.clone(), true, 0, None, None, &config);
+        assert!(preview.backfill(day).unwrap().is_some());
+        assert_eq!(test_fixtures::revlog_ids(&path).len(), 2);
+
+        let processor = AnkiProcessor::new(path.clone(), false, 0, None, None, &config);
+        let id = processor.backfill(day).unwrap().unwrap();
+        let (start, end) = processor.anki_day_window(day, four_am()).unwrap();
+        assert!(start <= id && id < end);
+        assert!(test_fixtures::revlog_ids(&path).contains(&id));
+        assert_eq!(scm(&path), scm_before + 1);
+        let (cid, usn, ease): (i64, i64, i64) = conn_for(&path)
+            .query_row("SELECT cid, usn, ease FROM revlog WHERE id = ?1", [id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((cid, usn, ease), (100, -1, 3));
+
+        // The day now has a review, so a second backfill does nothing
+        assert_eq!(processor.backfill(day).unwrap(), None);
+        assert_eq!(test_fixtures::revlog_ids(&path).len(), 3);
+
+        // Nothing was reviewed before the first day
+        let first_day = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        assert!(matches!(processor.backfill(first_day), Err(AppError::Config(_))));
+    }
+
+    #[test]
+    fn test_validate_collection_path() {
+        let (dir, path) = test_fixtures::create_collection(4);
+        assert!(validate_collection_path(&path).is_ok());
+
+        let missing = dir.path().join("missing.anki2");
+        let err = validate_collection_path(&missing).unwrap_err();
+        assert!(matches!(&err, AppError::Config(message) if message.contains("does not exist")), "{}", err);
+
+        let wrong_extension = dir.path().join("collection.db");
+        fs::write(&wrong_extension, b"").unwrap();
+        let err = validate_collection_path(&wrong_extension).unwrap_err();
+        assert!(matches!(&err, AppError::Config(message) if message.contains(".anki2")), "{}", err);
+    }
+
+    #[test]
+    fn test_verify_target_count() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        test_fixtures::add_deck(&path, 20, "French");
+        for (note, deck) in [(1, 10), (2, 10), (3, 20)] {
+            test_fixtures::add_note(&path, note);
+            test_fixtures::add_card(&path, 100 + note, note, deck);
+            test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3) + note, 100 + note);
+        }
+        // One Spanish review already on the target day
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 2), 101);
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = from.pred_opt().unwrap();
+        let config = AppConfig {
+            mode: AppMode::Decks(vec!["Spanish".to_string()]),
+            verify: true,
+            ..Default::default()
+        };
+
+        let processor = processor_for(&path, false, from, to, &config);
+        let report = processor.process().unwrap();
+        assert_eq!(report.summary.reviews, 2);
+        assert_eq!(report.verified, Some(true));
+
+        let conn = conn_for(&path);
+        let spanish = ["Spanish".to_string()];
+        let window = processor.target_window(to, four_am()).unwrap();
+        assert!(processor.verify_target_count(&conn, window, Some(&spanish), 1, 2).unwrap());
+        assert!(!processor.verify_target_count(&conn, window, Some(&spanish), 0, 2).unwrap());
+    }
+
+    #[test]
+    fn test_check_landing_strict_fails_on_misplaced_review() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        let target = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let (start, _) = date::day_window(&Local, target, four_am()).unwrap();
+        test_fixtures::add_review(&path, start + 1000, 100);
+        test_fixtures::add_review(&path, start - 1000, 100);
+
+        let config = AppConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let processor = processor_for(&path, false, target, target, &config);
+
+        assert!(processor.check_landing(&conn_for(&path), &[start + 1000], target, four_am()).is_ok());
+        assert!(matches!(
+            processor.check_landing(&conn_for(&path), &[start + 1000, start - 1000], target, four_am()),
+            Err(AppError::MisplacedReviews(_))
+        ));
+
+        let lenient = AppConfig::default();
+        let processor = processor_for(&path, false, target, target, &lenient);
+        assert!(processor.check_landing(&conn_for(&path), &[start - 1000], target, four_am()).is_ok());
+    }
+
+    #[test]
+    fn test_json_report_describes_simulated_run() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Spanish");
+        test_fixtures::add_deck(&path, 2, "French\u{1f}Verbs");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_note(&path, 11);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_card(&path, 101, 11, 2);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, 101);
+
+        let config = AppConfig {
+            json: true,
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let report = processor_for(&path, true, from, to, &config).process().unwrap();
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["mode"], "all");
+        assert_eq!(json["decks"], serde_json::json!([]));
+        assert_eq!(json["from_date"], "2025-01-03");
+        assert_eq!(json["to_date"], "2025-01-01");
+        assert_eq!(json["rollover_hours"], 4);
+        assert_eq!(json["offset_days"], 2);
+        assert_eq!(json["simulate"], true);
+        assert_eq!(json["note_ids"], serde_json::json!([10, 11]));
+        assert_eq!(json["summary"], serde_json::json!({ "notes": 2, "cards": 2, "reviews": 2 }));
+        assert_eq!(json["card_ids"], serde_json::json!([100, 101]));
+        assert_eq!(
+            json["by_deck"],
+            serde_json::json!({
+                "French::Verbs": { "notes": 1, "cards": 1 },
+                "Spanish": { "notes": 1, "cards": 1 },
+            })
+        );
+        // Simulate mode leaves the collection untouched
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id, review_id + 1000]);
+    }
+
+    #[test]
+    fn test_undo_restores_reviews_and_cards_once() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_review(&path, review_id, 100);
+        test_fixtures::add_review(&path, review_id + 1000, 100);
+        let card_state = |path: &Path| -> (i64, i64) {
+            Connection::open(path)
+                .unwrap()
+                .query_row("SELECT mod, usn FROM cards WHERE id = 100", [], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+        };
+        let before = card_state(&path);
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_ne!(test_fixtures::revlog_ids(&path), vec![review_id, review_id + 1000]);
+        assert_ne!(card_state(&path), before);
+
+        let journal = journal::read_journal(Path::new(&report.journal.unwrap())).unwrap();
+        assert_eq!(journal.revlog.len(), 2);
+        assert_eq!(journal.cards.len(), 1);
+
+        let processor = processor_for(&path, false, from, to, &config);
+        let report = processor.undo(&journal).unwrap();
+        assert_eq!((report.restored_reviews, report.reinserted_reviews, report.cards), (2, 0, 1));
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id, review_id + 1000]);
+        assert_eq!(card_state(&path), before);
+
+        // A second undo must not shift the reviews again
+        assert!(matches!(processor.undo(&journal), Err(AppError::UndoMismatch(_))));
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id, review_id + 1000]);
+    }
+
+    #[test]
+    fn test_multi_day_span_moves_reviews_by_days_between() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 5);
+        test_fixtures::add_review(&path, review_id, 100);
+
+        let config = AppConfig {
+            backup: false,
+            ..Default::default()
+        };
+        let from = NaiveDate::from_ymd_opt(2025, 1, 5).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let processor = processor_for(&path, false, from, to, &config);
+        assert_eq!(processor.id_offset(), 3 * 86_400_000);
+
+        let report = processor.process().unwrap();
+
+        assert_eq!(report.offset_days, 3);
+        assert_eq!(
+            test_fixtures::revlog_ids(&path),
+            vec![test_fixtures::noon_utc_millis(2025, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_default_dates_move_today_to_yesterday() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        let today = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let (from, to) = date::resolve_date_range(None, None, today, 1);
+
+        let config = AppConfig::default();
+        let processor = processor_for(&path, true, from.unwrap(), to.unwrap(), &config);
+        assert_eq!(processor.id_offset(), date::calculate_id_offset(1));
+        assert!(reviewed_notes(&processor, &path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lone_from_moves_that_day_back_by_shift() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 10, "Spanish");
+        test_fixtures::add_note(&path, 1);
+        test_fixtures::add_card(&path, 100, 1, 10);
+        test_fixtures::add_review(&path, test_fixtures::noon_utc_millis(2025, 1, 3), 100);
+        let today = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3);
+
+        let (from, to) = date::resolve_date_range(from, None, today, 1);
+        let config = AppConfig::default();
+        let processor = processor_for(&path, false, from.unwrap(), to.unwrap(), &config);
+        assert_eq!(processor.id_offset(), date::calculate_id_offset(1));
+        let report = processor.process().unwrap();
+        assert_eq!(report.to_date, "2025-01-02");
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![test_fixtures::noon_utc_millis(2025, 1, 2)]);
+    }
+
+    #[test]
+    fn test_rollover_lookup_fallbacks() {
+        let (_dir, path) = test_fixtures::create_collection(5);
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let config = AppConfig::default();
+        let processor = processor_for(&path, true, date, date, &config);
+
+        // From the config table
+        assert_eq!(processor.get_rollover(&conn_for(&path)).unwrap(), NaiveTime::from_hms_opt(5, 0, 0).unwrap());
+
+        // From col.conf when the config table has no rollover key
+        test_fixtures::remove_rollover(&path);
+        test_fixtures::set_col_conf(&path, r#"{"rollover": 2, "nextPos": 1}"#);
+        assert_eq!(processor.get_rollover(&conn_for(&path)).unwrap(), NaiveTime::from_hms_opt(2, 0, 0).unwrap());
+
+        // Anki's default when neither has it
+        test_fixtures::set_col_conf(&path, r#"{"nextPos": 1}"#);
+        assert_eq!(processor.get_rollover(&conn_for(&path)).unwrap(), four_am());
+
+        // --rollover wins over everything
+        let config = AppConfig {
+            rollover: Some(NaiveTime::from_hms_opt(7, 30, 0).unwrap()),
+            ..Default::default()
+        };
+        let processor = processor_for(&path, true, date, date, &config);
+        assert_eq!(processor.get_rollover(&conn_for(&path)).unwrap(), NaiveTime::from_hms_opt(7, 30, 0).unwrap());
+        assert_eq!(processor.collection_rollover(&conn_for(&path)).unwrap(), four_am());
+    }
+
+    #[test]
+    fn test_notes_are_selected_by_the_anki_day() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let hour = 3_600_000;
+        let day = 86_400_000;
+        let moved = |zone: &str, review_id: i64| {
+            let (_dir, path) = test_fixtures::create_collection(4);
+            test_fixtures::add_deck(&path, 1, "Default");
+            test_fixtures::add_note(&path, 10);
+            test_fixtures::add_card(&path, 100, 10, 1);
+            test_fixtures::add_review(&path, review_id, 100);
+            let config = AppConfig { timezone: date::Zone::parse(zone).unwrap(), ..Default::default() };
+            assert_eq!(processor_for(&path, true, from, to, &config).count_notes().unwrap(), 1);
+            processor_for(&path, false, from, to, &config).process().unwrap();
+            test_fixtures::revlog_ids(&path)
+        };
+
+        // 02:00 UTC on the 4th is still the 3rd's Anki day with a 04:00 rollover
+        let late = test_fixtures::noon_utc_millis(2025, 1, 4) - 10 * hour;
+        assert_eq!(moved("UTC", late), vec![late - day]);
+
+        // Noon in Auckland on the 3rd is 23:00 UTC on the 2nd
+        let auckland = test_fixtures::noon_utc_millis(2025, 1, 2) + 11 * hour;
+        assert_eq!(moved("Pacific/Auckland", auckland), vec![auckland - day]);
+    }
+
+    #[test]
+    fn test_reviews_around_utc_midnight_stay_on_their_anki_day() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let la = |day, hour, minute| test_fixtures::local_millis("America/Los_Angeles", 2025, 1, day, hour, minute);
+        // UTC midnight falls at 16:00 in Los Angeles, in the middle of the Anki day
+        let on_the_day = [la(3, 4, 1), la(3, 15, 59), la(3, 16, 1), la(4, 3, 59)];
+        let off_the_day = [la(3, 3, 30), la(4, 4, 1)];
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        for (cid, review_id) in (100..).zip(on_the_day.iter().chain(&off_the_day)) {
+            test_fixtures::add_note(&path, cid);
+            test_fixtures::add_card(&path, cid, cid, 1);
+            test_fixtures::add_review(&path, *review_id, cid);
+        }
+
+        let config = AppConfig { timezone: date::Zone::parse("America/Los_Angeles").unwrap(), ..Default::default() };
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+
+        assert_eq!(report.note_ids, vec![100, 101, 102, 103]);
+        let mut expected: Vec<i64> = on_the_day.iter().map(|id| id - 86_400_000).chain(off_the_day).collect();
+        expected.sort_unstable();
+        assert_eq!(test_fixtures::revlog_ids(&path), expected);
+    }
+
+    #[test]
+    fn test_only_legacy_collections_count_days_from_crt() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let tokyo = date::Zone::Fixed(chrono::FixedOffset::east_opt(9 * 3_600).unwrap());
+        // 11:00 in Tokyo on 2025-01-03, but still the Anki day 2025-01-02 in UTC and west of it
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3) - 10 * 3_600_000;
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        test_fixtures::add_card(&path, 100, 10, 1);
+        test_fixtures::add_review(&path, review_id, 100);
+        let config = AppConfig::default();
+
+        // Without a crt the system's zone applies
+        let processor = processor_for(&path, true, from, to, &config);
+        assert_eq!(processor.load_zone(&conn_for(&path)).unwrap(), date::Zone::Local);
+
+        // A legacy collection created at 04:00 in Tokyo
+        test_fixtures::set_creation(&path, 1_577_818_800, None);
+        let processor = processor_for(&path, true, from, to, &config);
+        assert_eq!(processor.load_zone(&conn_for(&path)).unwrap(), tokyo);
+
+        // --timezone still wins
+        let utc = AppConfig { timezone: date::Zone::parse("UTC").unwrap(), ..Default::default() };
+        let processor = processor_for(&path, true, from, to, &utc);
+        assert_eq!(processor.load_zone(&conn_for(&path)).unwrap(), utc.timezone);
+
+        // The review counts as Tokyo's 3rd
+        let report = AnkiProcessor::new(path.clone(), false, 0, Some(from), Some(to), &config).process().unwrap();
+        assert_eq!(report.note_ids, vec![10]);
+        assert_eq!(test_fixtures::revlog_ids(&path), vec![review_id - 86_400_000]);
+
+        // On a later scheduler, neither crt nor the creation offset moves the day boundaries
+        test_fixtures::set_creation(&path, 1_577_818_800, Some(-540));
+        test_fixtures::set_scheduler_version(&path, 2);
+        let processor = processor_for(&path, true, from, to, &config);
+        assert_eq!(processor.load_zone(&conn_for(&path)).unwrap(), date::Zone::Local);
+    }
+
+    #[test]
+    fn test_missing_rollover_key_defaults_to_four() {
+        let conn = test_fixtures::in_memory_collection(4);
+        conn.execute("DELETE FROM config WHERE KEY = 'rollover'", []).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let config = AppConfig::default();
+        let processor = processor_for(Path::new("collection.anki2"), true, date, date, &config);
+        assert_eq!(processor.collection_rollover(&conn).unwrap(), four_am());
+
+        // A value that is there but unreadable is still an error, not silently 4:00
+        conn.execute("INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('rollover', 0, 0, CAST('late' AS BLOB))", [])
+            .unwrap();
+        assert!(processor.collection_rollover(&conn).is_err());
+    }
+
+    #[test]
+    fn test_rollover_override_warning() {
+        let seven = NaiveTime::from_hms_opt(7, 30, 0).unwrap();
+        assert_eq!(
+            rollover_override_warning(seven, Some(four_am())),
+            "Warning: Anki days start at 07:30 for this run, overridden by --rollover or the config file \
+             instead of the collection's 04:00."
+        );
+        assert!(rollover_override_warning(four_am(), Some(four_am())).ends_with("(the same as the collection's setting)."));
+        assert!(rollover_override_warning(seven, None).ends_with("config file."));
+    }
+
+    #[test]
+    fn test_rollover_parse_error_is_fatal() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        Connection::open(&path)
+            .unwrap()
+            .execute("UPDATE config SET val = CAST('four' AS BLOB) WHERE KEY = 'rollover'", [])
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let config = AppConfig::default();
+
+        assert!(processor_for(&path, true, date, date, &config).get_rollover(&conn_for(&path)).is_err());
+    }
+
+    #[test]
+    fn test_scm_untouched_when_nothing_moves() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        test_fixtures::add_note(&path, 10);
+        // The card was created after the day the review would move to, so the review is skipped
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        test_fixtures::add_card(&path, review_id - 1000, 10, 1);
+        test_fixtures::add_review(&path, review_id, review_id - 1000);
+        let scm = || -> i64 { conn_for(&path).query_row("SELECT scm FROM col", [], |row| row.get(0)).unwrap() };
+        let before = scm();
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert_eq!(report.skipped_reviews, 1);
+        assert!(report.revlog_ids.is_empty());
+        assert_eq!(scm(), before);
+
+        // No matching notes at all
+        let from = NaiveDate::from_ymd_opt(2025, 2, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 2, 2).unwrap();
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+        assert!(report.note_ids.is_empty());
+        assert_eq!(scm(), before);
+    }
+
+    #[test]
+    fn test_scm_bumped_once_per_run() {
+        let (_dir, path) = test_fixtures::create_collection(4);
+        test_fixtures::add_deck(&path, 1, "Default");
+        let review_id = test_fixtures::noon_utc_millis(2025, 1, 3);
+        for i in 0..3 {
+            test_fixtures::add_note(&path, 10 + i);
+            test_fixtures::add_card(&path, 100 + i, 10 + i, 1);
+            test_fixtures::add_review(&path, review_id + i * 1000, 100 + i);
+        }
+        let scm = || -> i64 {
+            Connection::open(&path)
+                .unwrap()
+                .query_row("SELECT scm FROM col", [], |row| row.get(0))
+                .unwrap()
+        };
+        let before = scm();
+
+        let config = AppConfig::default();
+        let from = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+        let to = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let report = processor_for(&path, false, from, to, &config).process().unwrap();
+
+        assert_eq!(report.note_ids.len(), 3);
+        assert_eq!(report.summary, RunSummary { notes: 3, cards: 3, reviews: 3 });
+        assert_eq!(report.summary.message(false), "Moved 3 reviews across 3 cards in 3 notes.");
+        assert_eq!(scm(), before + 1);
+        let usns: Vec<i64> = Connection::open(&path)
+            .unwrap()
+            .prepare("SELECT usn FROM cards ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(usns, vec![-1, -1, -1]);
+    }
+
+    #[test]
+    fn test_collection_path() {
+        let collection = AnkiCollection::new("test_collection");
+        let path = collection.collection_path();
+
+        assert!(path.to_str().unwrap().contains("test_collection"));
+        assert!(path.to_str().unwrap().ends_with("collection.anki2"));
+    }
+
+    #[test]
+    fn test_collection_path_from_anki_base() {
+        let base = tempfile::tempdir().unwrap();
+        env::set_var("ANKI_BASE", base.path());
+        let path = AnkiCollection::new("User 1").collection_path();
+        env::remove_var("ANKI_BASE");
+
+        assert_eq!(path, base.path().join("User 1").join("collection.anki2"));
+    }
+
+    #[test]
+    fn test_base_dir_has_no_doubled_separators() {
+        let home = PathBuf::from(shellexpand::tilde("~").to_string());
+        for (os, dirs) in [
+            ("macos", ["Library", "Application Support", "Anki2"]),
+            ("linux", [".local", "share", "Anki2"]),
+        ] {
+            let path = AnkiCollection::base_dir(os).join("User 1").join("collection.anki2");
+            let expected = dirs.iter().fold(home.clone(), |path, dir| path.join(dir));
+
+            assert_eq!(path, expected.join("User 1").join("collection.anki2"));
+            assert!(!path.to_string_lossy().contains("//"), "{}: {}", os, path.display());
+            assert_eq!(path.file_name().unwrap(), "collection.anki2");
+            assert_eq!(path.parent().unwrap().file_name().unwrap(), "User 1");
+        }
+    }
+
+    #[test]
+    fn test_windows_base_dir_from_environment() {
+        let expected_tail = Path::new("Anki2").join("User 1").join("collection.anki2");
+
+        env::set_var("APPDATA", "C:\\Users\\alice\\AppData\\Roaming");
+        let path = AnkiCollection::base_dir("windows").join("User 1").join("collection.anki2");
+        assert!(path.starts_with("C:\\Users\\alice\\AppData\\Roaming"));
+        assert!(path.ends_with(&expected_tail));
+
+        env::remove_var("APPDATA");
+        env::set_var("USERPROFILE", "C:\\Users\\bob");
+        let path = AnkiCollection::base_dir("windows").join("User 1").join("collection.anki2");
+        assert!(path.starts_with(Path::new("C:\\Users\\bob").join("AppData").join("Roaming")));
+        assert!(path.ends_with(&expected_tail));
+        assert!(!path.to_string_lossy().contains("%USERNAME%"));
+        assert!(!path.to_string_lossy().contains("\\\\"));
+        assert_eq!(path.file_name().unwrap(), "collection.anki2");
+        env::remove_var("USERPROFILE");
+    }
+
+    #[test]
+    fn test_multi_run_report_totals_the_collections() {
+        let run = |notes, cards, reviews| RunReport { summary: RunSummary { notes, cards, reviews }, ..Default::default() };
+        let failure = CollectionFailure { collection: "User 3".to_string(), error: "locked".to_string() };
+
+        let report = MultiRunReport::new(vec![run(1, 2, 3), run(4, 5, 6)], vec![failure]);
+
+        assert_eq!(report.summary, RunSummary { notes: 5, cards: 7, reviews: 9 });
+        assert_eq!(report.failures.len(), 1);
+        let json: serde_json::Value = serde_json::from_str(&report::to_json(&report).unwrap()).unwrap();
+        assert_eq!(json["collections"].as_array().unwrap().len(), 2);
+        assert_eq!(json["failures"][0]["collection"], "User 3");
+    }
+
+    #[test]
+    fn test_list_collections() {
+        let base = tempfile::tempdir().unwrap();
+        for profile in ["User 1", "Русский"] {
+            let dir = base.path().join(profile);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("collection.anki2"), b"").unwrap();
+        }
+        fs::create_dir(base.path().join("addons21")).unwrap();
+        fs::write(base.path().join("prefs21.db"), b"").unwrap();
+
+        let names = AnkiCollection::list_collections(base.path()).unwrap();
+        assert_eq!(names, vec!["User 1".to_string(), "Русский".to_string()]);
+        assert_eq!(AnkiCollection::matching(base.path(), "User *").unwrap(), vec!["User 1".to_string()]);
+        assert_eq!(AnkiCollection::matching(base.path(), "*").unwrap(), names);
+        assert!(AnkiCollection::matching(base.path(), "addons*").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scan_and_pick_collection() {
+        let root = tempfile::tempdir().unwrap();
+        for profile in ["Anki2/User 1", "backup/old/Русский"] {
+            let dir = root.path().join(profile);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("collection.anki2"), b"").unwrap();
+        }
+        fs::write(root.path().join("Anki2").join("prefs21.db"), b"").unwrap();
+
+        let found = AnkiCollection::scan(root.path()).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                root.path().join("Anki2/User 1/collection.anki2"),
+                root.path().join("backup/old/Русский/collection.anki2"),
+            ]
+        );
+
+        let mut output = Vec::new();
+        let picked = pick_collection(root.path(), &found, &mut "3\n2\n".as_bytes(), &mut output, true).unwrap();
+        assert_eq!(picked, found[1]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  1  User 1  ("), "{}", output);
+        assert!(output.contains("  2  Русский  ("), "{}", output);
+        assert!(output.contains("Please enter a number from 1 to 2."));
+
+        // Several collections and nobody to ask
+        let err = pick_collection(root.path(), &found, &mut "".as_bytes(), &mut Vec::new(), false).unwrap_err();
+        assert!(err.to_string().contains("Found 2 collections"));
+
+        // A single collection is offered directly
+        let single = &found[..1];
+        assert_eq!(pick_collection(root.path(), single, &mut "\n".as_bytes(), &mut Vec::new(), true).unwrap(), found[0]);
+        assert!(matches!(
+            pick_collection(root.path(), single, &mut "n\n".as_bytes(), &mut Vec::new(), true),
+            Err(AppError::Cancelled)
+        ));
+        assert_eq!(pick_collection(root.path(), single, &mut "".as_bytes(), &mut Vec::new(), false).unwrap(), found[0]);
+        assert!(pick_collection(root.path(), &[], &mut "".as_bytes(), &mut Vec::new(), true).is_err());
+    }
+
+    #[test]
+    fn test_unix_base_dirs_expand_tilde() {
+        for os in ["linux", "macos"] {
+            let base = AnkiCollection::base_dir(os);
+            assert!(!base.to_string_lossy().starts_with('~'));
+            assert!(base.ends_with("Anki2"));
+        }
+    }
+}
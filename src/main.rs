@@ -1,665 +1,800 @@
-mod utils;
-mod date;
-
-use rusqlite::{params, Connection, Result};
-use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
-use clap::{Arg, ArgMatches, Command};
-use std::env;
-use unicase::UniCase;
-use std::path::PathBuf;
-use date::{parse_date, validate_dates};
-use utils::{log, replace_deck_delimiter};
-
-const APP_NAME: &str = env!("CARGO_PKG_NAME");
-const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
-
-const GREEN: &str = "\x1b[32m";
-const RESET: &str = "\x1b[0m";
-
-/// Registers a custom collation named `unicase` to enable Unicode-aware case-insensitive comparisons
-/// in SQLite.
-///
-/// # Why is this needed?
-/// SQLite's default `NOCASE` collation only supports ASCII case-insensitivity. This means that
-/// comparisons like "Ä" vs. "ä" or "ß" vs. "ss" will not work as expected. For applications dealing
-/// with Unicode data, such as deck names in Anki that might include international characters,
-/// this limitation can result in inaccurate or incomplete query results.
-///
-/// To address this, we define a `unicase` collation that uses Rust's `unicase` crate to perform
-/// Unicode-aware case-insensitive comparisons.
-///
-/// # How does it work?
-/// - SQLite provides a mechanism for defining custom collations via the `create_collation` method
-///   in the `rusqlite` crate.
-/// - The `unicase` crate simplifies case-insensitive comparisons by normalizing strings before
-///   comparing them.
-/// - When this collation is applied in SQLite queries, it ensures that the string comparison respects
-///   Unicode case-folding rules.
-///
-/// # Arguments
-/// - `conn`: A reference to the SQLite `Connection` object where the collation should be registered.
-///
-/// # Returns
-/// - `Ok(())` if the collation is registered successfully.
-/// - An error if SQLite fails to register the collation.
-///
-/// # Usage
-/// This function is typically called during database initialization, after opening a connection.
-/// The `unicase` collation can then be used in queries:
-///
-/// ```sql
-/// SELECT name
-/// FROM decks
-/// WHERE name COLLATE unicase LIKE '%example%'
-/// ORDER BY name COLLATE unicase;
-/// ```
-///
-/// # Example
-/// ```rust
-/// let conn = Connection::open("example.db")?;
-/// register_unicase_collation(&conn)?;
-/// ```
-fn register_unicase_collation(conn: &Connection) -> Result<()> {
-    conn.create_collation("unicase", |s1: &str, s2: &str| {
-        let s1_key = UniCase::new(s1);
-        let s2_key = UniCase::new(s2);
-        s1_key.cmp(&s2_key)
-    })?;
-    Ok(())
+//! Command-line front end: parses the arguments and hands the work to the library.
+
+mod cli;
+
+use anki_streak_fixer::date::{self, validate_dates};
+use anki_streak_fixer::error::AppError;
+use anki_streak_fixer::report::to_json;
+use anki_streak_fixer::utils::{self, red_text, SummaryRow};
+use anki_streak_fixer::{
+    config_file, journal, open_database_with_collation, pick_collection, pick_deck, state, validate_collection_path,
+    AnkiCollection, AnkiProcessor, AppConfig, AppMode, CollectionFailure, LimitOrder, LimitScope, ModTime,
+    MultiRunReport, OlderReviews, RunReport, UndoReport, EXIT_NO_NOTES,
+};
+use chrono::{NaiveDate, NaiveTime};
+use clap::ArgMatches;
+use log::debug;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{} {}", red_text("[ERROR]"), err); // Print the error in red
+        std::process::exit(1);
+    }
 }
 
-/// Opens a SQLite database and registers the `unicase` collation for Unicode case-insensitivity.
-///
-/// # Why is this needed?
-/// When working with SQLite databases that contain non-ASCII text, such as Unicode deck names in Anki,
-/// the default case-insensitive collation (`NOCASE`) is insufficient because it does not handle Unicode
-/// characters correctly. Without registering a custom collation, queries involving case-insensitive
-/// matches may fail or produce incomplete results.
-///
-/// This function abstracts the process of opening a SQLite connection and ensuring that the custom
-/// `unicase` collation is available for all queries that require Unicode case-insensitivity.
-///
-/// # How does it work?
-/// - This function opens a SQLite database using the given file path.
-/// - After opening the connection, it registers the `unicase` collation by calling `register_unicase_collation`.
-/// - This ensures that any subsequent queries can use the `unicase` collation.
-///
-/// # Arguments
-/// - `db_path`: The file path to the SQLite database. This should be a valid path to an existing database file.
-///
-/// # Returns
-/// - `Ok(Connection)` if the database is opened and the collation is registered successfully.
-/// - An error if the database cannot be opened or the collation fails to register.
-///
-/// # Usage
-/// Use this function instead of directly calling `Connection::open` to ensure that the custom collation is
-/// registered automatically.
-///
-/// # Example
-/// ```rust
-/// let conn = open_database_with_collation("example.db")?;
-/// let query = "SELECT name FROM decks WHERE name COLLATE unicase LIKE '%example%' ORDER BY name COLLATE unicase;";
-/// let mut stmt = conn.prepare(query)?;
-/// ```
-fn open_database_with_collation(db_path: &str) -> Result<Connection> {
-    let conn = Connection::open(db_path)?;
-    register_unicase_collation(&conn)?;
-    Ok(conn)
-}
+fn run() -> Result<(), AppError> {
+    let matches = cli::matches();
+    let (subcommand, args) = matches.subcommand().expect("clap requires a subcommand");
+    utils::set_color(utils::color_wanted(args.get_flag("no_color")));
+    // A mistyped date is reported before anything else happens
+    for option in DATE_OPTIONS {
+        date_arg(args, option)?;
+    }
 
-enum AppMode {
-    Deck(String), // Contains the deck name
-    All,          // All decks
-}
+    if subcommand == "list" {
+        utils::init_logger(args.get_count("verbose"));
+        let base_dir = AnkiCollection::profiles_dir();
+        let names = AnkiCollection::list_collections(&base_dir)
+            .map_err(|err| AppError::Config(format!("Cannot read '{}': {}", base_dir.display(), err)))?;
+        names.iter().for_each(|name| println!("{}", name));
+        return Ok(());
+    }
 
-struct AppConfig {
-    verbose: bool,
-    mode: AppMode
-}
+    // Defaults from the config file; command-line arguments always take precedence
+    let config_path = match args.get_one::<String>("config") {
+        Some(path) => Some(PathBuf::from(shellexpand::tilde(path).to_string())),
+        None => config_file::default_path(),
+    };
+    let file_config = match &config_path {
+        Some(path) if args.contains_id("config") || path.exists() => {
+            config_file::load(path).map_err(AppError::Config)?
+        }
+        _ => config_file::FileConfig::default(),
+    };
+    // `--since-last-run` reads the state file next to the config file
+    let state_path = config_path.as_deref().map(state::path_next_to);
 
-#[derive(Debug)]
-struct AnkiCollection {
-    collection_name: String,
-}
+    // `--json` output must stay parseable, so a config file can't turn on verbose logging with it
+    let verbose = args.get_count("verbose") > 0
+        || (file_config.verbose.unwrap_or(false) && !args.get_flag("json"));
+    utils::init_logger(args.get_count("verbose").max(u8::from(verbose)));
 
-impl AnkiCollection {
-    fn new(collection_name: &str) -> Self {
-        Self {
-            collection_name: collection_name.to_string(),
-        }
+    if let Ok(Some(pattern)) = args.try_get_one::<String>("collection_glob") {
+        debug!("Application started.");
+        return run_fix_glob(args, pattern, &file_config, state_path.as_deref());
     }
 
-    fn collection_path(&self) -> PathBuf {
-        let base_path = match env::consts::OS {
-            "macos" => "~/Library/Application Support/Anki2/",
-            "windows" => "C:\\Users\\%USERNAME%\\AppData\\Roaming\\Anki2\\",
-            "linux" => "~/.local/share/Anki2/",
-            _ => panic!("Unsupported OS"),
-        };
+    let db_path = collection_path(args, &file_config)?;
+
+    debug!("Application started.");
 
-        let expanded_base = shellexpand::tilde(base_path);
-        PathBuf::from(expanded_base.to_string()).join(&self.collection_name).join("collection.anki2")
+    match subcommand {
+        "undo" => run_undo(args, db_path, &file_config),
+        "report" => run_report(args, db_path, &file_config),
+        _ => {
+            let Some(report) = run_fix(args, db_path, &file_config, state_path.as_deref())? else {
+                return Ok(());
+            };
+            // Only a real run that finished counts as the last run
+            if !report.simulate {
+                record_run(state_path.as_deref());
+            }
+            if args.get_flag("json") {
+                println!("{}", to_json(&report).expect("Failed to serialize report"));
+            } else if args.get_flag("quiet") {
+                println!("{}", report.summary.message(report.simulate));
+            } else {
+                print_run_summary(&report);
+            }
+            if report.verified == Some(false) {
+                return Err(AppError::VerificationFailed);
+            }
+            if report.summary.notes == 0 {
+                std::process::exit(EXIT_NO_NOTES);
+            }
+            Ok(())
+        }
     }
 }
 
-struct AnkiProcessor<'a> {
-    simulate: bool,
-    db_path: PathBuf,
-    limit: i64,
-    from_date: Option<NaiveDate>,
-    to_date: Option<NaiveDate>,
-    config: &'a AppConfig,
+/// Resolves the collection to work on: an explicit file, one found by `--scan`, or a profile
+/// name looked up under the Anki2 directory.
+fn collection_path(args: &ArgMatches, file_config: &config_file::FileConfig) -> Result<PathBuf, AppError> {
+    let scan_root = args.get_one::<String>("scan").map(|dir| PathBuf::from(shellexpand::tilde(dir).to_string()));
+    let db_path = match (args.get_one::<String>("collection_path"), scan_root) {
+        (None, Some(root)) => {
+            let found = AnkiCollection::scan(&root)
+                .map_err(|err| AppError::Config(format!("Cannot scan '{}': {}", root.display(), err)))?;
+            let stdin = io::stdin();
+            let path = pick_collection(&root, &found, &mut stdin.lock(), &mut io::stdout(), stdin.is_terminal())?;
+            if !args.get_flag("quiet") {
+                println!("Using '{}'.", path.display());
+            }
+            path
+        }
+        (Some(path), _) => {
+            let path = PathBuf::from(shellexpand::tilde(path).to_string());
+            validate_collection_path(&path)?;
+            path
+        }
+        (None, None) => {
+            let collection_name = args
+                .get_one::<String>("collection")
+                .or(file_config.collection.as_ref())
+                .ok_or_else(|| {
+                    AppError::Config(
+                        "No collection given. Pass -c/--collection or --collection-path, or set `collection` in the config file."
+                            .to_string(),
+                    )
+                })?;
+            AnkiCollection::new(collection_name).collection_path()
+        }
+    };
+    Ok(db_path)
 }
 
-impl<'a> AnkiProcessor<'a> {
-    fn new(
-        collection_name: &str,
-        simulate: bool,
-        limit: i64,
-        from_date: Option<NaiveDate>,
-        to_date: Option<NaiveDate>,
-        config: &'a AppConfig,
-    ) -> Self {
-        let collection = AnkiCollection::new(collection_name);
-        Self {
-            //deck_name: deck_name.to_string(),
-            simulate,
-            db_path: collection.collection_path(),
-            limit,
-            from_date,
-            to_date,
-            config,
+/// Works out which decks to process from the deck arguments, falling back to the config file's
+/// `deck`.
+///
+/// Without any deck, all decks are processed only when `--all` is given or `all_by_default` is
+/// set; otherwise it is an error, so that a forgotten deck name never rewrites every deck.
+fn deck_mode(
+    args: &ArgMatches,
+    file_config: &config_file::FileConfig,
+    db_path: &Path,
+    all_by_default: bool,
+) -> Result<AppMode, AppError> {
+    // Optional deck names
+    let mut deck_names: Vec<String> = args
+        .get_many::<String>("deck_name")
+        .map(|names| names.cloned().collect())
+        .unwrap_or_default();
+    let all = args.get_flag("all");
+    let note_ids = note_ids(args)?;
+    let deck_stdin = args.get_flag("deck_stdin");
+    if deck_stdin {
+        deck_names = stdin_deck_names()?;
+        if deck_names.is_empty() {
+            return Err(AppError::Config("--deck-stdin was given, but stdin holds no deck names.".to_string()));
         }
     }
+    if deck_names.is_empty()
+        && !args.contains_id("deck_pattern")
+        && !args.contains_id("deck_id")
+        && note_ids.is_none()
+        && !all
+    {
+        deck_names.extend(file_config.deck.clone());
+    }
 
-    fn process(&self) -> Result<()> {
-        log(self.config.verbose, "Starting processing...");
-        if self.simulate {
-            println!(
-                "Running {} v{} - {}Simulation mode{}",
-                APP_NAME, APP_VERSION, GREEN, RESET
-            );
-        } else {
-            println!("Running {} v{}", APP_NAME, APP_VERSION);
-        }
-
-
-        let rollover_hours = self.get_rollover_hours()?;
-        let today = Local::now().date_naive();
-
-        // Use from_date if provided, otherwise use today
-        let base_date = self.from_date.unwrap_or_else(|| today);
-        let rid_string = self.generate_rid_string(base_date, rollover_hours);
-
-        let note_ids = self.fetch_reviewed_notes()?;
-
-        if note_ids.is_empty() {
-            let msg = match &self.config.mode {
-                AppMode::All => format!("No notes found in any deck for {}", base_date),
-                AppMode::Deck(deck_name) => format!(
-                    "No notes found in the deck '{}' for {}",
-                    deck_name, base_date
-                ),
-            };
+    // Set mode based on deck name presence
+    let mode = if let Some(ids) = note_ids {
+        AppMode::NoteIds(ids)
+    } else if let Some(pattern) = args.get_one::<String>("deck_pattern") {
+        AppMode::Pattern(pattern.clone())
+    } else if let Some(id) = args.get_one::<i64>("deck_id") {
+        AppMode::DeckId(*id)
+    } else if deck_names.is_empty() && args.get_flag("interactive") {
+        let conn = open_database_with_collation(&db_path.to_string_lossy())?;
+        let stdin = io::stdin();
+        pick_deck(&conn, &mut stdin.lock(), &mut io::stdout(), stdin.is_terminal())?
+    } else if deck_names.is_empty() && (all || all_by_default) {
+        AppMode::All
+    } else if deck_names.is_empty() {
+        return Err(AppError::Config(
+            "No deck given. Name a deck (or use --deck-pattern, --deck-id or -i), \
+             or pass --all to process every deck in the collection."
+                .to_string(),
+        ));
+    } else {
+        AppMode::Decks(deck_names)
+    };
+    Ok(mode)
+}
 
-            println!("{}", msg);
-        } else {
-            self.process_notes(note_ids, &rid_string)?;
+/// Reads the deck names for `--deck-stdin` from `input`, one per line and trimmed, skipping blank
+/// lines and `#` comments.
+fn read_deck_names(input: impl BufRead) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let name = line.trim();
+        if !name.is_empty() && !name.starts_with('#') {
+            names.push(name.to_string());
         }
-
-        log(self.config.verbose, "Processing completed.");
-        Ok(())
     }
+    Ok(names)
+}
 
-    fn get_rollover_hours(&self) -> Result<i64> {
-        log(self.config.verbose, "Querying rollover hours.");
-        let query = "SELECT val FROM config WHERE key = 'rollover';";
+/// The deck names piped in for `--deck-stdin`. Stdin is read once, so every collection of a
+/// `--collection-glob` run gets the same decks.
+fn stdin_deck_names() -> Result<Vec<String>, AppError> {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+    if let Some(names) = NAMES.get() {
+        return Ok(names.clone());
+    }
+    let names = read_deck_names(io::stdin().lock())
+        .map_err(|e| AppError::Config(format!("Cannot read deck names from stdin: {}", e)))?;
+    Ok(NAMES.get_or_init(|| names).clone())
+}
 
-        let conn = Connection::open(&self.db_path)?;
-        let mut stmt = conn.prepare(query)?;
+/// The notes given with `--note-ids` or in the `--note-ids-file`, or `None` when neither was
+/// given (or the subcommand has no such options).
+fn note_ids(args: &ArgMatches) -> Result<Option<Vec<i64>>, AppError> {
+    if let Ok(Some(ids)) = args.try_get_many::<i64>("note_ids") {
+        return Ok(Some(ids.copied().collect()));
+    }
+    let Ok(Some(file)) = args.try_get_one::<String>("note_ids_file") else {
+        return Ok(None);
+    };
+    let path = shellexpand::tilde(file).to_string();
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::Config(format!("Cannot read the note ids in '{}': {}", path, e)))?;
+    contents
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse::<i64>()
+                .map_err(|_| AppError::Config(format!("'{}' in '{}' is not a note id.", id, path)))
+        })
+        .collect::<Result<Vec<i64>, AppError>>()
+        .map(Some)
+}
 
-        // Retrieve the value as a BLOB
-        let raw_val: Vec<u8> = stmt.query_row([], |row| row.get(0))?;
+/// Whether to only simulate: with `--simulate`, or unless `--apply` is given, with `simulate =
+/// true` in the config file or in safe mode (`--safe-mode` or `safe_mode = true`).
+fn simulate(args: &ArgMatches, file_config: &config_file::FileConfig) -> bool {
+    if args.get_flag("simulate") {
+        return true;
+    }
+    (safe_mode(args, file_config) || file_config.simulate.unwrap_or(false)) && !args.get_flag("apply")
+}
 
-        // Interpret the BLOB as a UTF-8 encoded string of digits
-        let rollover_str = String::from_utf8(raw_val)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        log(self.config.verbose, &format!("Rollover string: {}", rollover_str));
+/// Whether safe mode is on, from `--safe-mode` or `safe_mode = true` in the config file.
+fn safe_mode(args: &ArgMatches, file_config: &config_file::FileConfig) -> bool {
+    args.get_flag("safe_mode") || file_config.safe_mode.unwrap_or(false)
+}
 
-        // Parse the string as an integer
-        rollover_str
-            .parse::<i64>()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Failed to parse rollover value: {}", e),
-                ),
-            )))
+/// Tells that safe mode kept a run from writing, unless `quiet`.
+fn print_safe_mode_notice(quiet: bool) {
+    if !quiet {
+        println!("Safe mode is on, so nothing is written. Pass --apply to make the changes.");
     }
+}
 
+/// The options that take a date.
+const DATE_OPTIONS: [&str; 3] = ["from", "to", "backfill"];
 
-    fn generate_rid_string(&self, date: NaiveDate, rollover_hours: i64) -> String {
-        let rollover_time = NaiveTime::from_hms_opt(rollover_hours as u32, 0, 0)
-            .expect("Invalid rollover hour");
+/// The date given to the option `id`, or `None` when it wasn't given (or the subcommand has no
+/// such option). Dates are parsed here rather than by clap, so that a mistyped one is reported
+/// with the option, the value and the accepted formats instead of clap's usage text.
+fn date_arg(args: &ArgMatches, id: &str) -> Result<Option<NaiveDate>, AppError> {
+    let Ok(Some(value)) = args.try_get_one::<String>(id) else {
+        return Ok(None);
+    };
+    date::parse_date_arg(&format!("--{}", id), value).map(Some).map_err(AppError::Config)
+}
 
-        // Combine the date and rollover time
-        let naive_rollover_datetime = date.and_time(rollover_time);
+/// Where backups go: `--backup-dir`, else the config file's `backup_dir`.
+fn backup_dir(args: &ArgMatches, file_config: &config_file::FileConfig) -> Option<PathBuf> {
+    args.get_one::<String>("backup_dir")
+        .or(file_config.backup_dir.as_ref())
+        .map(|dir| PathBuf::from(shellexpand::tilde(dir).to_string()))
+}
 
-        // Convert to local timezone using the system's timezone offset
-        let local_rollover_datetime = chrono::Local
-            .from_local_datetime(&naive_rollover_datetime)
-            .single()
-            .expect("Ambiguous or invalid local datetime");
+/// `undo`: reverses a previous run using its journal.
+fn run_undo(args: &ArgMatches, db_path: PathBuf, file_config: &config_file::FileConfig) -> Result<(), AppError> {
+    let config = AppConfig {
+        keep_backups: args.get_one::<usize>("keep_backups").copied(),
+        backup: !args.get_flag("no_backup"),
+        backup_dir: backup_dir(args, file_config),
+        json: args.get_flag("json"),
+        quiet: args.get_flag("quiet"),
+        force_schema: args.get_flag("force_schema"),
+        force: args.get_flag("force"),
+        ..Default::default()
+    };
+    let simulate = simulate(args, file_config);
+    if simulate && safe_mode(args, file_config) {
+        print_safe_mode_notice(config.json || config.quiet);
+    }
+
+    let journal_path = args.get_one::<String>("journal").expect("clap requires a journal");
+    let journal_path = PathBuf::from(shellexpand::tilde(journal_path).to_string());
+    let journal = journal::read_journal(&journal_path).map_err(|err| {
+        AppError::Config(format!("Cannot read journal '{}': {}", journal_path.display(), err))
+    })?;
+    let processor = AnkiProcessor::new(db_path, simulate, 0, None, None, &config);
+    let report = UndoReport { journal: journal_path.display().to_string(), ..processor.undo(&journal)? };
+    if config.json {
+        println!("{}", to_json(&report).expect("Failed to serialize report"));
+    }
+    Ok(())
+}
 
-        // Calculate start and end times
-        let start_time = local_rollover_datetime.timestamp_millis();
-        let end_time = start_time + 86_400_000; // Add 24 hours in milliseconds
+/// `report`: prints the reviews per day and the streak without changing anything.
+fn run_report(args: &ArgMatches, db_path: PathBuf, file_config: &config_file::FileConfig) -> Result<(), AppError> {
+    let config = AppConfig {
+        // A report changes nothing, so it covers every deck unless told otherwise
+        mode: deck_mode(args, file_config, &db_path, true)?,
+        json: args.get_flag("json"),
+        rollover: args.get_one::<NaiveTime>("rollover").copied().or(file_config.rollover),
+        timezone: args.get_one::<date::Zone>("timezone").copied().unwrap_or_default(),
+        exclude_subdecks: args.get_flag("exclude_subdecks"),
+        case_sensitive: args.get_flag("case_sensitive"),
+        ..Default::default()
+    };
 
-        format!("rid:{}:{}", start_time, end_time)
+    // Either date may be given alone; the range defaults to the last 30 days
+    let dates = [date_arg(args, "from")?, date_arg(args, "to")?];
+    let mut dates: Vec<NaiveDate> = dates.into_iter().flatten().collect();
+    let today = chrono::Local::now().date_naive();
+    dates.sort();
+    let (start, end) = match dates.as_slice() {
+        [] => (today - chrono::Duration::days(29), today),
+        [date] => (*date, today.max(*date)),
+        [start, end] => (*start, *end),
+        _ => unreachable!(),
+    };
+    let processor = AnkiProcessor::new(db_path, true, 0, None, None, &config);
+    let streak_report = processor.streak_report(start, end)?;
+    if args.get_flag("prune_empty_days") {
+        let empty = streak_report.empty_days(start, end);
+        if config.json {
+            println!("{}", to_json(&empty).expect("Failed to serialize report"));
+        } else {
+            empty.empty_days.iter().for_each(|date| println!("{}", date));
+        }
+    } else if config.json {
+        println!("{}", to_json(&streak_report).expect("Failed to serialize report"));
+    } else {
+        processor.print_streak_report(&streak_report);
     }
+    Ok(())
+}
 
-    /// Fetches matching deck names where the name contains the provided deck name.
-    /// Ensures that the parent deck is processed if it matches or has children.
-    fn fetch_matching_decks(&self) -> Result<Vec<String>> {
-        // Ensure this is only called in AppMode::Deck
-        let deck_name = match &self.config.mode {
-            AppMode::Deck(name) => name,
-            AppMode::All => {
-                return Err(rusqlite::Error::InvalidQuery); // Protect against misuse
-            }
-        };
+/// `fix`: moves reviews back to an earlier day and returns the report of the run, or does one of
+/// the operations that share its arguments (`--resolve-deck`, `--backfill`, `--count-only`) and
+/// returns `None`.
+fn run_fix(
+    args: &ArgMatches,
+    db_path: PathBuf,
+    file_config: &config_file::FileConfig,
+    state_path: Option<&Path>,
+) -> Result<Option<RunReport>, AppError> {
+    let simulate = simulate(args, file_config);
+    // `--resolve-deck` only looks up the name it is given
+    let mode = deck_mode(args, file_config, &db_path, args.contains_id("resolve_deck"))?;
+
+    // Optional clamp for the answer duration of shifted reviews
+    let cap_review_time = args.get_one::<i64>("cap_review_time").copied();
+
+    // Optional backup retention
+    let keep_backups = args.get_one::<usize>("keep_backups").copied();
+
+    // How --limit picks notes, optionally seeded for reproducible sampling
+    let limit_order = match args.get_one::<String>("limit_order").map(|s| s.as_str()) {
+        Some("random") => LimitOrder::Random,
+        Some("recent") => LimitOrder::Recent,
+        Some("oldest") => LimitOrder::Oldest,
+        _ => LimitOrder::NoteId,
+    };
+    let limit_scope = match args.get_one::<String>("limit_scope").map(|s| s.as_str()) {
+        Some("per-deck") => LimitScope::PerDeck,
+        _ => LimitScope::Global,
+    };
+    let seed = args.get_one::<u64>("seed").copied();
 
-        log(
-            self.config.verbose,
-            &format!("Fetching matching deck names for '{}'", deck_name),
-        );
+    let mod_time = match args.get_one::<String>("mod_time").map(|s| s.as_str()) {
+        Some("review") => ModTime::Review,
+        Some("keep") => ModTime::Keep,
+        _ => ModTime::Now,
+    };
 
-        // SQL query to fetch decks that match or are children of the provided name
-        let query = "
-        SELECT name
-        FROM decks
-        WHERE name COLLATE unicase = ?1
-        OR name COLLATE unicase LIKE ?2 || '::%'
-        ORDER BY name COLLATE unicase;
-    ";
-
-        // Open the database and register the `unicase` collation
-        let conn = open_database_with_collation(self.db_path.to_str().unwrap())?;
-        let mut stmt = conn.prepare(query)?;
-
-        let matching_decks = stmt
-            .query_map(
-                params![deck_name, deck_name],
-                |row| row.get::<_, String>(0),
-            )?
-            .collect::<Result<Vec<String>, _>>()?;
-
-        if matching_decks.is_empty() {
-            log(
-                self.config.verbose,
-                &format!("No decks found matching or under '{}'", deck_name),
-            );
-            return Err(rusqlite::Error::InvalidQuery);
-        }
+    let newest_only = args.get_one::<String>("newest_only").map(|s| match s.as_str() {
+        "delete" => OlderReviews::Delete,
+        _ => OlderReviews::Leave,
+    });
+
+    // `revlog.type` of each kind of review `--review-types` names
+    let review_types = args.get_many::<String>("review_types").map(|names| {
+        let mut types: Vec<i64> = names
+            .map(|name| match name.as_str() {
+                "learn" => 0,
+                "review" => 1,
+                "relearn" => 2,
+                _ => 3, // cram
+            })
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+        types
+    });
 
-        log(
-            self.config.verbose,
-            &match matching_decks.len() {
-                1 => format!("Single matching deck found: '{}'", matching_decks[0]),
-                _ => format!(
-                    "Parent deck '{}' contains the following child decks:\n{}",
-                    deck_name,
-                    matching_decks
-                        .iter()
-                        .map(|d| replace_deck_delimiter(d))
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                ),
-            },
-        );
+    // Create global config
+    let config = AppConfig {
+        mode,
+        cap_review_time,
+        mod_time,
+        csv: args.get_one::<String>("csv").map(|path| PathBuf::from(shellexpand::tilde(path).to_string())),
+        keep_backups,
+        limit_order,
+        limit_scope,
+        seed,
+        // Backups are on unless explicitly disabled
+        backup: !args.get_flag("no_backup"),
+        backup_dir: backup_dir(args, file_config),
+        compact: args.get_flag("compact"),
+        check_landing: !args.get_flag("no_landing_check"),
+        verify: args.get_flag("verify"),
+        strict: args.get_flag("strict"),
+        json: args.get_flag("json"),
+        quiet: args.get_flag("quiet"),
+        rollover: args.get_one::<NaiveTime>("rollover").copied().or(file_config.rollover),
+        timezone: args.get_one::<date::Zone>("timezone").copied().unwrap_or_default(),
+        force: args.get_flag("force"),
+        force_schema: args.get_flag("force_schema"),
+        exclude_subdecks: args.get_flag("exclude_subdecks"),
+        case_sensitive: args.get_flag("case_sensitive"),
+        tag: args.get_one::<String>("tag").cloned(),
+        note_type: args.get_one::<String>("note_type").cloned(),
+        review_types,
+        reschedule: args.get_flag("reschedule"),
+        by_card: args.get_flag("by_card"),
+        newest_only,
+    };
 
-        Ok(matching_decks)
+    if let Some(date) = date_arg(args, "backfill")? {
+        // Adding history that never happened is never done without an explicit --yes
+        if !simulate && !args.get_flag("yes") {
+            return Err(AppError::Config(
+                "--backfill adds a review that never happened to the collection. Pass --yes to confirm, \
+                 or --simulate to preview it."
+                    .to_string(),
+            ));
+        }
+        if let Err(err) = validate_dates(Some(date), None, chrono::Local::now().date_naive()) {
+            return Err(AppError::Config(err));
+        }
+        let mut processor = AnkiProcessor::new(db_path, simulate, 0, None, None, &config);
+        redirect_to_target(args, &mut processor)?;
+        processor.backfill(date)?;
+        return Ok(None);
     }
 
-    fn fetch_reviewed_notes(&self) -> Result<Vec<i64>> {
-        log(self.config.verbose, "Fetching reviewed notes...");
+    if let Some(name) = args.get_one::<String>("resolve_deck") {
+        let processor = AnkiProcessor::new(db_path, true, 0, None, None, &config);
+        processor.resolve_deck(name)?;
+        return Ok(None);
+    }
 
-        let conn = open_database_with_collation(self.db_path.to_str().unwrap())?;
+    // `--count-only` prints nothing but the number of notes
+    let count_only = args.get_flag("count_only");
+    if simulate && safe_mode(args, file_config) {
+        print_safe_mode_notice(config.json || config.quiet || count_only);
+    }
 
-        // Ensure we have a valid `from_date` to work with
-        let from_date = match self.from_date {
-            Some(date) => date,
-            None => {
-                return Err(rusqlite::Error::InvalidQuery); // `--from` date is required
-            }
-        };
+    // Deleting reviews is never done without an explicit --yes, as with --backfill
+    if newest_only == Some(OlderReviews::Delete) && !simulate && !count_only && !args.get_flag("yes") {
+        return Err(AppError::Config(
+            "--newest-only delete deletes reviews from the collection. Pass --yes to confirm, \
+             or --simulate to preview it."
+                .to_string(),
+        ));
+    }
 
-        log(
-            self.config.verbose,
-            &format!("Fetching notes reviewed on: {}", from_date),
-        );
+    // Allow user to optionally limit the number of cards moved to previous day
+    let limit: i64 = args.get_one::<String>("limit").unwrap_or(&"0".to_string()).parse().unwrap_or(0);
 
-        // Convert `from_date` to a timestamp range
-        let from_timestamp_start = from_date
-            .and_hms_opt(0, 0, 0)
-            .unwrap()
-            .and_utc()
-            .timestamp();
-        let from_timestamp_end = from_timestamp_start + 86_400; // Add 24 hours to get the next day
-
-        // Query logic based on mode
-        let query = match &self.config.mode {
-            AppMode::All => {
-                log(self.config.verbose, "Mode: All decks");
-                // Return a query that doesn't limit by deck
-                "
-            SELECT DISTINCT notes.id
-            FROM cards
-            JOIN notes ON cards.nid = notes.id
-            JOIN revlog ON cards.id = revlog.cid
-            WHERE revlog.id / 1000 BETWEEN ?1 AND ?2
-            ORDER BY notes.id;
-            "
-            }
-            AppMode::Deck(_) => {
-                // Fetch the parent deck and its hierarchy
-                let matching_decks = self.fetch_matching_decks()?;
-                let parent_deck = &matching_decks[0]; // Assume first is parent
-
-                log(
-                    self.config.verbose,
-                    &format!(
-                        "Processing parent deck '{}'{}",
-                        parent_deck,
-                        if matching_decks.len() > 1 {
-                            format!(
-                                " with children:\n{}",
-                                matching_decks[1..]
-                                    .iter()
-                                    .map(|d| replace_deck_delimiter(d))
-                                    .collect::<Vec<_>>()
-                                    .join("\n")
-                            )
-                        } else {
-                            "".to_string()
-                        }
-                    ),
-                );
-
-                "
-            SELECT DISTINCT notes.id
-            FROM cards
-            JOIN notes ON cards.nid = notes.id
-            JOIN decks ON cards.did = decks.id
-            JOIN revlog ON cards.id = revlog.cid
-            WHERE decks.name COLLATE unicase = ?3
-            AND revlog.id / 1000 BETWEEN ?1 AND ?2
-            ORDER BY notes.id;
-            "
+    // User may have specified from/to dates
+    let mut from_date = date_arg(args, "from")?;
+    let mut to_date = date_arg(args, "to")?;
+    // Or the shorthand: the last `--days` days, moved back by `--shift` days
+    let mut days = args.get_one::<i64>("days").copied();
+    let shift = args.get_one::<i64>("shift").copied();
+    // Or every day since the last successful run
+    if args.get_flag("since_last_run") {
+        let state_path = state_path.ok_or_else(|| {
+            AppError::Config("Cannot locate the state file for --since-last-run: HOME is not set.".to_string())
+        })?;
+        match state::load(state_path).map_err(AppError::Config)? {
+            Some(state) => {
+                let last_run = state.last_run_date();
+                let today = chrono::Local::now().date_naive();
+                days = Some((today - last_run).num_days().max(0));
+                if !config.json && !config.quiet && !count_only {
+                    println!("Last run on {}; moving the reviews since then.", last_run);
+                }
             }
-        };
-
-        // Prepare and execute the query
-        let mut stmt = conn.prepare(query)?;
-
-        let notes = match &self.config.mode {
-            AppMode::All => stmt
-                .query_map(params![from_timestamp_start, from_timestamp_end], |row| row.get(0))?
-                .collect::<Result<Vec<i64>, _>>()?,
-            AppMode::Deck(_) => {
-                let matching_decks = self.fetch_matching_decks()?;
-                let parent_deck = &matching_decks[0]; // Use parent deck
-                stmt.query_map(
-                    params![from_timestamp_start, from_timestamp_end, parent_deck],
-                    |row| row.get(0),
-                )?
-                    .collect::<Result<Vec<i64>, _>>()?
+            None => {
+                if !config.json && !config.quiet && !count_only {
+                    println!("No previous run recorded; moving today's reviews back to yesterday.");
+                }
             }
-        };
+        }
+    }
+    // `--shift` on its own, without a `--from` day, applies to today
+    if days.is_some() || (shift.is_some() && from_date.is_none()) {
+        let today = chrono::Local::now().date_naive();
+        from_date = Some(today);
+        to_date = Some(today - chrono::Duration::days(shift.unwrap_or(1)));
+    }
+    // A lone --from is a single day, but --to needs a --from day to move reviews from
+    if from_date.is_none() && to_date.is_some() {
+        return Err(AppError::Config("If --to is specified, --from must also be specified.".to_string()));
+    }
 
-        // Apply limit if specified
-        let limited_notes = if self.limit > 0 {
-            notes.into_iter().take(self.limit as usize).collect()
-        } else {
-            notes
-        };
+    let today = chrono::Local::now().date_naive(); // Use current date
+    // Without dates, move today's reviews back to yesterday; a lone --from moves back by --shift
+    let (from_date, to_date) = date::resolve_date_range(from_date, to_date, today, shift.unwrap_or(1));
+    validate_dates(from_date, to_date, today).map_err(AppError::Config)?;
+    // Checked before the collection is opened, so a mistyped date never touches it
+    if let (Some(from), Some(to)) = (from_date, to_date) {
+        let max_days = args.get_one::<i64>("max_offset_days").copied().unwrap_or(date::DEFAULT_MAX_OFFSET_DAYS);
+        date::check_max_offset(from, to, max_days).map_err(AppError::Config)?;
+    }
+    if !config.json && !config.quiet && !count_only {
+        println!("Dates are valid.");
+    }
 
-        Ok(limited_notes)
+    let mut processor = AnkiProcessor::new(
+        db_path,
+        simulate,
+        limit,
+        from_date,
+        to_date,
+        &config
+    );
+    if let Some(days) = days {
+        processor.set_span_days(days + 1);
+    }
+    if count_only {
+        println!("{}", processor.count_notes()?);
+        return Ok(None);
+    }
+    if !args.get_flag("yes") {
+        processor.require_confirmation();
     }
 
-    fn process_notes(&self, notes: Vec<i64>, rid_string: &str) -> Result<()> {
-        log(
-            self.config.verbose,
-            &format!("Processing {} notes...", notes.len()),
-        );
+    redirect_to_target(args, &mut processor)?;
 
-        let start_time: i64 = rid_string.split(':').nth(1).unwrap().parse().unwrap();
-        let end_time: i64 = rid_string.split(':').nth(2).unwrap().parse().unwrap();
+    Ok(Some(processor.process()?))
+}
 
-        // Calculate the actual ID offset using your utility functions
-        let id_offset = if let (Some(from), Some(to)) = (self.from_date, self.to_date) {
-            let days_difference = date::days_between(to, from);
-            date::calculate_id_offset(days_difference)
-        } else {
-            date::calculate_id_offset(1) // Default 1-day offset if dates are not provided
-        };
+/// Applies `--target-collection`, so the processor writes to a copy instead of the source.
+fn redirect_to_target(args: &ArgMatches, processor: &mut AnkiProcessor) -> Result<(), AppError> {
+    if let Some(target) = args.get_one::<String>("target_collection") {
+        let target = PathBuf::from(shellexpand::tilde(target).to_string());
+        // `--force` also lets an existing target be replaced
+        let overwrite = args.get_flag("overwrite") || args.get_flag("force");
+        processor.redirect_to_target(&target, overwrite)?;
+    }
+    Ok(())
+}
 
-        let conn = Connection::open(&self.db_path)?;
-
-        // Prepare queries
-        let update_revlog_query = "
-        UPDATE revlog
-        SET id = id - ?
-        WHERE id IN (
-            SELECT r.id
-            FROM revlog r
-            INNER JOIN cards c ON r.cid = c.id
-            INNER JOIN notes n ON n.id = c.nid
-            WHERE n.id = ?
-            AND r.id >= ?
-            AND r.id < ?
-        )
-        RETURNING cid;
-    ";
-
-        let update_cards_query = "
-            UPDATE cards
-            SET mod = ?, usn = -1
-            WHERE id = ?;
-        ";
-
-        let mut affected_cards = Vec::new();
-        let current_time = chrono::Utc::now().timestamp();
-
-        for note_id in &notes {
-            let mut stmt = conn.prepare(update_revlog_query)?;
-
-            // Collect affected card IDs for the current note
-            let note_cards = stmt
-                .query_map(params![id_offset, note_id, start_time, end_time], |row| {
-                    row.get::<_, i64>(0) // Extract the card ID
-                })?
-                .collect::<Result<Vec<i64>, _>>()?;
-
-            // Clone note_cards before extending
-            affected_cards.extend(note_cards.clone());
-
-            if self.simulate {
-                println!(
-                    "Simulating update for note {} (from {} to {}), moving back {} days.",
-                    note_id,
-                    start_time,
-                    end_time,
-                    id_offset / 86_400_000 // Convert offset back to days for display
-                );
-            } else {
-                // Update the cards table for affected cards
-                for cid in &note_cards {
-                    conn.execute(update_cards_query, params![current_time, cid])?;
+/// `fix --collection-glob`: runs the fix on every profile whose name matches `pattern`, one
+/// collection after another. A collection that fails is reported and the others still run.
+fn run_fix_glob(
+    args: &ArgMatches,
+    pattern: &str,
+    file_config: &config_file::FileConfig,
+    state_path: Option<&Path>,
+) -> Result<(), AppError> {
+    let base_dir = AnkiCollection::profiles_dir();
+    let names = AnkiCollection::matching(&base_dir, pattern)
+        .map_err(|err| AppError::Config(format!("Cannot read '{}': {}", base_dir.display(), err)))?;
+    if names.is_empty() {
+        return Err(AppError::Config(format!(
+            "No profile in '{}' matches '{}'.",
+            base_dir.display(),
+            pattern
+        )));
+    }
+    let json = args.get_flag("json");
+    let quiet = args.get_flag("quiet");
+
+    let mut reports = Vec::new();
+    let mut failures = Vec::new();
+    for name in names {
+        if !json && !quiet {
+            println!("\n== {} ==", name);
+        }
+        // Each collection is processed in its own transaction, so a failure leaves the rest alone
+        match run_fix(args, AnkiCollection::new(&name).collection_path(), file_config, state_path) {
+            Ok(Some(report)) => {
+                if !json && !quiet {
+                    print_run_summary(&report);
                 }
-                println!("Note date updated successfully for {}.", note_id);
-
-                log(self.config.verbose, "Will trigger full database sync criterion.");
-                let force_sync_query = "
-                    UPDATE col SET scm = scm + 1;
-                ";
-                conn.execute(force_sync_query, [])?;
+                reports.push(report);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("{} {}: {}", red_text("[ERROR]"), name, err);
+                failures.push(CollectionFailure { collection: name, error: err.to_string() });
             }
         }
+    }
 
-        log(
-            self.config.verbose,
-            &format!("Marked {} cards as needing sync.", affected_cards.len()),
+    let report = MultiRunReport::new(reports, failures);
+    if report.collections.iter().any(|run| !run.simulate) {
+        record_run(state_path);
+    }
+    if json {
+        println!("{}", to_json(&report).expect("Failed to serialize report"));
+    } else if !report.collections.is_empty() {
+        let simulate = report.collections.iter().all(|run| run.simulate);
+        println!(
+            "{}All {} collections: {}",
+            if quiet { "" } else { "\n" },
+            report.collections.len(),
+            report.summary.message(simulate)
         );
-
-        Ok(())
     }
-
+    if !report.failures.is_empty() {
+        eprintln!("{} {} collections failed.", red_text("[ERROR]"), report.failures.len());
+        std::process::exit(1);
+    }
+    if report.collections.iter().any(|run| run.verified == Some(false)) {
+        return Err(AppError::VerificationFailed);
+    }
+    if report.summary.notes == 0 && !report.collections.is_empty() {
+        std::process::exit(EXIT_NO_NOTES);
+    }
+    Ok(())
 }
 
-fn get_clap_matches() -> ArgMatches {
-    Command::new(APP_NAME)
-        .version(APP_VERSION)
-        .about("Processes Anki notes based on deck and collection.")
-        .arg(
-            Arg::new("deck_name")
-                .help("Name of the deck to process.")
-                //.required(true)
-                .index(1),
-        )
-        .arg(
-            Arg::new("collection")
-                .help("Name of the Anki collection.")
-                .short('c')
-                .long("collection")
-                .value_name("COLLECTION"),
-        )
-        .arg(
-            Arg::new("simulate")
-                .help("Simulate the changes without applying them.")
-                .short('s')
-                .long("simulate")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("limit")
-                .help("Limit the number of cards moved to previous day.")
-                .short('l')
-                .long("limit")
-                .value_name("LIMIT"),
-        )
-        .arg(
-            Arg::new("from")
-                .help("Start date (format: YYYY-MM-DD or YYYYMMDD)")
-                .long("from")
-                .value_name("FROM_DATE")
-                .value_parser(|s: &str| parse_date(s)),
-        )
-        .arg(
-            Arg::new("to")
-                .help("End date (format: YYYY-MM-DD or YYYYMMDD)")
-                .long("to")
-                .value_name("TO_DATE")
-                .value_parser(|s: &str| parse_date(s)),
-        )
-        .arg(
-            Arg::new("verbose")
-                .help("Emit verbose logging")
-                .short('v')
-                .long("verbose")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches()
+/// Prints the per-deck table and totals of one collection's run.
+fn print_run_summary(report: &RunReport) {
+    let rows: Vec<SummaryRow> = report
+        .by_deck
+        .iter()
+        .map(|(deck, counts)| SummaryRow { deck: deck.clone(), notes: counts.notes, cards: counts.cards })
+        .collect();
+    utils::print_summary(&rows, &report.summary.message(report.simulate), report.skipped_reviews);
 }
 
-fn main() -> Result<()> {
-    let matches = get_clap_matches();
-
-    // Optional deck name
-    let deck_name = matches.get_one::<String>("deck_name").map(|s| s.as_str());
-    // Required collection name
-    let collection_name = matches.get_one::<String>("collection").unwrap().as_str();
+/// Records a real run in the state file, for `--since-last-run`.
+fn record_run(state_path: Option<&Path>) {
+    let Some(state_path) = state_path else {
+        return;
+    };
+    let state = state::State { last_run: chrono::Utc::now() };
+    if let Err(err) = state::save(state_path, &state) {
+        eprintln!("Warning: could not record this run in '{}': {}", state_path.display(), err);
+    }
+}
 
-    let simulate = matches.get_flag("simulate");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let verbose = matches.get_flag("verbose");
+    #[test]
+    fn test_all_decks_need_all_flag() {
+        let file_config = config_file::FileConfig::default();
+        let mode = |args: &[&str], all_by_default: bool| {
+            let matches = cli::command().try_get_matches_from(args).unwrap();
+            let (_, args) = matches.subcommand().unwrap();
+            deck_mode(args, &file_config, Path::new("collection.anki2"), all_by_default)
+        };
 
-    // Set mode based on deck name presence
-    let mode = match deck_name {
-        Some(name) => AppMode::Deck(name.to_string()),
-        None => AppMode::All,
-    };
+        let err = mode(&["asf", "fix", "-c", "User 1"], false).unwrap_err();
+        assert!(matches!(err, AppError::Config(_)));
+        assert!(err.to_string().contains("pass --all"));
+        assert_eq!(mode(&["asf", "fix", "-c", "User 1", "--all"], false).unwrap(), AppMode::All);
+        assert_eq!(
+            mode(&["asf", "fix", "Spanish", "-c", "User 1"], false).unwrap(),
+            AppMode::Decks(vec!["Spanish".to_string()])
+        );
+        assert_eq!(mode(&["asf", "report", "-c", "User 1"], true).unwrap(), AppMode::All);
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "Spanish", "--all"]).is_err());
+
+        // A deck from the config file counts as naming one, unless --all overrides it
+        let file_config = config_file::FileConfig { deck: Some("French".to_string()), ..Default::default() };
+        let matches = cli::command().try_get_matches_from(["asf", "fix", "--all"]).unwrap();
+        let (_, args) = matches.subcommand().unwrap();
+        assert_eq!(deck_mode(args, &file_config, Path::new("collection.anki2"), false).unwrap(), AppMode::All);
+        let matches = cli::command().try_get_matches_from(["asf", "fix"]).unwrap();
+        let (_, args) = matches.subcommand().unwrap();
+        assert_eq!(
+            deck_mode(args, &file_config, Path::new("collection.anki2"), false).unwrap(),
+            AppMode::Decks(vec!["French".to_string()])
+        );
+    }
 
-    // Create global config
-    let config = AppConfig { verbose, mode };
+    #[test]
+    fn test_note_ids_from_flag_and_file() {
+        let file_config = config_file::FileConfig { deck: Some("French".to_string()), ..Default::default() };
+        let mode = |args: &[&str]| {
+            let matches = cli::command().try_get_matches_from(args).unwrap();
+            let (_, args) = matches.subcommand().unwrap();
+            deck_mode(args, &file_config, Path::new("collection.anki2"), false)
+        };
 
-    log(config.verbose, "Application started.");
+        assert_eq!(mode(&["asf", "fix", "--note-ids", "3,1"]).unwrap(), AppMode::NoteIds(vec![3, 1]));
 
-    // Allow user to optionally limit the number of cards moved to previous day
-    let limit: i64 = matches.get_one::<String>("limit").unwrap_or(&"0".to_string()).parse().unwrap_or(0);
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("ids.txt");
+        std::fs::write(&file, "1700000000001, 1700000000002\n1700000000003\n").unwrap();
+        assert_eq!(
+            mode(&["asf", "fix", "--note-ids-file", file.to_str().unwrap()]).unwrap(),
+            AppMode::NoteIds(vec![1700000000001, 1700000000002, 1700000000003])
+        );
+        std::fs::write(&file, "12 abc").unwrap();
+        let err = mode(&["asf", "fix", "--note-ids-file", file.to_str().unwrap()]).unwrap_err();
+        assert!(err.to_string().contains("'abc'"), "unexpected error: {}", err);
 
-    // User may have specified from/to dates
-    let from_date: Option<NaiveDate> = matches.get_one("from").copied();
-    let to_date: Option<NaiveDate> = matches.get_one("to").copied();
-    // Check that either both dates are provided or neither is provided
-    match (from_date, to_date) {
-        (Some(_), None) => {
-            eprintln!("Error: If --from is specified, --to must also be specified");
-            std::process::exit(1);
-        },
-        (None, Some(_)) => {
-            eprintln!("Error: If --to is specified, --from must also be specified");
-            std::process::exit(1);
-        },
-        _ => () // Both Some or both None is fine
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "Spanish", "--note-ids", "1"]).is_err());
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "--all", "--note-ids", "1"]).is_err());
     }
 
-    let today = chrono::Local::now().date_naive(); // Use current date
-    if let Err(err) = validate_dates(from_date, to_date, today) {
-        eprintln!("\x1b[31m[ERROR]\x1b[0m {}", err); // Print the error in red
-        std::process::exit(1); // Exit with an error code
+    #[test]
+    fn test_safe_mode_simulates_unless_applied() {
+        let simulates = |args: &[&str], file_config: &config_file::FileConfig| {
+            let matches = cli::command().try_get_matches_from(args).unwrap();
+            let (_, args) = matches.subcommand().unwrap();
+            simulate(args, file_config)
+        };
+        let plain = config_file::FileConfig::default();
+        let safe = config_file::FileConfig { safe_mode: Some(true), ..Default::default() };
+        let simulating = config_file::FileConfig { simulate: Some(true), ..Default::default() };
+
+        // Without safe mode nothing changes
+        assert!(!simulates(&["asf", "fix", "--all"], &plain));
+        assert!(simulates(&["asf", "fix", "--all", "-s"], &plain));
+        assert!(!simulates(&["asf", "fix", "--all", "--apply"], &plain));
+
+        // In safe mode only --apply (or --no-simulate) writes; --yes alone doesn't
+        assert!(simulates(&["asf", "fix", "--all", "--safe-mode"], &plain));
+        assert!(simulates(&["asf", "fix", "--all", "--yes"], &safe));
+        assert!(simulates(&["asf", "fix", "--all", "-s"], &safe));
+        assert!(!simulates(&["asf", "fix", "--all", "--apply"], &safe));
+        assert!(!simulates(&["asf", "fix", "--all", "--no-simulate", "--yes"], &safe));
+        assert!(!simulates(&["asf", "undo", "journal.json", "--safe-mode", "--apply"], &plain));
+        assert!(simulates(&["asf", "undo", "journal.json"], &safe));
+
+        // --apply also overrides `simulate = true`, but can't be combined with --simulate
+        assert!(!simulates(&["asf", "fix", "--all", "--apply"], &simulating));
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "--all", "-s", "--apply"]).is_err());
     }
 
-    let processor = AnkiProcessor::new(
-        collection_name,
-        simulate,
-        limit,
-        from_date,
-        to_date,
-        &config
-    );
-    processor.process()
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::NaiveDate;
-
     #[test]
-    fn test_generate_rid_string() {
-        let config = AppConfig{verbose:true, mode:AppMode::All};
-        let processor = AnkiProcessor::new("test_collection", true, 1, None, None, &config);
-        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
-        let rid_string = processor.generate_rid_string(date, 1);
-
-        assert!(rid_string.starts_with("rid:"));
-        let parts: Vec<&str> = rid_string.split(':').collect();
-        assert_eq!(parts.len(), 3);
-        assert_eq!(parts[1], "1735711200000"); // Expected timestamp for 2025-01-01 01:00:00 local time
-        assert_eq!(parts[2], "1735797600000");  // Expected timestamp for 2025-01-02 01:00:00 local
+    fn test_deck_names_read_from_stdin() {
+        let input = io::Cursor::new("Spanish::Verbs\n\n  # French is done\n  Русский \r\nGerman # not a comment\n");
+        assert_eq!(
+            read_deck_names(input).unwrap(),
+            vec!["Spanish::Verbs".to_string(), "Русский".to_string(), "German # not a comment".to_string()]
+        );
+        assert!(read_deck_names(io::Cursor::new("\n# nothing\n")).unwrap().is_empty());
 
-        let date2 = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
-        let rid_string2 = processor.generate_rid_string(date2, 1);
-        assert_eq!(rid_string2, "rid:1735711200000:1735797600000");
+        // Stdin replaces naming the decks, so the two can't be combined
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "Spanish", "--deck-stdin"]).is_err());
+        assert!(cli::command().try_get_matches_from(["asf", "fix", "--all", "--deck-stdin"]).is_err());
+        assert!(cli::command().try_get_matches_from(["asf", "report", "--deck-stdin"]).is_ok());
     }
 
     #[test]
-    fn test_collection_path() {
-        let collection = AnkiCollection::new("test_collection");
-        let path = collection.collection_path();
+    fn test_date_errors_name_the_option() {
+        let date = |args: &[&str], id: &str| {
+            let matches = cli::command().try_get_matches_from(args).unwrap();
+            let (_, args) = matches.subcommand().unwrap();
+            date_arg(args, id)
+        };
 
-        assert!(path.to_str().unwrap().contains("test_collection"));
-        assert!(path.to_str().unwrap().ends_with("collection.anki2"));
+        let err = date(&["asf", "fix", "--all", "--from", "2025-01-03", "--to", "2025-13-01"], "to").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("--to '2025-13-01'"), "unexpected error: {}", message);
+        assert!(message.contains("YYYY-MM-DD") && message.contains("yesterday"), "unexpected error: {}", message);
+        let err = date(&["asf", "report", "--from", "03/01/2025"], "from").unwrap_err();
+        assert!(err.to_string().contains("--from '03/01/2025'"), "unexpected error: {}", err);
+
+        assert_eq!(
+            date(&["asf", "fix", "--all", "--from", "20250103"], "from").unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 3)
+        );
+        assert_eq!(date(&["asf", "fix", "--all"], "from").unwrap(), None);
+        assert_eq!(date(&["asf", "undo", "journal.json"], "from").unwrap(), None);
     }
 }
@@ -0,0 +1,1131 @@
+//! The SQL this tool runs against a collection.
+//!
+//! Every function takes the connection to run on and returns plain data, leaving output and the
+//! choice of what to run to `AnkiProcessor`. Functions that run once per note use cached
+//! statements, so calling them in a loop prepares each query only once.
+//!
+//! The queries that select and change reviews log their SQL and bound parameters at trace level
+//! (`-vv`), so a failing query can be copied out of the log and re-run by hand.
+
+use crate::date;
+use crate::error::AppError;
+use crate::journal::DeletedReview;
+use crate::table_exists;
+use crate::utils::{replace_deck_delimiter, to_internal_deck_name};
+use chrono::{Local, NaiveTime};
+use log::trace;
+use rusqlite::types::{ToSqlOutput, Value};
+use rusqlite::{params, Connection, OptionalExtension, Result, ToSql};
+
+/// Logs `sql` and its bound parameters at trace level.
+fn trace_sql(sql: &str, params: &[&dyn ToSql]) {
+    if !log::log_enabled!(log::Level::Trace) {
+        return;
+    }
+    let params = params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| format!("?{} = {}", i + 1, describe_param(*param)))
+        .collect::<Vec<_>>();
+    trace!(
+        "SQL: {}\n  with {}",
+        sql.trim(),
+        if params.is_empty() { "no parameters".to_string() } else { params.join(", ") }
+    );
+}
+
+/// Renders a bound parameter for the log. Integers in the range of millisecond or second
+/// timestamps (revlog, card and note ids are creation times) get their local time alongside,
+/// and text is shown with `::` deck separators.
+fn describe_param(param: &dyn ToSql) -> String {
+    let value = match param.to_sql() {
+        Ok(ToSqlOutput::Borrowed(value)) => Value::from(value),
+        Ok(ToSqlOutput::Owned(value)) => value,
+        Ok(_) => return "<blob>".to_string(),
+        Err(err) => return format!("<{}>", err),
+    };
+    match value {
+        Value::Integer(ms) if (1_000_000_000_000..10_000_000_000_000).contains(&ms) => {
+            format!("{} ({})", ms, date::format_review_time(&Local, ms))
+        }
+        Value::Integer(secs) if (1_000_000_000..10_000_000_000).contains(&secs) => {
+            format!("{} ({})", secs, date::format_review_time(&Local, secs * 1000))
+        }
+        Value::Integer(value) => value.to_string(),
+        Value::Text(text) => format!("'{}'", replace_deck_delimiter(&text)),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Reads the `rollover` key from the `config` table of current collections.
+///
+/// Returns `None` when the table or the key is missing, and an error when the value is neither
+/// an hour nor an hour and minutes (`4:30`).
+pub fn rollover_from_config_table(conn: &Connection) -> Result<Option<NaiveTime>, AppError> {
+    if !table_exists(conn, "config")? {
+        return Ok(None);
+    }
+
+    // Retrieve the value as a BLOB
+    let raw_val: Option<Vec<u8>> = conn
+        .query_row("SELECT val FROM config WHERE key = 'rollover';", [], |row| row.get(0))
+        .optional()?;
+    let Some(raw_val) = raw_val else {
+        return Ok(None);
+    };
+
+    // Interpret the BLOB as a UTF-8 encoded string such as `4` or `4:30`
+    let rollover_str = String::from_utf8(raw_val).map_err(|e| invalid_setting("rollover", e))?;
+    date::parse_rollover(&rollover_str).map(Some).map_err(|e| invalid_setting("rollover", e))
+}
+
+fn invalid_setting(key: &str, err: impl ToString) -> AppError {
+    AppError::InvalidSetting { key: key.to_string(), message: err.to_string() }
+}
+
+/// Reads `rollover` from the JSON preferences older collections keep in `col.conf`, where it is
+/// a whole hour.
+pub fn rollover_from_col_conf(conn: &Connection) -> Result<Option<NaiveTime>, AppError> {
+    if !crate::table_has_column(conn, "col", "conf")? {
+        return Ok(None);
+    }
+
+    let conf: String = conn.query_row("SELECT conf FROM col;", [], |row| row.get(0))?;
+    let conf: serde_json::Value = serde_json::from_str(&conf).map_err(|e| invalid_setting("col.conf", e))?;
+    conf.get("rollover")
+        .and_then(|value| value.as_i64())
+        .map(|hour| date::rollover_from_hour(hour).map_err(|e| invalid_setting("rollover", e)))
+        .transpose()
+}
+
+/// Reads the creation offset (minutes west of UTC) from the `config` table, or from `col.conf`
+/// in older collections. Collections that still use Anki's legacy day counting have none.
+pub fn creation_offset(conn: &Connection) -> Result<Option<i64>, AppError> {
+    if table_exists(conn, "config")? {
+        let raw_val: Option<Vec<u8>> = conn
+            .query_row("SELECT val FROM config WHERE key = 'creationOffset';", [], |row| row.get(0))
+            .optional()?;
+        if let Some(raw_val) = raw_val {
+            // Stored as JSON, so `null` when the collection was switched back to legacy counting
+            let value: serde_json::Value =
+                serde_json::from_slice(&raw_val).map_err(|e| invalid_setting("creationOffset", e))?;
+            return Ok(value.as_i64());
+        }
+    }
+    if !crate::table_has_column(conn, "col", "conf")? {
+        return Ok(None);
+    }
+
+    let conf: String = conn.query_row("SELECT conf FROM col;", [], |row| row.get(0))?;
+    let conf: serde_json::Value = serde_json::from_str(&conf).map_err(|e| invalid_setting("col.conf", e))?;
+    Ok(conf.get("creationOffset").and_then(|value| value.as_i64()))
+}
+
+/// Reads the scheduler version (`schedVer`) from the `config` table, or from `col.conf` in older
+/// collections. Anki treats a collection without one as being on the v1 scheduler.
+pub fn scheduler_version(conn: &Connection) -> Result<i64, AppError> {
+    if table_exists(conn, "config")? {
+        let raw_val: Option<Vec<u8>> = conn
+            .query_row("SELECT val FROM config WHERE key = 'schedVer';", [], |row| row.get(0))
+            .optional()?;
+        if let Some(raw_val) = raw_val {
+            let value: serde_json::Value =
+                serde_json::from_slice(&raw_val).map_err(|e| invalid_setting("schedVer", e))?;
+            return value.as_i64().ok_or_else(|| invalid_setting("schedVer", "not a number"));
+        }
+    }
+    if !crate::table_has_column(conn, "col", "conf")? {
+        return Ok(1);
+    }
+
+    let conf: String = conn.query_row("SELECT conf FROM col;", [], |row| row.get(0))?;
+    let conf: serde_json::Value = serde_json::from_str(&conf).map_err(|e| invalid_setting("col.conf", e))?;
+    Ok(conf.get("schedVer").and_then(|value| value.as_i64()).unwrap_or(1))
+}
+
+/// Returns `col.crt`, the collection's creation time in seconds.
+pub fn creation_time(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT crt FROM col;", [], |row| row.get(0))
+}
+
+/// Returns the decks named `deck_name` (`::` or `0x1F` separated, compared case-insensitively
+/// unless `case_sensitive` is set), followed by their children unless `exclude_subdecks` is set.
+///
+/// Needs the `unicase` collation.
+pub fn decks_matching(
+    conn: &Connection,
+    deck_name: &str,
+    exclude_subdecks: bool,
+    case_sensitive: bool,
+) -> Result<Vec<String>> {
+    // Anki stores `Parent::Child` as `Parent\x1fChild`; ?1 is that internal form, while ?2 keeps
+    // the input as typed for collections that still store literal `::` separators. Names are
+    // compared without surrounding spaces, tabs and non-breaking spaces, which a deck can pick up
+    // when it is renamed by pasting.
+    let internal = to_internal_deck_name(deck_name);
+
+    let query = if case_sensitive && exclude_subdecks {
+        "
+        SELECT name
+        FROM decks
+        WHERE trim(name, char(32, 9, 160)) = ?1
+        OR trim(name, char(32, 9, 160)) = ?2
+        ORDER BY name COLLATE unicase;
+    "
+    } else if case_sensitive {
+        // `LIKE` ignores ASCII case, so children are matched by comparing their prefix exactly
+        "
+        SELECT name
+        FROM decks
+        WHERE trim(name, char(32, 9, 160)) = ?1
+        OR trim(name, char(32, 9, 160)) = ?2
+        OR substr(name, 1, length(?1) + 1) = ?1 || char(31)
+        OR substr(name, 1, length(?2) + 2) = ?2 || '::'
+        ORDER BY name COLLATE unicase;
+    "
+    } else if exclude_subdecks {
+        "
+        SELECT name
+        FROM decks
+        WHERE trim(name, char(32, 9, 160)) COLLATE unicase = ?1
+        OR trim(name, char(32, 9, 160)) COLLATE unicase = ?2
+        ORDER BY name COLLATE unicase;
+    "
+    } else {
+        "
+        SELECT name
+        FROM decks
+        WHERE trim(name, char(32, 9, 160)) COLLATE unicase = ?1
+        OR trim(name, char(32, 9, 160)) COLLATE unicase = ?2
+        OR name COLLATE unicase LIKE ?1 || char(31) || '%'
+        OR name COLLATE unicase LIKE ?2 || '::%'
+        ORDER BY name COLLATE unicase;
+    "
+    };
+
+    trace_sql(query, params![internal, deck_name]);
+    let mut stmt = conn.prepare(query)?;
+    let decks = stmt
+        .query_map(params![internal, deck_name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Returns every deck whose name contains `deck_name` anywhere, ignoring case. Used to suggest
+/// what a name that matched nothing could have meant.
+///
+/// Needs the `unicase` collation.
+pub fn decks_containing(conn: &Connection, deck_name: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT name
+        FROM decks
+        WHERE instr(lower(name), lower(?1)) > 0
+        OR instr(lower(name), lower(?2)) > 0
+        ORDER BY name COLLATE unicase;
+    ",
+    )?;
+    let decks = stmt
+        .query_map(params![deck_name, to_internal_deck_name(deck_name)], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Returns the decks whose names match the `LIKE` pattern `like` (escaped with `\`), followed by
+/// their children unless `exclude_subdecks` is set.
+///
+/// Needs the `unicase` collation.
+pub fn decks_matching_like(conn: &Connection, like: &str, exclude_subdecks: bool) -> Result<Vec<String>> {
+    let query = if exclude_subdecks {
+        "
+        SELECT name
+        FROM decks
+        WHERE name LIKE ?1 ESCAPE '\\'
+        ORDER BY name COLLATE unicase;
+    "
+    } else {
+        "
+        SELECT name
+        FROM decks
+        WHERE name LIKE ?1 ESCAPE '\\'
+        OR name LIKE ?1 || char(31) || '%' ESCAPE '\\'
+        ORDER BY name COLLATE unicase;
+    "
+    };
+
+    trace_sql(query, params![like]);
+    let mut stmt = conn.prepare(query)?;
+    let decks = stmt
+        .query_map(params![like], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Case-sensitive counterpart of `decks_matching_like`: returns the decks whose names match the
+/// SQLite `GLOB` pattern `glob`, followed by their children unless `exclude_subdecks` is set.
+///
+/// Needs the `unicase` collation.
+pub fn decks_matching_glob(conn: &Connection, glob: &str, exclude_subdecks: bool) -> Result<Vec<String>> {
+    let query = if exclude_subdecks {
+        "
+        SELECT name
+        FROM decks
+        WHERE name GLOB ?1
+        ORDER BY name COLLATE unicase;
+    "
+    } else {
+        "
+        SELECT name
+        FROM decks
+        WHERE name GLOB ?1
+        OR name GLOB ?1 || char(31) || '*'
+        ORDER BY name COLLATE unicase;
+    "
+    };
+
+    trace_sql(query, params![glob]);
+    let mut stmt = conn.prepare(query)?;
+    let decks = stmt
+        .query_map(params![glob], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Returns the names of all decks, in the order Anki lists them.
+///
+/// Needs the `unicase` collation.
+pub fn deck_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM decks ORDER BY name COLLATE unicase;")?;
+    let decks = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Returns the name of the deck with the given `id`, followed by the names of its children
+/// unless `exclude_subdecks` is set. Children are found by their stored name, so no typed name
+/// is ever compared. Empty when no deck has the id.
+///
+/// Needs the `unicase` collation.
+pub fn decks_with_id(conn: &Connection, id: i64, exclude_subdecks: bool) -> Result<Vec<String>> {
+    let query = "
+        SELECT decks.name
+        FROM decks, (SELECT name FROM decks WHERE id = ?1) AS parent
+        WHERE decks.id = ?1
+        OR (NOT ?2 AND substr(decks.name, 1, length(parent.name) + 1) = parent.name || char(31))
+        ORDER BY decks.name COLLATE unicase;
+    ";
+    trace_sql(query, params![id, exclude_subdecks]);
+    let mut stmt = conn.prepare(query)?;
+    let decks = stmt
+        .query_map(params![id, exclude_subdecks], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(decks)
+}
+
+/// Which reviewed notes `reviewed_notes` selects.
+#[derive(Debug, Default)]
+pub struct NoteFilter<'a> {
+    pub start: i64,                  // First revlog id (inclusive) of the review window
+    pub end: i64,                    // End of the review window (exclusive), in milliseconds
+    pub decks: Option<&'a [String]>, // Home decks, by stored name; `None` for all decks
+    pub tag: Option<&'a str>,        // `LIKE` pattern matched against the space-padded tags
+    pub note_type: Option<&'a str>,  // Note type name; needs a `notetypes` table
+    pub review_types: Option<&'a [i64]>, // `revlog.type` values to count; `None` for all
+    pub note_ids: Option<&'a [i64]>, // Only these notes; `None` for any note
+    pub by_card: bool,               // Select cards instead of notes, for `--by-card`
+}
+
+/// A note with reviews inside the window of a `NoteFilter`, as seen from one of its home decks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviewedNote {
+    pub note_id: i64,      // The card id instead when the filter is `by_card`
+    pub deck_id: i64,      // Home deck of the reviewed cards
+    pub first_review: i64, // Earliest revlog id in the window
+    pub last_review: i64,  // Latest revlog id in the window
+}
+
+/// Returns every note with a review inside the filter's window, ordered by note id. A note with
+/// reviewed cards in several decks appears once per deck. With `by_card` every reviewed card is
+/// returned on its own instead, ordered by card id.
+///
+/// Cards in a filtered deck count towards their original deck (`odid`). Needs the `unicase`
+/// collation.
+pub fn reviewed_notes(conn: &Connection, filter: &NoteFilter) -> Result<Vec<ReviewedNote>> {
+    // Conditions and their values, numbered after the two window bounds
+    let mut conditions = vec!["revlog.id >= ?1 AND revlog.id < ?2".to_string()];
+    let mut values: Vec<&dyn rusqlite::ToSql> = vec![&filter.start, &filter.end];
+
+    if let Some(decks) = filter.decks {
+        // One placeholder per matched deck
+        let placeholders = (0..decks.len())
+            .map(|i| format!("?{}", values.len() + i + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        conditions.push(format!(
+            "(SELECT name FROM decks WHERE id = COALESCE(NULLIF(cards.odid, 0), cards.did)) IN ({})",
+            placeholders
+        ));
+        values.extend(decks.iter().map(|d| d as &dyn rusqlite::ToSql));
+    }
+
+    if let Some(tag) = &filter.tag {
+        values.push(tag);
+        conditions.push(format!("' ' || notes.tags || ' ' LIKE ?{} ESCAPE '\\'", values.len()));
+    }
+
+    if let Some(types) = filter.review_types {
+        conditions.push(review_type_condition(types, "revlog"));
+    }
+
+    // Ids are integers, so they are written into the query like the review types
+    if let Some(ids) = filter.note_ids {
+        let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+        conditions.push(format!("notes.id IN ({})", ids));
+    }
+
+    if let Some(note_type) = &filter.note_type {
+        values.push(note_type);
+        conditions.push(format!(
+            "notes.mid IN (SELECT id FROM notetypes WHERE name COLLATE unicase = ?{})",
+            values.len()
+        ));
+    }
+
+    let unit = if filter.by_card { "cards.id" } else { "notes.id" };
+    let query = format!(
+        "
+        SELECT {unit} AS unit, COALESCE(NULLIF(cards.odid, 0), cards.did) AS home, MIN(revlog.id), MAX(revlog.id)
+        FROM cards
+        JOIN notes ON cards.nid = notes.id
+        JOIN revlog ON cards.id = revlog.cid
+        WHERE {}
+        GROUP BY unit, home
+        ORDER BY unit, home;
+        ",
+        conditions.join("\n        AND ")
+    );
+    trace_sql(&query, &values);
+
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt
+        .query_map(values.as_slice(), |row| {
+            Ok(ReviewedNote {
+                note_id: row.get(0)?,
+                deck_id: row.get(1)?,
+                first_review: row.get(2)?,
+                last_review: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Returns those of `ids` that are notes of the collection, in ascending order.
+pub fn existing_notes(conn: &Connection, ids: &[i64]) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare_cached("SELECT 1 FROM notes WHERE id = ?1")?;
+    let mut known = Vec::new();
+    for id in ids {
+        if stmt.exists([id])? {
+            known.push(*id);
+        }
+    }
+    known.sort_unstable();
+    known.dedup();
+    Ok(known)
+}
+
+/// Which of a note's (or card's) reviews the per-note queries below look at.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReviewScope<'a> {
+    pub decks: Option<&'a [String]>, // Home decks, by stored name; `None` for all decks
+    pub types: Option<&'a [i64]>,    // `revlog.type` values; `None` for all
+    pub by_card: bool,               // The id passed is a card id rather than a note id
+    pub day_ends: Option<&'a [i64]>, // Ends of the window's Anki days; covers each card's latest review per day
+}
+
+impl ReviewScope<'_> {
+    /// The column of the cards `c` that the id passed to a query is compared with.
+    fn unit(&self) -> &'static str {
+        if self.by_card {
+            "c.id"
+        } else {
+            "c.nid"
+        }
+    }
+
+    /// `AND` clause keeping the reviews `r` that are their card's latest of the Anki day among the
+    /// reviews of `types` (with `latest` false, those that are not). Empty without `day_ends`.
+    fn and_latest_of_day(&self, latest: bool) -> String {
+        match self.later_same_day("1") {
+            Some(later) => format!("AND {}EXISTS ({})", if latest { "NOT " } else { "" }, later),
+            None => String::new(),
+        }
+    }
+
+    /// `AND` clause keeping the reviews `r` whose card's latest review of the Anki day would still
+    /// land after the card was created when moved back by the offset in placeholder `offset`.
+    /// Only meaningful for reviews that are not their card's latest of the day.
+    fn and_latest_of_day_moves(&self, offset: usize) -> String {
+        match self.later_same_day("MAX(later.id)") {
+            Some(later) => format!("AND ({}) - ?{} > c.id", later, offset),
+            None => String::new(),
+        }
+    }
+
+    /// Query selecting `column` from the reviews of `types` of the same card as `r`, later on the
+    /// same Anki day. `None` without `day_ends`.
+    ///
+    /// The day ends are computed timestamps, so they are written into the query rather than bound.
+    fn later_same_day(&self, column: &str) -> Option<String> {
+        let ends = self.day_ends.filter(|ends| !ends.is_empty())?;
+        let day_end = ends
+            .iter()
+            .map(|end| format!("WHEN r.id < {end} THEN {end}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let types = self
+            .types
+            .map_or_else(String::new, |types| format!("AND {}", review_type_condition(types, "later")));
+        Some(format!(
+            "SELECT {} FROM revlog later WHERE later.cid = r.cid AND later.id > r.id \
+             AND later.id < (CASE {} ELSE r.id END) {}",
+            column, day_end, types
+        ))
+    }
+}
+
+/// SQL condition limiting the cards `c` to those whose home deck is one of `decks`, numbering the
+/// placeholders from `first`. Empty when `decks` is `None`.
+///
+/// Deck lists always hold names read from `decks.name`, so they are compared exactly; decks whose
+/// names differ only by case stay apart.
+fn home_deck_condition(decks: Option<&[String]>, first: usize, negate: bool) -> String {
+    let Some(decks) = decks else {
+        return String::new();
+    };
+    let placeholders = (0..decks.len())
+        .map(|i| format!("?{}", first + i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "AND (SELECT name FROM decks WHERE id = COALESCE(NULLIF(c.odid, 0), c.did)) {}IN ({})",
+        if negate { "NOT " } else { "" },
+        placeholders
+    )
+}
+
+/// SQL condition limiting the reviews of `table` to the `revlog.type` values in `types`. The values
+/// are validated integers, so they are written into the query rather than bound.
+fn review_type_condition(types: &[i64], table: &str) -> String {
+    let types = types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ");
+    format!("{}.type IN ({})", table, types)
+}
+
+/// `review_type_condition` as an extra `AND` clause on the reviews `r`; empty when `types` is
+/// `None`.
+fn and_review_types(types: Option<&[i64]>) -> String {
+    types.map_or_else(String::new, |types| format!("AND {}", review_type_condition(types, "r")))
+}
+
+/// Runs a `(revlog id, card id)` query whose first parameters are `leading`, followed by one per
+/// deck in `decks`.
+fn query_reviews(
+    conn: &Connection,
+    sql: &str,
+    leading: &[&dyn rusqlite::ToSql],
+    decks: Option<&[String]>,
+) -> Result<Vec<(i64, i64)>> {
+    let mut values = leading.to_vec();
+    values.extend(decks.unwrap_or_default().iter().map(|d| d as &dyn rusqlite::ToSql));
+    trace_sql(sql, &values);
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt
+        .query_map(values.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, i64)>, _>>()?;
+    Ok(rows)
+}
+
+/// Returns `(revlog id, card id)` of the reviews of note `id` (card `id` when `scope.by_card`)
+/// inside `[start, end)` that can be moved back by `offset` milliseconds, counting only cards
+/// whose home deck is in `scope.decks` and reviews whose `type` is in `scope.types`. This is the
+/// read-only counterpart of `move_reviews`.
+///
+/// Needs the `unicase` collation.
+pub fn movable_reviews(
+    conn: &Connection,
+    id: i64,
+    (start, end): (i64, i64),
+    offset: i64,
+    scope: &ReviewScope,
+) -> Result<Vec<(i64, i64)>> {
+    let sql = format!(
+        "
+        SELECT r.id, r.cid
+        FROM revlog r
+        INNER JOIN cards c ON r.cid = c.id
+        WHERE {} = ?1
+        AND r.id >= ?2
+        AND r.id < ?3
+        AND r.id - ?4 > c.id
+        {}
+        {}
+        {};
+    ",
+        scope.unit(),
+        and_review_types(scope.types),
+        scope.and_latest_of_day(true),
+        home_deck_condition(scope.decks, 5, false)
+    );
+    query_reviews(conn, &sql, &[&id, &start, &end, &offset], scope.decks)
+}
+
+/// Returns `(revlog id, card id)` of the reviews of note `id` (card `id` when `scope.by_card`)
+/// inside `[start, end)` that moving back by `offset` would place before their card was created
+/// (card ids are creation times), counting only the reviews `scope` covers.
+///
+/// Needs the `unicase` collation.
+pub fn reviews_before_creation(
+    conn: &Connection,
+    id: i64,
+    (start, end): (i64, i64),
+    offset: i64,
+    scope: &ReviewScope,
+) -> Result<Vec<(i64, i64)>> {
+    let sql = format!(
+        "
+        SELECT r.id, r.cid
+        FROM revlog r
+        INNER JOIN cards c ON r.cid = c.id
+        WHERE {} = ?1
+        AND r.id >= ?2
+        AND r.id < ?3
+        AND r.id - ?4 <= c.id
+        {}
+        {}
+        {};
+    ",
+        scope.unit(),
+        and_review_types(scope.types),
+        scope.and_latest_of_day(true),
+        home_deck_condition(scope.decks, 5, false)
+    );
+    query_reviews(conn, &sql, &[&id, &start, &end, &offset], scope.decks)
+}
+
+/// Returns `(revlog id, card id)` of the reviews of note `id` (card `id` when `scope.by_card`)
+/// inside `[start, end)` whose card's home deck is not one of `scope.decks`, counting only
+/// reviews whose `type` is in `scope.types`. Empty when `scope` covers all decks.
+///
+/// Needs the `unicase` collation.
+pub fn reviews_outside_decks(
+    conn: &Connection,
+    id: i64,
+    (start, end): (i64, i64),
+    scope: &ReviewScope,
+) -> Result<Vec<(i64, i64)>> {
+    if scope.decks.is_none() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "
+        SELECT r.id, r.cid
+        FROM revlog r
+        INNER JOIN cards c ON r.cid = c.id
+        WHERE {} = ?1
+        AND r.id >= ?2
+        AND r.id < ?3
+        {}
+        {}
+        {};
+    ",
+        scope.unit(),
+        and_review_types(scope.types),
+        scope.and_latest_of_day(true),
+        home_deck_condition(scope.decks, 4, true)
+    );
+    query_reviews(conn, &sql, &[&id, &start, &end], scope.decks)
+}
+
+/// Returns `(revlog id, card id)` of the reviews of note `id` (card `id` when `scope.by_card`)
+/// inside `[start, end)` that a later review of their card on the same Anki day supersedes, for
+/// `--newest-only`. Reviews whose superseding review stays put, because moving it back by
+/// `offset` would place it before the card was created, are not included. Empty unless
+/// `scope.day_ends` is given.
+///
+/// Needs the `unicase` collation.
+pub fn superseded_reviews(
+    conn: &Connection,
+    id: i64,
+    (start, end): (i64, i64),
+    offset: i64,
+    scope: &ReviewScope,
+) -> Result<Vec<(i64, i64)>> {
+    if scope.day_ends.is_none() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "
+        SELECT r.id, r.cid
+        FROM revlog r
+        INNER JOIN cards c ON r.cid = c.id
+        WHERE {} = ?1
+        AND r.id >= ?2
+        AND r.id < ?3
+        {}
+        {}
+        {}
+        {}
+        ORDER BY r.id;
+    ",
+        scope.unit(),
+        and_review_types(scope.types),
+        scope.and_latest_of_day(false),
+        scope.and_latest_of_day_moves(4),
+        home_deck_condition(scope.decks, 5, false)
+    );
+    query_reviews(conn, &sql, &[&id, &start, &end, &offset], scope.decks)
+}
+
+/// Deletes the reviews `ids` and returns them as they were, for the undo journal.
+pub fn delete_reviews(conn: &Connection, ids: &[i64]) -> Result<Vec<DeletedReview>> {
+    let sql = "DELETE FROM revlog WHERE id = ?1 RETURNING id, cid, usn, ease, ivl, lastIvl, factor, time, type;";
+    let mut stmt = conn.prepare_cached(sql)?;
+    let mut deleted = Vec::with_capacity(ids.len());
+    for id in ids {
+        trace_sql(sql, params![id]);
+        deleted.push(stmt.query_row(params![id], |row| {
+            Ok(DeletedReview {
+                id: row.get(0)?,
+                cid: row.get(1)?,
+                usn: row.get(2)?,
+                ease: row.get(3)?,
+                ivl: row.get(4)?,
+                last_ivl: row.get(5)?,
+                factor: row.get(6)?,
+                time: row.get(7)?,
+                kind: row.get(8)?,
+            })
+        })?);
+    }
+    Ok(deleted)
+}
+
+/// Puts back a review removed by `delete_reviews`.
+pub fn restore_review(conn: &Connection, review: &DeletedReview) -> Result<()> {
+    let sql = "INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9);";
+    let values = params![
+        review.id,
+        review.cid,
+        review.usn,
+        review.ease,
+        review.ivl,
+        review.last_ivl,
+        review.factor,
+        review.time,
+        review.kind
+    ];
+    trace_sql(sql, values);
+    conn.execute(sql, values)?;
+    Ok(())
+}
+
+/// Counts the reviews of card `cid` inside `[start, end)` that are not inside `[skip_start,
+/// skip_end)`, the window whose reviews are about to move away.
+pub fn card_reviews_between(
+    conn: &Connection,
+    cid: i64,
+    (start, end): (i64, i64),
+    (skip_start, skip_end): (i64, i64),
+) -> Result<usize> {
+    conn.prepare_cached(
+        "SELECT COUNT(*) FROM revlog WHERE cid = ?1 AND id >= ?2 AND id < ?3 AND NOT (id >= ?4 AND id < ?5);",
+    )?
+    .query_row(params![cid, start, end, skip_start, skip_end], |row| row.get(0))
+}
+
+/// Moves the reviews `movable_reviews` selects back by `offset` milliseconds and returns their
+/// new `(revlog id, card id)`.
+///
+/// `time` is only clamped to `cap_review_time` when a cap is given; otherwise it is written back
+/// unchanged. Needs the `unicase` collation.
+pub fn move_reviews(
+    conn: &Connection,
+    id: i64,
+    (start, end): (i64, i64),
+    offset: i64,
+    cap_review_time: Option<i64>,
+    scope: &ReviewScope,
+) -> Result<Vec<(i64, i64)>> {
+    let sql = format!(
+        "
+        UPDATE revlog
+        SET id = id - ?1,
+            time = CASE WHEN ?5 IS NULL THEN time ELSE MIN(time, ?5) END
+        WHERE id IN (
+            SELECT r.id
+            FROM revlog r
+            INNER JOIN cards c ON r.cid = c.id
+            WHERE {} = ?2
+            AND r.id >= ?3
+            AND r.id < ?4
+            AND r.id - ?1 > c.id
+            {}
+            {}
+            {}
+        )
+        RETURNING id, cid;
+    ",
+        scope.unit(),
+        and_review_types(scope.types),
+        scope.and_latest_of_day(true),
+        home_deck_condition(scope.decks, 6, false)
+    );
+    query_reviews(conn, &sql, &[&offset, &id, &start, &end, &cap_review_time], scope.decks)
+}
+
+/// Returns the stored name of the home deck of card `id` (its original deck while it sits in a
+/// filtered deck).
+pub fn home_deck(conn: &Connection, id: i64) -> Result<String> {
+    conn.prepare_cached(
+        "
+        SELECT decks.name
+        FROM cards
+        JOIN decks ON COALESCE(NULLIF(cards.odid, 0), cards.did) = decks.id
+        WHERE cards.id = ?1;
+    ",
+    )?
+    .query_row(params![id], |row| row.get(0))
+}
+
+/// Returns the note of card `id`.
+pub fn card_note(conn: &Connection, id: i64) -> Result<i64> {
+    conn.prepare_cached("SELECT nid FROM cards WHERE id = ?1;")?
+        .query_row(params![id], |row| row.get(0))
+}
+
+/// Returns the `mod` and `usn` of card `id`.
+pub fn card_mod_usn(conn: &Connection, id: i64) -> Result<(i64, i64)> {
+    conn.prepare_cached("SELECT mod, usn FROM cards WHERE id = ?1;")?
+        .query_row(params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+}
+
+/// Returns the `due` of card `id` if it is a day number, as for cards in the review and
+/// day-learning queues and for buried or suspended review cards, and `None` otherwise (new cards
+/// and intraday learning steps, whose `due` means something else).
+pub fn day_due(conn: &Connection, id: i64) -> Result<Option<i64>> {
+    conn.prepare_cached(
+        "SELECT due FROM cards WHERE id = ?1 AND (queue IN (2, 3) OR (queue < 0 AND type = 2));",
+    )?
+    .query_row(params![id], |row| row.get(0))
+    .optional()
+}
+
+pub fn set_due(conn: &Connection, id: i64, due: i64) -> Result<()> {
+    conn.prepare_cached("UPDATE cards SET due = ?1 WHERE id = ?2;")?
+        .execute(params![due, id])?;
+    Ok(())
+}
+
+/// Sets `mod` of every card in `cards`, given as `(card id, mod)`, and flags it for sync
+/// (`usn = -1`).
+pub fn mark_cards_modified(conn: &Connection, cards: &[(i64, i64)]) -> Result<()> {
+    let sql = "UPDATE cards SET mod = ?1, usn = -1 WHERE id = ?2;";
+    trace!("Running SQL:\n{}\n  once for each (card, mod) of {:?}", sql.trim(), cards);
+    let mut stmt = conn.prepare_cached(sql)?;
+    for (id, modified) in cards {
+        stmt.execute(params![modified, id])?;
+    }
+    Ok(())
+}
+
+/// The most recent review before some point in time, which a backfilled review copies its card
+/// and scheduling state from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackfillTemplate {
+    pub card_id: i64,
+    pub kind: i64, // `revlog.type`: 0 learn, 1 review, 2 relearn, ...
+    pub ivl: i64,
+    pub factor: i64,
+}
+
+/// Returns the latest review before the revlog id `before` of a card whose home deck is in
+/// `decks` (any card when `None`).
+///
+/// Needs the `unicase` collation.
+pub fn backfill_template(conn: &Connection, before: i64, decks: Option<&[String]>) -> Result<Option<BackfillTemplate>> {
+    let sql = format!(
+        "
+        SELECT r.cid, r.type, r.ivl, r.factor
+        FROM revlog r
+        JOIN cards c ON c.id = r.cid
+        WHERE r.id < ?1
+        {}
+        ORDER BY r.id DESC
+        LIMIT 1;
+        ",
+        home_deck_condition(decks, 2, false)
+    );
+    let mut values: Vec<&dyn rusqlite::ToSql> = vec![&before];
+    values.extend(decks.unwrap_or_default().iter().map(|d| d as &dyn rusqlite::ToSql));
+    trace_sql(&sql, &values);
+    conn.query_row(&sql, values.as_slice(), |row| {
+        Ok(BackfillTemplate {
+            card_id: row.get(0)?,
+            kind: row.get(1)?,
+            ivl: row.get(2)?,
+            factor: row.get(3)?,
+        })
+    })
+    .optional()
+}
+
+/// Inserts a synthetic "Good" review of `template`'s card with the revlog id `id`, marked for
+/// sync. A learning review stays a learning review; anything else is recorded as a plain review.
+pub fn insert_backfilled_review(conn: &Connection, id: i64, template: &BackfillTemplate, time: i64) -> Result<()> {
+    let kind = if template.kind == 0 { 0 } else { 1 };
+    let sql = "INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+         VALUES (?1, ?2, -1, 3, ?3, ?3, ?4, ?5, ?6);";
+    let values = params![id, template.card_id, template.ivl, template.factor, time, kind];
+    trace_sql(sql, values);
+    conn.execute(sql, values)?;
+    Ok(())
+}
+
+/// Returns the first revlog id from `id` on that no review uses yet.
+pub fn free_revlog_id(conn: &Connection, mut id: i64) -> Result<i64> {
+    let mut stmt = conn.prepare("SELECT 1 FROM revlog WHERE id = ?1;")?;
+    while stmt.exists(params![id])? {
+        id += 1;
+    }
+    Ok(id)
+}
+
+/// Bumps `col.scm`, which makes Anki require a full sync instead of merging the changed revlog.
+pub fn bump_schema(conn: &Connection) -> Result<()> {
+    let sql = "UPDATE col SET scm = scm + 1;";
+    trace_sql(sql, &[]);
+    conn.execute(sql, [])?;
+    Ok(())
+}
+
+/// Returns the ids of the reviews inside `[start, end)` in ascending order, limited to cards whose
+/// home deck is one of `decks` when given.
+///
+/// Needs the `unicase` collation.
+pub fn review_ids(conn: &Connection, (start, end): (i64, i64), decks: Option<&[String]>) -> Result<Vec<i64>> {
+    let deck_filter = match decks {
+        Some(decks) => {
+            let placeholders = (0..decks.len())
+                .map(|i| format!("?{}", i + 3))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("AND decks.name IN ({})", placeholders)
+        }
+        None => String::new(),
+    };
+    let query = format!(
+        "
+        SELECT revlog.id
+        FROM revlog
+        JOIN cards ON revlog.cid = cards.id
+        JOIN decks ON COALESCE(NULLIF(cards.odid, 0), cards.did) = decks.id
+        WHERE revlog.id >= ?1 AND revlog.id < ?2
+        {}
+        ORDER BY revlog.id;
+        ",
+        deck_filter
+    );
+
+    let mut stmt = conn.prepare(&query)?;
+    let mut values: Vec<&dyn rusqlite::ToSql> = vec![&start, &end];
+    values.extend(decks.unwrap_or_default().iter().map(|d| d as &dyn rusqlite::ToSql));
+    trace_sql(&query, &values);
+    let ids = stmt
+        .query_map(values.as_slice(), |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    /// An in-memory collection with decks `Spanish` (1), `Spanish::Verbs` (2) and `French` (3),
+    /// and one card per deck (100, 200, 300 of notes 10, 20, 30) reviewed at 1000s and 5000s.
+    fn collection() -> Connection {
+        let conn = test_fixtures::in_memory_collection(4);
+        conn.execute_batch(
+            "
+            INSERT INTO decks (id, name) VALUES (1, 'Spanish'), (2, 'Spanish' || char(31) || 'Verbs'), (3, 'French');
+            INSERT INTO notetypes (id, name) VALUES (1, 'Basic'), (2, 'Cloze');
+            INSERT INTO notes (id, mid, tags) VALUES (10, 1, ' verbs '), (20, 2, ''), (30, 1, ' verbs ');
+            INSERT INTO cards (id, nid, did, mod, usn, type, queue, due, odid)
+            VALUES (100, 10, 1, 0, 0, 2, 2, 100, 0), (200, 20, 2, 0, 0, 2, 2, 100, 0), (300, 30, 3, 0, 0, 2, 2, 100, 0);
+            INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+            VALUES (1000000, 100, 0, 3, 1, 1, 2500, 5000, 1), (1000001, 200, 0, 3, 1, 1, 2500, 5000, 1),
+                   (1000002, 300, 0, 3, 1, 1, 2500, 5000, 1), (5000000, 100, 0, 3, 1, 1, 2500, 5000, 1);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_rollover_lookup() {
+        let conn = test_fixtures::in_memory_collection(5);
+        assert_eq!(rollover_from_config_table(&conn).unwrap(), NaiveTime::from_hms_opt(5, 0, 0));
+        assert_eq!(rollover_from_col_conf(&conn).unwrap(), None);
+
+        conn.execute("UPDATE config SET val = CAST('4:30' AS BLOB);", []).unwrap();
+        assert_eq!(rollover_from_config_table(&conn).unwrap(), NaiveTime::from_hms_opt(4, 30, 0));
+
+        conn.execute("UPDATE config SET val = CAST('soon' AS BLOB);", []).unwrap();
+        let err = rollover_from_config_table(&conn).unwrap_err();
+        assert!(matches!(&err, AppError::InvalidSetting { key, .. } if key == "rollover"), "{:?}", err);
+
+        conn.execute("DELETE FROM config;", []).unwrap();
+        assert_eq!(rollover_from_config_table(&conn).unwrap(), None);
+    }
+
+    #[test]
+    fn test_creation_offset_lookup() {
+        let conn = test_fixtures::in_memory_collection(4);
+        assert_eq!(creation_offset(&conn).unwrap(), None);
+
+        conn.execute(
+            "INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('creationOffset', 0, 0, CAST('-540' AS BLOB));",
+            [],
+        )
+        .unwrap();
+        assert_eq!(creation_offset(&conn).unwrap(), Some(-540));
+
+        conn.execute("UPDATE config SET val = CAST('{' AS BLOB) WHERE KEY = 'creationOffset';", []).unwrap();
+        let err = creation_offset(&conn).unwrap_err();
+        assert!(matches!(&err, AppError::InvalidSetting { key, .. } if key == "creationOffset"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_scheduler_version_lookup() {
+        let conn = test_fixtures::in_memory_collection(4);
+        assert_eq!(scheduler_version(&conn).unwrap(), 1);
+
+        conn.execute("ALTER TABLE col ADD COLUMN conf text NOT NULL DEFAULT '{\"schedVer\": 2}'", []).unwrap();
+        assert_eq!(scheduler_version(&conn).unwrap(), 2);
+
+        // The config table takes precedence over col.conf
+        conn.execute("INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('schedVer', 0, 0, CAST('1' AS BLOB));", [])
+            .unwrap();
+        assert_eq!(scheduler_version(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_describe_param() {
+        let ms: i64 = 1_735_711_200_000;
+        let local = date::format_review_time(&Local, ms);
+        assert_eq!(describe_param(&ms), format!("{} ({})", ms, local));
+        assert_eq!(describe_param(&(ms / 1000)), format!("{} ({})", ms / 1000, local));
+        assert_eq!(describe_param(&86_400_000i64), "86400000");
+        assert_eq!(describe_param(&"Spanish\u{1f}Verbs"), "'Spanish::Verbs'");
+        assert_eq!(describe_param(&None::<i64>), "Null");
+    }
+
+    #[test]
+    fn test_deck_lookups() {
+        let conn = collection();
+        assert_eq!(
+            decks_matching(&conn, "spanish", false, false).unwrap(),
+            vec!["Spanish".to_string(), "Spanish\u{1f}Verbs".to_string()]
+        );
+        assert_eq!(decks_matching(&conn, "Spanish", true, false).unwrap(), vec!["Spanish".to_string()]);
+        assert!(decks_matching(&conn, "spanish", false, true).unwrap().is_empty());
+        assert_eq!(
+            decks_matching(&conn, "Spanish::Verbs", false, true).unwrap(),
+            vec!["Spanish\u{1f}Verbs".to_string()]
+        );
+        assert_eq!(decks_matching(&conn, "Spanish", false, true).unwrap().len(), 2);
+        assert_eq!(decks_matching_glob(&conn, "*ench", false).unwrap(), vec!["French".to_string()]);
+        assert!(decks_matching_glob(&conn, "*ENCH", false).unwrap().is_empty());
+        assert_eq!(decks_containing(&conn, "verb").unwrap(), vec!["Spanish\u{1f}Verbs".to_string()]);
+        assert_eq!(decks_matching_like(&conn, "%ench", false).unwrap(), vec!["French".to_string()]);
+    }
+
+    /// `(note id, home deck id)` of the notes `reviewed_notes` selects.
+    fn note_decks(conn: &Connection, filter: &NoteFilter) -> Vec<(i64, i64)> {
+        reviewed_notes(conn, filter)
+            .unwrap()
+            .into_iter()
+            .map(|note| (note.note_id, note.deck_id))
+            .collect()
+    }
+
+    #[test]
+    fn test_reviewed_notes_filters() {
+        let conn = collection();
+        let window = NoteFilter { start: 1_000_000, end: 1_001_000, ..Default::default() };
+        assert_eq!(note_decks(&conn, &window), vec![(10, 1), (20, 2), (30, 3)]);
+
+        let spanish = ["Spanish".to_string(), "Spanish\u{1f}Verbs".to_string()];
+        let filter = NoteFilter { decks: Some(&spanish), ..window };
+        assert_eq!(note_decks(&conn, &filter), vec![(10, 1), (20, 2)]);
+
+        let filter = NoteFilter { tag: Some("% verbs %"), note_type: Some("basic"), ..filter };
+        assert_eq!(note_decks(&conn, &filter), vec![(10, 1)]);
+    }
+
+    #[test]
+    fn test_review_queries_are_scoped_to_decks() {
+        let conn = collection();
+        // Note 10 gets a second card in French
+        conn.execute_batch(
+            "
+            INSERT INTO cards (id, nid, did, mod, usn, type, queue, due, odid) VALUES (101, 10, 3, 0, 0, 2, 2, 100, 0);
+            INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type) VALUES (1000003, 101, 0, 3, 1, 1, 2500, 5000, 1);
+            ",
+        )
+        .unwrap();
+        let window = (0, 2_000_000);
+        let spanish = ["Spanish".to_string()];
+
+        let all = ReviewScope::default();
+        let in_spanish = ReviewScope { decks: Some(&spanish), ..all };
+        assert_eq!(movable_reviews(&conn, 10, window, 500_000, &all).unwrap().len(), 2);
+        assert_eq!(movable_reviews(&conn, 10, window, 500_000, &in_spanish).unwrap(), vec![(1000000, 100)]);
+        assert_eq!(reviews_outside_decks(&conn, 10, window, &in_spanish).unwrap(), vec![(1000003, 101)]);
+        assert!(reviews_outside_decks(&conn, 10, window, &ReviewScope { types: Some(&[0, 2]), ..in_spanish })
+            .unwrap()
+            .is_empty());
+        assert!(movable_reviews(&conn, 10, window, 500_000, &ReviewScope { types: Some(&[0]), ..all })
+            .unwrap()
+            .is_empty());
+        // A card id selects just that card's reviews
+        let card = ReviewScope { by_card: true, ..all };
+        assert_eq!(movable_reviews(&conn, 101, window, 500_000, &card).unwrap(), vec![(1000003, 101)]);
+        assert_eq!(
+            move_reviews(&conn, 10, window, 500_000, None, &ReviewScope { types: Some(&[1]), ..in_spanish }).unwrap(),
+            vec![(500000, 100)]
+        );
+    }
+
+    #[test]
+    fn test_move_reviews_and_mark_cards() {
+        let conn = collection();
+        let window = (0, 2_000_000);
+
+        let all = ReviewScope::default();
+        assert_eq!(movable_reviews(&conn, 10, window, 500_000, &all).unwrap(), vec![(1000000, 100)]);
+        assert_eq!(reviews_before_creation(&conn, 10, window, 999_950, &all).unwrap(), vec![(1000000, 100)]);
+
+        assert_eq!(move_reviews(&conn, 10, window, 500_000, Some(1000), &all).unwrap(), vec![(500000, 100)]);
+        assert_eq!(review_ids(&conn, (0, 2_000_000), None).unwrap(), vec![500000, 1000001, 1000002]);
+        assert_eq!(
+            review_ids(&conn, (0, 2_000_000), Some(&["French".to_string()])).unwrap(),
+            vec![1000002]
+        );
+
+        mark_cards_modified(&conn, &[(100, 42)]).unwrap();
+        assert_eq!(card_mod_usn(&conn, 100).unwrap(), (42, -1));
+    }
+}
@@ -0,0 +1,159 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Version of the `--json` output format, printed as `schema_version` in every JSON document.
+///
+/// Bump it whenever a field is removed, renamed or changes meaning, so that scripts can tell
+/// which layout they are reading. Adding a field does not change the version.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// A `--json` document: the payload's fields preceded by the format and tool versions.
+#[derive(Serialize)]
+struct JsonDocument<'a, T: Serialize> {
+    schema_version: u32,
+    version: &'a str, // Version of the tool that wrote the document
+    #[serde(flatten)]
+    payload: &'a T,
+}
+
+/// Renders `payload` as the pretty-printed JSON printed by `--json`, with `schema_version` and
+/// the tool's `version` added.
+pub fn to_json<T: Serialize>(payload: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&JsonDocument {
+        schema_version: JSON_SCHEMA_VERSION,
+        version: crate::APP_VERSION,
+        payload,
+    })
+}
+
+/// Number of reviews done on one Anki day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub reviews: usize,
+}
+
+/// The longest run of consecutive days with at least one review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Streak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: usize,
+}
+
+/// Review activity over a range of days, as shown by `--report`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StreakReport {
+    pub days: Vec<DayCount>,
+    pub longest_streak: Option<Streak>,
+    pub missing: Vec<NaiveDate>, // Days without a single review
+}
+
+/// The days without a single review in a range, as listed by `report --prune-empty-days`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EmptyDays {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub empty_days: Vec<NaiveDate>,
+}
+
+impl StreakReport {
+    /// Every day of the report without a review, including those before the first and after the
+    /// last active day, for the range `from` to `to` the report covers.
+    pub fn empty_days(&self, from: NaiveDate, to: NaiveDate) -> EmptyDays {
+        EmptyDays { from, to, empty_days: self.missing.clone() }
+    }
+}
+
+/// Builds the report from per-day review counts, which must be consecutive and in order.
+pub fn streak_report(days: Vec<DayCount>) -> StreakReport {
+    let mut longest: Option<Streak> = None;
+    let mut current: Option<Streak> = None;
+    let mut missing = Vec::new();
+
+    for day in &days {
+        if day.reviews == 0 {
+            missing.push(day.date);
+            current = None;
+            continue;
+        }
+        let streak = match current.take() {
+            Some(streak) => Streak { end: day.date, days: streak.days + 1, ..streak },
+            None => Streak { start: day.date, end: day.date, days: 1 },
+        };
+        if longest.as_ref().is_none_or(|best| streak.days > best.days) {
+            longest = Some(streak.clone());
+        }
+        current = Some(streak);
+    }
+
+    StreakReport { days, longest_streak: longest, missing }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_carries_schema_and_tool_version() {
+        let report = streak_report(Vec::new());
+        let json: serde_json::Value = serde_json::from_str(&to_json(&report).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["missing"], serde_json::json!([]));
+        assert!(to_json(&report).unwrap().starts_with("{\n  \"schema_version\": 1,"));
+    }
+
+    fn days(start: NaiveDate, counts: &[usize]) -> Vec<DayCount> {
+        counts
+            .iter()
+            .zip(start.iter_days())
+            .map(|(reviews, date)| DayCount { date, reviews: *reviews })
+            .collect()
+    }
+
+    #[test]
+    fn test_streak_report_finds_longest_streak_and_gaps() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let report = streak_report(days(start, &[3, 0, 5, 1, 2, 0, 0, 4]));
+
+        assert_eq!(
+            report.longest_streak,
+            Some(Streak {
+                start: NaiveDate::from_ymd_opt(2025, 1, 3).unwrap(),
+                end: NaiveDate::from_ymd_opt(2025, 1, 5).unwrap(),
+                days: 3,
+            })
+        );
+        assert_eq!(
+            report.missing,
+            vec![
+                NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 1, 6).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 1, 7).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_days_lists_every_day_without_reviews() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 1, 5).unwrap();
+        let report = streak_report(days(start, &[0, 2, 0, 1, 0]));
+
+        let empty = report.empty_days(start, end);
+        let json: serde_json::Value = serde_json::from_str(&to_json(&empty).unwrap()).unwrap();
+        assert_eq!(json["from"], "2025-01-01");
+        assert_eq!(json["to"], "2025-01-05");
+        assert_eq!(json["empty_days"], serde_json::json!(["2025-01-01", "2025-01-03", "2025-01-05"]));
+    }
+
+    #[test]
+    fn test_streak_report_without_reviews() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let report = streak_report(days(start, &[0, 0]));
+
+        assert_eq!(report.longest_streak, None);
+        assert_eq!(report.missing.len(), 2);
+    }
+}
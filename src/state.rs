@@ -0,0 +1,66 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What is remembered between runs, for `--since-last-run`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub last_run: DateTime<Utc>, // When the last successful real run finished
+}
+
+impl State {
+    /// The local calendar day of the last run.
+    pub fn last_run_date(&self) -> NaiveDate {
+        self.last_run.with_timezone(&Local).date_naive()
+    }
+}
+
+/// Returns where the state file lives: `state.json` in the same directory as the config file.
+pub fn path_next_to(config_path: &Path) -> PathBuf {
+    config_path.parent().unwrap_or_else(|| Path::new(".")).join("state.json")
+}
+
+/// Reads the state file at `path`. A missing file is not an error; there simply was no run yet.
+pub fn load(path: &Path) -> Result<Option<State>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Cannot read state file '{}': {}", path.display(), err)),
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| format!("Invalid state file '{}': {}", path.display(), e))
+}
+
+/// Writes `state` to `path`, creating its directory if needed.
+pub fn save(path: &Path, state: &State) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(state).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = path_next_to(&dir.path().join("anki_streak_fixer").join("config.toml"));
+        assert_eq!(path, dir.path().join("anki_streak_fixer").join("state.json"));
+        assert_eq!(load(&path).unwrap(), None);
+
+        let state = State {
+            last_run: "2025-01-03T15:30:00Z".parse().unwrap(),
+        };
+        save(&path, &state).unwrap();
+        assert_eq!(load(&path).unwrap(), Some(state));
+
+        fs::write(&path, "{}").unwrap();
+        assert!(load(&path).unwrap_err().starts_with("Invalid state file"));
+    }
+}
@@ -0,0 +1,241 @@
+//! Helpers for building minimal Anki collections on disk for tests.
+//!
+//! Only the tables and columns this tool touches are created, so the fixtures
+//! stay small while still exercising the real queries.
+
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Creates `collection.anki2` inside a fresh temporary directory.
+///
+/// The returned `TempDir` must be kept alive for as long as the file is used.
+pub fn create_collection(rollover: i64) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let path = dir.path().join("collection.anki2");
+    let conn = Connection::open(&path).expect("Failed to create collection");
+    create_schema(&conn, rollover);
+    (dir, path)
+}
+
+/// Creates the same collection in memory, with the `unicase` collation registered.
+pub fn in_memory_collection(rollover: i64) -> Connection {
+    let conn = Connection::open_in_memory().expect("Failed to create collection");
+    crate::register_unicase_collation(&conn).expect("Failed to register collation");
+    create_schema(&conn, rollover);
+    conn
+}
+
+fn create_schema(conn: &Connection, rollover: i64) {
+    conn.execute_batch(
+        "
+        CREATE TABLE col (
+            id integer PRIMARY KEY,
+            crt integer NOT NULL,
+            mod integer NOT NULL,
+            scm integer NOT NULL,
+            ver integer NOT NULL
+        );
+        CREATE TABLE config (
+            KEY text NOT NULL PRIMARY KEY,
+            usn integer NOT NULL,
+            mtime_secs integer NOT NULL,
+            val blob NOT NULL
+        );
+        CREATE TABLE decks (
+            id integer PRIMARY KEY NOT NULL,
+            name text NOT NULL
+        );
+        CREATE TABLE notetypes (
+            id integer NOT NULL PRIMARY KEY,
+            name text NOT NULL
+        );
+        CREATE TABLE notes (
+            id integer PRIMARY KEY,
+            mid integer NOT NULL,
+            tags text NOT NULL
+        );
+        CREATE TABLE cards (
+            id integer PRIMARY KEY,
+            nid integer NOT NULL,
+            did integer NOT NULL,
+            mod integer NOT NULL,
+            usn integer NOT NULL,
+            type integer NOT NULL,
+            queue integer NOT NULL,
+            due integer NOT NULL,
+            odid integer NOT NULL
+        );
+        CREATE TABLE revlog (
+            id integer PRIMARY KEY,
+            cid integer NOT NULL,
+            usn integer NOT NULL,
+            ease integer NOT NULL,
+            ivl integer NOT NULL,
+            lastIvl integer NOT NULL,
+            factor integer NOT NULL,
+            time integer NOT NULL,
+            type integer NOT NULL
+        );
+        INSERT INTO col (id, crt, mod, scm, ver) VALUES (1, 0, 0, 0, 18);
+        ",
+    )
+    .expect("Failed to create schema");
+    conn.execute(
+        "INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('rollover', 0, 0, ?1)",
+        params![rollover.to_string().into_bytes()],
+    )
+    .expect("Failed to insert rollover");
+}
+
+pub fn remove_rollover(path: &Path) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute("DELETE FROM config WHERE KEY = 'rollover'", []).unwrap();
+}
+
+/// Sets `col.crt` and, unless `None`, the `creationOffset` config key (minutes west of UTC).
+pub fn set_creation(path: &Path, crt: i64, creation_offset: Option<i64>) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute("UPDATE col SET crt = ?1", params![crt]).unwrap();
+    if let Some(offset) = creation_offset {
+        conn.execute(
+            "INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('creationOffset', 0, 0, ?1)",
+            params![offset.to_string().into_bytes()],
+        )
+        .unwrap();
+    }
+}
+
+/// Sets the `schedVer` config key; collections without one are on the v1 scheduler.
+pub fn set_scheduler_version(path: &Path, version: i64) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT OR REPLACE INTO config (KEY, usn, mtime_secs, val) VALUES ('schedVer', 0, 0, ?1)",
+        params![version.to_string().into_bytes()],
+    )
+    .unwrap();
+}
+
+/// Adds the legacy `col.conf` JSON column (if missing) and sets it to `conf`.
+pub fn set_col_conf(path: &Path, conf: &str) {
+    let conn = Connection::open(path).unwrap();
+    let has_conf = conn
+        .prepare("SELECT 1 FROM pragma_table_info('col') WHERE name = 'conf'")
+        .unwrap()
+        .exists([])
+        .unwrap();
+    if !has_conf {
+        conn.execute("ALTER TABLE col ADD COLUMN conf text NOT NULL DEFAULT '{}'", []).unwrap();
+    }
+    conn.execute("UPDATE col SET conf = ?1", params![conf]).unwrap();
+}
+
+pub fn add_deck(path: &Path, id: i64, name: &str) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute("INSERT INTO decks (id, name) VALUES (?1, ?2)", params![id, name])
+        .unwrap();
+}
+
+pub fn add_note(path: &Path, id: i64) {
+    add_note_with(path, id, 1, "");
+}
+
+/// Adds a note of note type `mid` with `tags` stored as Anki does, e.g. `" leech verbs "`.
+pub fn add_note_with(path: &Path, id: i64, mid: i64, tags: &str) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT INTO notes (id, mid, tags) VALUES (?1, ?2, ?3)",
+        params![id, mid, tags],
+    )
+    .unwrap();
+}
+
+pub fn add_notetype(path: &Path, id: i64, name: &str) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute("INSERT INTO notetypes (id, name) VALUES (?1, ?2)", params![id, name])
+        .unwrap();
+}
+
+pub fn add_card(path: &Path, id: i64, nid: i64, did: i64) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT INTO cards (id, nid, did, mod, usn, type, queue, due, odid)
+         VALUES (?1, ?2, ?3, 0, 0, 2, 2, 100, 0)",
+        params![id, nid, did],
+    )
+    .unwrap();
+}
+
+/// Moves card `id` into filtered deck `did`, remembering its current deck in `odid` as Anki does.
+pub fn move_to_filtered_deck(path: &Path, id: i64, did: i64) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute("UPDATE cards SET odid = did, did = ?2 WHERE id = ?1", params![id, did])
+        .unwrap();
+}
+
+/// Adds a review of card `cid` at `id` (milliseconds since the epoch).
+pub fn add_review(path: &Path, id: i64, cid: i64) {
+    add_review_with_time(path, id, cid, 5000);
+}
+
+/// Adds a review that took `time` milliseconds to answer.
+pub fn add_review_with_time(path: &Path, id: i64, cid: i64, time: i64) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+         VALUES (?1, ?2, 0, 3, 1, 1, 2500, ?3, 1)",
+        params![id, cid, time],
+    )
+    .unwrap();
+}
+
+/// Adds a review whose `revlog.type` is `kind` (0 learn, 1 review, 2 relearn, 3 cram).
+pub fn add_review_of_type(path: &Path, id: i64, cid: i64, kind: i64) {
+    add_review(path, id, cid);
+    let conn = Connection::open(path).unwrap();
+    conn.execute("UPDATE revlog SET type = ?1 WHERE id = ?2", params![kind, id]).unwrap();
+}
+
+pub fn revlog_ids(path: &Path) -> Vec<i64> {
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT id FROM revlog ORDER BY id").unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<i64>, _>>()
+        .unwrap()
+}
+
+/// Returns `(id, time)` for every review, ordered by id.
+pub fn revlog_times(path: &Path) -> Vec<(i64, i64)> {
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT id, time FROM revlog ORDER BY id").unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<Vec<(i64, i64)>, _>>()
+        .unwrap()
+}
+
+/// Milliseconds for the local time `hh:mm` on the given day in `zone`, e.g. to
+/// place a review just after the rollover or just before UTC midnight.
+pub fn local_millis(zone: &str, year: i32, month: u32, day: u32, hour: u32, minute: u32) -> i64 {
+    use chrono::TimeZone;
+    let zone: chrono_tz::Tz = zone.parse().unwrap();
+    let local = chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap();
+    zone.from_local_datetime(&local).unwrap().timestamp_millis()
+}
+
+/// Milliseconds for noon UTC on the given day, which sits inside both the UTC
+/// day and the rollover window of that day for the timezones tests run under.
+/// It is the one time where UTC days and Anki days agree, so tests of day
+/// boundaries use `local_millis` instead.
+pub fn noon_utc_millis(year: i32, month: u32, day: u32) -> i64 {
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp_millis()
+}
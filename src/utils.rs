@@ -1,26 +1,289 @@
+use log::LevelFilter;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-pub fn log(verbose: bool, message: &str) {
-    if verbose {
-        println!("{}", message);
+/// Sets up logging to stderr: warnings only by default, debug messages with `-v` (or
+/// `verbose = true` in the config file), and SQL and timings as well with `-vv`. `RUST_LOG`, when
+/// set, takes precedence, e.g. `RUST_LOG=anki_streak_fixer=trace`.
+pub fn init_logger(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let mut builder = env_logger::Builder::new();
+    builder
+        .filter_level(LevelFilter::Warn)
+        .filter_module(env!("CARGO_CRATE_NAME"), level)
+        .format(|buf, record| writeln!(buf, "[{}] {}", record.level(), record.args()));
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
     }
+    // Only fails if a logger is already installed
+    let _ = builder.try_init();
 }
 
 pub fn replace_deck_delimiter(deck_name: &str) -> String {
     deck_name.replace('\u{001F}', "::")
 }
 
-// pub fn red_text(text: &str) -> String {
-//     format!("\x1b[31m{}\x1b[0m", text)
-// }
+/// Converts a user-facing `Parent::Child` deck name into the form Anki stores in `decks.name`,
+/// where levels are separated by the `0x1F` unit separator.
+pub fn to_internal_deck_name(deck_name: &str) -> String {
+    deck_name.replace("::", "\u{001F}")
+}
+
+/// Translates a deck glob such as `Language::*::Listening` into a SQL `LIKE` pattern over
+/// `decks.name`, escaped with `\`.
+///
+/// `*` matches any run of characters (including `::`) and `::` becomes the `0x1F` separator.
+pub fn deck_glob_to_like(glob: &str) -> String {
+    let mut pattern = String::new();
+    for c in to_internal_deck_name(glob).chars() {
+        match c {
+            '*' => pattern.push('%'),
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            _ => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// Translates a deck glob like `deck_glob_to_like` does, but into a case-sensitive SQLite `GLOB`
+/// pattern, for `--case-sensitive`. The `GLOB` wildcards `?` and `[` are matched literally.
+pub fn deck_glob_to_glob(glob: &str) -> String {
+    let mut pattern = String::new();
+    for c in to_internal_deck_name(glob).chars() {
+        match c {
+            '?' => pattern.push_str("[?]"),
+            '[' => pattern.push_str("[[]"),
+            _ => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// Whether all of `text` matches the glob `pattern`: `*` matches any run of characters, `?` any
+/// single one, and everything else only itself, with exact case.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The last `*` seen and how far into `text` it currently reaches
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p, t));
+            p += 1;
+        } else if pattern.get(p).is_some_and(|c| *c == '?' || *c == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` swallow one more character and try again
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Renders the UTF-8 bytes of `text` as space-separated hex pairs.
+pub fn hex_bytes(text: &str) -> String {
+    text.bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Turns ANSI colors on or off for everything printed afterwards.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether output should be colored: not with `--no-color`, nor when the `NO_COLOR` environment
+/// variable is set to a non-empty value (see <https://no-color.org>).
+pub fn color_wanted(no_color_flag: bool) -> bool {
+    !no_color_flag && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+fn paint(code: &str, text: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn red_text(text: &str) -> String {
+    paint("31", text, COLOR.load(Ordering::Relaxed))
+}
+
+pub fn green_text(text: &str) -> String {
+    paint("32", text, COLOR.load(Ordering::Relaxed))
+}
+
+pub fn yellow_text(text: &str) -> String {
+    paint("33", text, COLOR.load(Ordering::Relaxed))
+}
+
+/// One deck's line in the summary table.
+#[derive(Debug, PartialEq)]
+pub struct SummaryRow {
+    pub deck: String,
+    pub notes: usize,
+    pub cards: usize,
+}
+
+/// Renders the per-deck counts as an aligned table with the deck names in green, followed by
+/// `message` and, if any reviews were skipped, a line about them in red.
+pub fn summary_table(rows: &[SummaryRow], message: &str, skipped: usize, color: bool) -> String {
+    let mut lines = Vec::new();
+    if !rows.is_empty() {
+        let width = rows
+            .iter()
+            .map(|row| row.deck.chars().count())
+            .chain(std::iter::once("Deck".len()))
+            .max()
+            .unwrap_or_default();
+        lines.push(format!("{:<width$}  {:>5}  {:>5}", "Deck", "Notes", "Cards"));
+        for row in rows {
+            // Pad before coloring so the escape codes don't count towards the width
+            let deck = format!("{:<width$}", row.deck);
+            lines.push(format!("{}  {:>5}  {:>5}", paint("32", &deck, color), row.notes, row.cards));
+        }
+    }
+    lines.push(message.to_string());
+    if skipped > 0 {
+        lines.push(paint(
+            "31",
+            &format!("Skipped {} reviews that would have been moved before their card was created.", skipped),
+            color,
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Prints `summary_table`, colored unless colors were turned off.
+pub fn print_summary(rows: &[SummaryRow], message: &str, skipped: usize) {
+    println!("{}", summary_table(rows, message, skipped, COLOR.load(Ordering::Relaxed)));
+}
+
+/// A `done/total` note counter redrawn in place, so long runs show they are still working.
+///
+/// Drawing failures are ignored: the counter is cosmetic and must never abort a run.
+pub struct Progress<W: Write> {
+    out: Option<W>, // `None` when the counter is suppressed
+    label: String,
+    total: usize,
+    done: usize,
+    drawn: bool, // Whether the counter is currently on screen
+}
+
+impl Progress<io::Stderr> {
+    /// A counter on stderr, shown only if `enabled` and stderr is a terminal, so it never ends
+    /// up in redirected output or next to `--json`.
+    pub fn stderr(label: &str, total: usize, enabled: bool) -> Self {
+        let stderr = io::stderr();
+        let out = (enabled && stderr.is_terminal()).then_some(stderr);
+        Self::with_output(out, label, total)
+    }
+}
+
+impl<W: Write> Progress<W> {
+    /// A counter drawn on `out`, or suppressed when it is `None`.
+    pub fn with_output(out: Option<W>, label: &str, total: usize) -> Self {
+        Self { out, label: label.to_string(), total, done: 0, drawn: false }
+    }
+
+    /// Removes the counter from the screen before other output is printed; the next `advance`
+    /// draws it again.
+    pub fn clear(&mut self) {
+        if let (Some(out), true) = (self.out.as_mut(), self.drawn) {
+            let _ = write!(out, "\r\x1b[2K");
+            let _ = out.flush();
+            self.drawn = false;
+        }
+    }
 
-// pub fn green_text(text: &str) -> String {
-//     format!("\x1b[32m{}\x1b[0m", text)
-// }
+    /// Counts one more item done and redraws the counter.
+    pub fn advance(&mut self) {
+        self.done += 1;
+        if let Some(out) = self.out.as_mut() {
+            let _ = write!(out, "\r\x1b[2K{} {}/{}", self.label, self.done, self.total);
+            let _ = out.flush();
+            self.drawn = true;
+        }
+    }
+
+    /// Clears the counter for good once the work is done.
+    pub fn finish(&mut self) {
+        self.clear();
+    }
+
+    #[cfg(test)]
+    fn into_output(mut self) -> Option<W> {
+        self.out.take()
+    }
+}
+
+impl<W: Write> Drop for Progress<W> {
+    /// Clears the counter when a run stops early, so an error message starts on its own line.
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_progress_counts_and_clears() {
+        let mut progress = Progress::with_output(Some(Vec::new()), "Notes", 2);
+        progress.advance();
+        progress.clear();
+        progress.clear();
+        progress.advance();
+        progress.finish();
+        let output = String::from_utf8(progress.into_output().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "\r\x1b[2KNotes 1/2\r\x1b[2K\r\x1b[2KNotes 2/2\r\x1b[2K"
+        );
+
+        let mut hidden: Progress<Vec<u8>> = Progress::with_output(None, "Notes", 2);
+        hidden.advance();
+        hidden.finish();
+        assert!(hidden.into_output().is_none());
+    }
+
+    #[test]
+    fn test_summary_table_aligns_columns() {
+        let rows = vec![
+            SummaryRow { deck: "Русский".to_string(), notes: 2, cards: 3 },
+            SummaryRow { deck: "French::Verbs".to_string(), notes: 10, cards: 12 },
+        ];
+        assert_eq!(
+            summary_table(&rows, "Moved 15 reviews.", 1, false),
+            "Deck           Notes  Cards\n\
+             Русский            2      3\n\
+             French::Verbs     10     12\n\
+             Moved 15 reviews.\n\
+             Skipped 1 reviews that would have been moved before their card was created."
+        );
+        assert!(summary_table(&rows, "", 0, true).contains("\x1b[32mFrench::Verbs\x1b[0m"));
+        assert_eq!(summary_table(&[], "Nothing moved.", 0, true), "Nothing moved.");
+    }
+
     #[test]
     fn test_replace_deck_delimiter_single_occurrence() {
         let input = "Deck\u{001F}SubDeck";
@@ -48,4 +311,44 @@ mod tests {
         let expected = "";
         assert_eq!(replace_deck_delimiter(input), expected);
     }
+
+    #[test]
+    fn test_to_internal_deck_name() {
+        assert_eq!(to_internal_deck_name("Deck::SubDeck::SubSubDeck"), "Deck\u{001F}SubDeck\u{001F}SubSubDeck");
+        assert_eq!(to_internal_deck_name("Deck"), "Deck");
+        assert_eq!(replace_deck_delimiter(&to_internal_deck_name("Deck::SubDeck")), "Deck::SubDeck");
+    }
+
+    #[test]
+    fn test_deck_glob_to_like() {
+        assert_eq!(deck_glob_to_like("Language::*::Listening"), "Language\u{001F}%\u{001F}Listening");
+        assert_eq!(deck_glob_to_like("100%_done*"), "100\\%\\_done%");
+        assert_eq!(deck_glob_to_like("Spanish"), "Spanish");
+    }
+
+    #[test]
+    fn test_deck_glob_to_glob() {
+        assert_eq!(deck_glob_to_glob("Language::*::Listening"), "Language\u{001F}*\u{001F}Listening");
+        assert_eq!(deck_glob_to_glob("Why?[1]*"), "Why[?][[]1]*");
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("Lang*", "Languages"));
+        assert!(glob_matches("*an*", "Spanish"));
+        assert!(glob_matches("User ?", "User 1"));
+        assert!(glob_matches("Рус*", "Русский"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("User ?", "User 10"));
+        assert!(!glob_matches("lang*", "Languages"));
+        assert!(!glob_matches("a*b", "aXbY"));
+        assert!(!glob_matches("", "x"));
+    }
+
+    #[test]
+    fn test_hex_bytes() {
+        assert_eq!(hex_bytes("a\u{001F}b"), "61 1f 62");
+        assert_eq!(hex_bytes(""), "");
+    }
 }
@@ -0,0 +1,167 @@
+//! Runs the built binary to check what it prints, which the library tests cannot see.
+
+mod common;
+
+use common::{noon_utc_millis, Collection};
+use std::process::{Command, Output};
+
+/// Runs the tool with `args`, keeping its config and state files in the collection's directory.
+fn run(collection: &Collection, args: &[&str]) -> Output {
+    let home = collection.path().parent().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_anki_streak_fixer"))
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Failed to run the binary")
+}
+
+#[test]
+fn quiet_prints_only_the_summary() {
+    let collection = Collection::new(4);
+    collection
+        .deck(1, "Spanish")
+        .card(10, 100, 1)
+        .review(noon_utc_millis(2025, 1, 3), 100);
+    let path = collection.path().to_str().unwrap();
+
+    let output = run(
+        &collection,
+        &[
+            "fix", "--collection-path", path, "--all", "--from", "2025-01-03", "--to", "2025-01-02",
+            "--timezone", "UTC", "--no-backup", "--yes", "--apply", "--quiet",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "unexpected output: {stdout}");
+    assert_eq!(collection.revlog_ids(), vec![noon_utc_millis(2025, 1, 2)]);
+}
+
+#[test]
+fn quiet_conflicts_with_verbose() {
+    let collection = Collection::new(4);
+    let output = run(&collection, &["-q", "-v", "fix", "--all"]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn date_validation_prints_nothing() {
+    let collection = Collection::new(4);
+    collection
+        .deck(1, "Spanish")
+        .card(10, 100, 1)
+        .review(noon_utc_millis(2025, 1, 3), 100);
+    let path = collection.path().to_str().unwrap();
+
+    // --count-only prints just the count, so any output from checking the dates would show up
+    let output = run(
+        &collection,
+        &[
+            "fix", "--collection-path", path, "--all", "--from", "2025-01-03", "--to", "2025-01-02",
+            "--timezone", "UTC", "--count-only",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+}
+
+#[test]
+fn undo_json_prints_a_summary() {
+    let collection = Collection::new(4);
+    let review = noon_utc_millis(2025, 1, 3);
+    collection.deck(1, "Spanish").card(10, 100, 1).review(review, 100);
+    let path = collection.path().to_str().unwrap();
+
+    let fix = run(
+        &collection,
+        &[
+            "fix", "--collection-path", path, "--all", "--from", "2025-01-03", "--to", "2025-01-02",
+            "--timezone", "UTC", "--no-backup", "--yes", "--apply", "--json",
+        ],
+    );
+    assert!(fix.status.success(), "{}", String::from_utf8_lossy(&fix.stderr));
+    let fix: serde_json::Value = serde_json::from_slice(&fix.stdout).unwrap();
+    let journal = fix["journal"].as_str().unwrap();
+
+    let undo = run(&collection, &["undo", journal, "--collection-path", path, "--no-backup", "--json"]);
+    assert!(undo.status.success(), "{}", String::from_utf8_lossy(&undo.stderr));
+    let undo: serde_json::Value = serde_json::from_slice(&undo.stdout).unwrap();
+    assert_eq!(undo["journal"], journal);
+    assert_eq!(undo["simulate"], false);
+    assert_eq!(undo["restored_reviews"], 1);
+    assert_eq!(collection.revlog_ids(), vec![review]);
+}
+
+#[test]
+fn backfill_writes_to_the_target_collection() {
+    let collection = Collection::new(4);
+    collection
+        .deck(1, "Spanish")
+        .card(10, 100, 1)
+        .review(noon_utc_millis(2025, 1, 1), 100);
+    let path = collection.path().to_str().unwrap();
+    let target = collection.path().with_file_name("copy.anki2");
+
+    let output = run(
+        &collection,
+        &[
+            "fix", "--collection-path", path, "--all", "--backfill", "2025-01-02", "--timezone", "UTC",
+            "--no-backup", "--yes", "--apply", "--target-collection", target.to_str().unwrap(),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(collection.revlog_ids(), vec![noon_utc_millis(2025, 1, 1)]);
+    let reviews: i64 = rusqlite::Connection::open(&target)
+        .unwrap()
+        .query_row("SELECT COUNT(*) FROM revlog", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(reviews, 2);
+}
+
+#[test]
+fn bad_arguments_exit_with_an_error() {
+    let collection = Collection::new(4);
+    let path = collection.path().to_str().unwrap();
+
+    let cases = [
+        (["--from", "2025-01-02", "--to", "2025-01-03"], "Invalid date range"),
+        (["--from", "2025-01-03", "--to", "2024-01-03"], "--max-offset-days"),
+        (["--from", "2025-01-03", "--to", "2025-01-02"], "already exists"),
+    ];
+    for (dates, message) in cases {
+        let mut args = vec!["fix", "--collection-path", path, "--all", "--target-collection", path];
+        args.extend(dates);
+        let output = run(&collection, &args);
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("[ERROR]") && stderr.contains(message), "unexpected error output: {stderr}");
+    }
+}
+
+#[test]
+fn unreadable_inputs_exit_with_an_error() {
+    let collection = Collection::new(4);
+    let path = collection.path().to_str().unwrap();
+    let missing_journal = collection.path().with_file_name("missing.json");
+    let missing_collection = collection.path().with_file_name("missing.anki2");
+
+    let cases = [
+        (vec!["undo", missing_journal.to_str().unwrap(), "--collection-path", path], "Cannot read journal"),
+        (vec!["fix", "--collection-path", missing_collection.to_str().unwrap(), "--all"], "does not exist"),
+    ];
+    for (args, message) in cases {
+        let output = run(&collection, &args);
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("[ERROR]") && stderr.contains(message), "unexpected error output: {stderr}");
+    }
+}
@@ -0,0 +1,129 @@
+//! A minimal Anki collection on disk for the integration tests.
+//!
+//! Only the tables and columns the tool reads or writes are created, with the same layout as
+//! current Anki, so the processor runs its real queries against it.
+
+// Each test binary uses only some of the builder methods
+#![allow(dead_code)]
+
+use anki_streak_fixer::open_database_with_collation;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Builds a collection in a temporary directory, which is removed when this is dropped.
+pub struct Collection {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl Collection {
+    /// An empty collection whose Anki days start at `rollover` o'clock.
+    pub fn new(rollover: i64) -> Self {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("collection.anki2");
+        Connection::open(&path)
+            .and_then(|conn| {
+                conn.execute_batch(
+                    "
+                    CREATE TABLE col (id integer PRIMARY KEY, crt integer NOT NULL, mod integer NOT NULL,
+                        scm integer NOT NULL, ver integer NOT NULL);
+                    CREATE TABLE config (KEY text NOT NULL PRIMARY KEY, usn integer NOT NULL,
+                        mtime_secs integer NOT NULL, val blob NOT NULL);
+                    CREATE TABLE decks (id integer PRIMARY KEY NOT NULL, name text NOT NULL);
+                    CREATE TABLE notes (id integer PRIMARY KEY, mid integer NOT NULL, tags text NOT NULL);
+                    CREATE TABLE cards (id integer PRIMARY KEY, nid integer NOT NULL, did integer NOT NULL,
+                        mod integer NOT NULL, usn integer NOT NULL, type integer NOT NULL,
+                        queue integer NOT NULL, due integer NOT NULL, odid integer NOT NULL);
+                    CREATE TABLE revlog (id integer PRIMARY KEY, cid integer NOT NULL, usn integer NOT NULL,
+                        ease integer NOT NULL, ivl integer NOT NULL, lastIvl integer NOT NULL,
+                        factor integer NOT NULL, time integer NOT NULL, type integer NOT NULL);
+                    INSERT INTO col (id, crt, mod, scm, ver) VALUES (1, 0, 0, 0, 18);
+                    ",
+                )?;
+                conn.execute(
+                    "INSERT INTO config (KEY, usn, mtime_secs, val) VALUES ('rollover', 0, 0, ?1)",
+                    params![rollover.to_string().into_bytes()],
+                )
+            })
+            .expect("Failed to create collection");
+        Self { _dir: dir, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Opens the collection the way the tool does, with the `unicase` collation registered.
+    pub fn conn(&self) -> Connection {
+        open_database_with_collation(self.path.to_str().unwrap()).expect("Failed to open collection")
+    }
+
+    /// Sets `col.crt`, the creation time in seconds that v1-scheduler collections count days from.
+    pub fn creation_time(&self, crt: i64) -> &Self {
+        self.conn().execute("UPDATE col SET crt = ?1", params![crt]).unwrap();
+        self
+    }
+
+    /// Adds a deck; `name` uses `::` between levels and is stored with Anki's `0x1F` separator.
+    pub fn deck(&self, id: i64, name: &str) -> &Self {
+        self.conn()
+            .execute("INSERT INTO decks (id, name) VALUES (?1, ?2)", params![id, name.replace("::", "\u{1f}")])
+            .unwrap();
+        self
+    }
+
+    /// Adds note `nid` with a single card `cid` in deck `did`.
+    pub fn card(&self, nid: i64, cid: i64, did: i64) -> &Self {
+        let conn = self.conn();
+        conn.execute("INSERT OR IGNORE INTO notes (id, mid, tags) VALUES (?1, 1, '')", params![nid])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO cards (id, nid, did, mod, usn, type, queue, due, odid) VALUES (?1, ?2, ?3, 0, 0, 2, 2, 100, 0)",
+            params![cid, nid, did],
+        )
+        .unwrap();
+        self
+    }
+
+    /// Adds a review of card `cid` at `id` milliseconds since the epoch.
+    pub fn review(&self, id: i64, cid: i64) -> &Self {
+        self.conn()
+            .execute(
+                "INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+                 VALUES (?1, ?2, 0, 3, 1, 1, 2500, 5000, 1)",
+                params![id, cid],
+            )
+            .unwrap();
+        self
+    }
+
+    /// Every revlog id, in order.
+    pub fn revlog_ids(&self) -> Vec<i64> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT id FROM revlog ORDER BY id").unwrap();
+        let ids = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<Vec<i64>, _>>().unwrap();
+        ids
+    }
+}
+
+/// Milliseconds since the epoch at the local time `hh:mm` on the given day in `zone`.
+pub fn local_millis(zone: &str, year: i32, month: u32, day: u32, hour: u32, minute: u32) -> i64 {
+    use chrono::TimeZone;
+    let zone: chrono_tz::Tz = zone.parse().unwrap();
+    let local = chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap();
+    zone.from_local_datetime(&local).unwrap().timestamp_millis()
+}
+
+/// Milliseconds since the epoch at noon UTC on the given day.
+pub fn noon_utc_millis(year: i32, month: u32, day: u32) -> i64 {
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp_millis()
+}
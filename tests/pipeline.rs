@@ -0,0 +1,215 @@
+//! Runs the whole pipeline, from deck selection through the review queries to the moved revlog
+//! ids, against collections built on disk.
+
+mod common;
+
+use anki_streak_fixer::date::Zone;
+use anki_streak_fixer::error::AppError;
+use anki_streak_fixer::{AnkiProcessor, AppConfig, AppMode};
+use chrono::NaiveDate;
+use common::{local_millis, noon_utc_millis, Collection};
+
+const DAY: i64 = 86_400_000;
+
+/// Moves the reviews of 2025-01-03 back to 2025-01-02 in the given decks, counting days in UTC.
+fn config_for(mode: AppMode) -> AppConfig {
+    AppConfig {
+        mode,
+        timezone: Zone::parse("UTC").unwrap(),
+        backup: false,
+        json: true, // Keeps the progress output out of the test log
+        ..Default::default()
+    }
+}
+
+fn processor<'a>(collection: &Collection, config: &'a AppConfig) -> AnkiProcessor<'a> {
+    AnkiProcessor::new(
+        collection.path().to_path_buf(),
+        false,
+        0,
+        NaiveDate::from_ymd_opt(2025, 1, 3),
+        NaiveDate::from_ymd_opt(2025, 1, 2),
+        config,
+    )
+}
+
+fn decks(names: &[&str]) -> AppMode {
+    AppMode::Decks(names.iter().map(|name| name.to_string()).collect())
+}
+
+#[test]
+fn parent_deck_includes_its_subdecks() {
+    let collection = Collection::new(4);
+    let review = noon_utc_millis(2025, 1, 3);
+    collection
+        .deck(1, "Spanish")
+        .deck(2, "Spanish::Verbs")
+        .deck(3, "French")
+        .deck(4, "Spanish Extra")
+        .card(10, 100, 1)
+        .card(11, 101, 2)
+        .card(12, 102, 3)
+        .card(13, 103, 4)
+        .review(review, 100)
+        .review(review + 1000, 101)
+        .review(review + 2000, 102)
+        .review(review + 3000, 103);
+
+    let config = config_for(decks(&["Spanish"]));
+    assert_eq!(processor(&collection, &config).count_notes().unwrap(), 2);
+    let report = processor(&collection, &config).process().unwrap();
+
+    assert_eq!(report.summary.notes, 2);
+    assert_eq!(
+        collection.revlog_ids(),
+        vec![review - DAY, review + 1000 - DAY, review + 2000, review + 3000]
+    );
+}
+
+#[test]
+fn subdeck_alone_leaves_its_parent() {
+    let collection = Collection::new(4);
+    let review = noon_utc_millis(2025, 1, 3);
+    collection
+        .deck(1, "Spanish")
+        .deck(2, "Spanish::Verbs")
+        .card(10, 100, 1)
+        .card(11, 101, 2)
+        .review(review, 100)
+        .review(review + 1000, 101);
+
+    let config = config_for(decks(&["Spanish::Verbs"]));
+    processor(&collection, &config).process().unwrap();
+
+    assert_eq!(collection.revlog_ids(), vec![review + 1000 - DAY, review]);
+}
+
+#[test]
+fn unicode_deck_names_match_regardless_of_case() {
+    let collection = Collection::new(4);
+    let review = noon_utc_millis(2025, 1, 3);
+    collection
+        .deck(1, "Русский::Глаголы")
+        .deck(2, "日本語")
+        .deck(3, "Русский")
+        .card(10, 100, 1)
+        .card(11, 101, 2)
+        .card(12, 102, 3)
+        .review(review, 100)
+        .review(review + 1000, 101)
+        .review(review + 2000, 102);
+
+    let config = config_for(decks(&["русский::глаголы", "日本語"]));
+    let report = processor(&collection, &config).process().unwrap();
+
+    assert_eq!(report.note_ids, vec![10, 11]);
+    assert_eq!(
+        collection.revlog_ids(),
+        vec![review - DAY, review + 1000 - DAY, review + 2000]
+    );
+}
+
+#[test]
+fn all_decks_respect_the_rollover() {
+    let collection = Collection::new(4);
+    // 03:00 on Jan 4 still belongs to the Anki day of Jan 3; 05:00 on Jan 3 starts it
+    let late = noon_utc_millis(2025, 1, 4) - 9 * 3_600_000;
+    let early = noon_utc_millis(2025, 1, 3) - 7 * 3_600_000;
+    let before = early - 3 * 3_600_000;
+    collection
+        .deck(1, "Default")
+        .card(10, 100, 1)
+        .review(before, 100)
+        .review(early, 100)
+        .review(late, 100);
+
+    let config = config_for(AppMode::All);
+    processor(&collection, &config).process().unwrap();
+
+    assert_eq!(collection.revlog_ids(), vec![early - DAY, before, late - DAY]);
+}
+
+#[test]
+fn legacy_collections_count_days_from_crt() {
+    // Created at 04:00 in Tokyo with no schedVer, so the v1 scheduler starts Anki days at
+    // 19:00 UTC the evening before
+    let collection = Collection::new(4);
+    let hour = 3_600_000;
+    // 06:00 in Tokyo on Jan 3, before UTC midnight has passed
+    let early = noon_utc_millis(2025, 1, 2) + 9 * hour;
+    // 03:00 in Tokyo on Jan 3, still the Anki day of Jan 2
+    let before = early - 3 * hour;
+    collection
+        .creation_time(1_577_818_800)
+        .deck(1, "Default")
+        .card(10, 100, 1)
+        .card(11, 101, 1)
+        .review(before, 100)
+        .review(early, 101);
+
+    // No --timezone, so the offset implied by crt applies
+    let config = AppConfig { timezone: Zone::Local, ..config_for(AppMode::All) };
+    assert_eq!(processor(&collection, &config).count_notes().unwrap(), 1);
+    let report = processor(&collection, &config).process().unwrap();
+
+    assert_eq!(report.note_ids, vec![11]);
+    assert_eq!(collection.revlog_ids(), vec![early - DAY, before]);
+}
+
+#[test]
+fn reviews_between_the_rollover_and_utc_midnight_move() {
+    // In Tokyo the Anki day of Jan 3 runs from 19:00 UTC on Jan 2 to 19:00 UTC on Jan 3
+    let collection = Collection::new(4);
+    let after_rollover = local_millis("Asia/Tokyo", 2025, 1, 3, 4, 1);
+    let before_utc_midnight = local_millis("Asia/Tokyo", 2025, 1, 3, 8, 59);
+    let previous_day = local_millis("Asia/Tokyo", 2025, 1, 3, 3, 59);
+    let next_day = local_millis("Asia/Tokyo", 2025, 1, 4, 4, 1);
+    collection
+        .deck(1, "Default")
+        .card(10, 100, 1)
+        .card(11, 101, 1)
+        .card(12, 102, 1)
+        .card(13, 103, 1)
+        .review(after_rollover, 100)
+        .review(before_utc_midnight, 101)
+        .review(previous_day, 102)
+        .review(next_day, 103);
+
+    let config = AppConfig { timezone: Zone::parse("Asia/Tokyo").unwrap(), ..config_for(AppMode::All) };
+    assert_eq!(processor(&collection, &config).count_notes().unwrap(), 2);
+    let report = processor(&collection, &config).process().unwrap();
+
+    assert_eq!(report.note_ids, vec![10, 11]);
+    assert_eq!(
+        collection.revlog_ids(),
+        vec![after_rollover - DAY, before_utc_midnight - DAY, previous_day, next_day]
+    );
+}
+
+#[test]
+fn no_reviews_on_the_day_moves_nothing() {
+    let collection = Collection::new(4);
+    let review = noon_utc_millis(2025, 1, 1);
+    collection.deck(1, "Spanish").card(10, 100, 1).review(review, 100);
+    let scm = || -> i64 { collection.conn().query_row("SELECT scm FROM col", [], |row| row.get(0)).unwrap() };
+
+    let config = config_for(decks(&["Spanish"]));
+    assert_eq!(processor(&collection, &config).count_notes().unwrap(), 0);
+    let report = processor(&collection, &config).process().unwrap();
+
+    assert_eq!(report.summary.notes, 0);
+    assert!(report.revlog_ids.is_empty());
+    assert_eq!(collection.revlog_ids(), vec![review]);
+    assert_eq!(scm(), 0);
+}
+
+#[test]
+fn unknown_deck_is_an_error() {
+    let collection = Collection::new(4);
+    collection.deck(1, "Spanish");
+
+    let config = config_for(decks(&["Italian"]));
+    let err = processor(&collection, &config).process().unwrap_err();
+
+    assert!(matches!(err, AppError::NoMatchingDeck(_)), "unexpected error: {:?}", err);
+}